@@ -454,6 +454,8 @@ impl Allowed {
                         SysOpClass::Images => ("System - Images".into_view(), "Work with static images"),
                         SysOpClass::Gifs => ("System - GIFs".into_view(), "Work with animated GIFs"),
                         SysOpClass::Tcp => ("System - TCP".into_view(), "Work with TCP sockets"),
+                        SysOpClass::Udp => ("System - UDP".into_view(), "Work with UDP sockets"),
+                        SysOpClass::Ffi => ("System - FFI".into_view(), "Call C functions in shared libraries"),
                         SysOpClass::Misc => ("System - Misc".into_view(), ""),
                     }
                 }
@@ -1196,6 +1196,10 @@ fn TutorialTesting() -> impl IntoView {
         <p>"If the result does not match the expectation, that incorrect result will be thrown."</p>
         <Editor example="---\n⍤∶≍, 4 +2 2 # Passes\n---"/>
         <Editor example="---\n⍤∶≍, [2 3 5] +1 [1 2 3]\n--- #  ↓↓↓↓↓↓↓"/> // Should fail
+        <p>"For a more helpful error, use "<Prim prim=Expect/>". When the arrays do not match, it shows both of them along with where they first differ."</p>
+        <Editor example="---\nexpect [2 3 5] +1 [1 2 3]\n---"/> // Should fail
+        <p>"Use "<Prim prim=Fill/>" to allow numbers to differ by a small amount."</p>
+        <Editor example="---\n⬚0.000001expect 0.3 +0.1 0.2\n---"/>
 
         <h2 id="run-modes">"Run Modes"</h2>
         <p>"Whether tests will run or not depends on how you run the code."</p>
@@ -0,0 +1,406 @@
+//! Algorithms for derivative
+//!
+//! [`derivative`](crate::Primitive::Derivative) uses forward-mode automatic differentiation.
+//! The function's instructions are transformed to work on dual numbers, where each number
+//! is paired with its derivative, or tangent. A dual array is a number array with an extra
+//! last axis of length 2 that holds the primal values and the tangents.
+
+use std::sync::Arc;
+
+use ecow::EcoVec;
+
+use crate::{
+    algorithm::{invert::InvertPattern, reduce, table, zip},
+    array::{Array, Shape},
+    function::{Function, FunctionId, Instr, Signature},
+    primitive::{ImplPrimitive, Primitive},
+    value::Value,
+    Uiua, UiuaResult,
+};
+
+impl Function {
+    /// Get the function's derivative with respect to its first argument
+    pub fn derivative(&self, env: &Uiua) -> UiuaResult<Self> {
+        let instrs = derivative_instrs(&self.instrs, self.signature(), env.span_index())
+            .map_err(|e| env.error(e))?;
+        Ok(Function::new(self.id.clone(), instrs, self.signature()))
+    }
+}
+
+/// Get the instructions that compute the derivative of a function with respect to its first argument
+///
+/// The first argument is seeded with a tangent of 1, the other arguments are treated as
+/// constants, and the tangent of each output is returned.
+pub(crate) fn derivative_instrs(
+    instrs: &[Instr],
+    sig: Signature,
+    span: usize,
+) -> Result<Vec<Instr>, String> {
+    let mut derivative = Vec::new();
+    for i in 0..sig.args {
+        let prim = if i == 0 {
+            ImplPrimitive::DualSeed
+        } else {
+            ImplPrimitive::DualConst
+        };
+        derivative.push(Instr::ImplPrim(prim, span));
+        if i + 1 < sig.args {
+            derivative.push(Instr::PushTempInline { count: 1, span });
+        }
+    }
+    for _ in 1..sig.args {
+        derivative.push(Instr::PopTempInline { count: 1, span });
+    }
+    derivative.extend(dual_instrs(instrs)?);
+    for i in 0..sig.outputs {
+        derivative.push(Instr::ImplPrim(ImplPrimitive::DualTangent, span));
+        if i + 1 < sig.outputs {
+            derivative.push(Instr::PushTempInline { count: 1, span });
+        }
+    }
+    for _ in 1..sig.outputs {
+        derivative.push(Instr::PopTempInline { count: 1, span });
+    }
+    Ok(derivative)
+}
+
+/// Transform instructions to work on dual arrays
+///
+/// Returns an error naming the first instruction that cannot be differentiated.
+fn dual_instrs(mut instrs: &[Instr]) -> Result<Vec<Instr>, String> {
+    use ImplPrimitive::*;
+    use Primitive::*;
+
+    let patterns: &[&dyn InvertPattern] = &[
+        &([Add], [DualAdd]),
+        &([Sub], [DualSub]),
+        &([Mul], [DualMul]),
+        &([Div], [DualDiv]),
+        &([Pow], [DualPow]),
+        &([Log], [DualLog]),
+        &([Neg], [DualNeg]),
+        &([Abs], [DualAbs]),
+        &([Sqrt], [DualSqrt]),
+        &([Sin], [DualSin]),
+        &([Table], [DualTable]),
+        &([Reduce], [DualReduce]),
+        &([Rows], [DualRows]),
+    ];
+
+    let mut dual = Vec::new();
+    'find_pattern: while let Some((instr, rest)) = instrs.split_first() {
+        for pattern in patterns {
+            if let Some((input, transformed)) = pattern.invert_extract(instrs) {
+                dual.extend(transformed);
+                instrs = input;
+                continue 'find_pattern;
+            }
+        }
+        match instr {
+            Instr::Push(val) => match to_dual(val, 0.0) {
+                Some(val) => dual.push(Instr::push(val)),
+                None => {
+                    return Err(format!(
+                        "Cannot differentiate a function with a {} constant",
+                        val.type_name()
+                    ))
+                }
+            },
+            Instr::PushFunc(f) => {
+                let instrs = dual_instrs(&f.instrs)?;
+                // Keeping a primitive's id would make modifiers use their fast paths for numbers
+                let id = match &f.id {
+                    FunctionId::Primitive(_) => FunctionId::Unnamed,
+                    id => id.clone(),
+                };
+                let f = Function::new(id, instrs, f.signature());
+                dual.push(Instr::PushFunc(Arc::new(f)));
+            }
+            // Instructions that only move values around work on dual arrays as-is
+            Instr::Prim(
+                Dup | Flip | Over | Pop | Identity | Dip | Gap | Oust | Fork | Bracket | Both,
+                _,
+            )
+            | Instr::ImplPrim(Cos, _)
+            | Instr::BeginArray
+            | Instr::EndArray { boxed: false, .. }
+            | Instr::Call(_)
+            | Instr::PushTempInline { .. }
+            | Instr::PopTempInline { .. }
+            | Instr::CopyTempInline { .. }
+            | Instr::DropTempInline { .. }
+            | Instr::PushTempUnder { .. }
+            | Instr::PopTempUnder { .. } => dual.push(match instr {
+                Instr::ImplPrim(Cos, span) => Instr::ImplPrim(DualCos, *span),
+                instr => instr.clone(),
+            }),
+            Instr::Prim(prim, _) => match prim.constant() {
+                Some(n) => dual.push(Instr::push(Array::from([n, 0.0].as_slice()))),
+                None => return Err(format!("Cannot differentiate {prim}")),
+            },
+            Instr::ImplPrim(prim, _) => return Err(format!("Cannot differentiate {prim}")),
+            Instr::EndArray { boxed: true, .. } => {
+                return Err("Cannot differentiate box arrays".into())
+            }
+            Instr::Switch { .. } => return Err("Cannot differentiate switch functions".into()),
+            Instr::Format { .. } => return Err("Cannot differentiate format strings".into()),
+            _ => return Err("Cannot differentiate this function".into()),
+        }
+        instrs = rest;
+    }
+    Ok(dual)
+}
+
+/// Turn a number array into a dual array with the given tangent
+fn to_dual(val: &Value, tangent: f64) -> Option<Value> {
+    let primal: Array<f64> = match val {
+        Value::Num(arr) => arr.clone(),
+        Value::Byte(arr) => arr.convert_ref(),
+        _ => return None,
+    };
+    let mut shape = primal.shape.clone();
+    shape.push(2);
+    let data: EcoVec<f64> = (primal.data.iter())
+        .flat_map(|&primal| [primal, tangent])
+        .collect();
+    Some(Array::new(shape, data).into())
+}
+
+/// Split a dual array into its primal values and tangents
+fn split(val: Value, env: &Uiua) -> UiuaResult<(Value, Value)> {
+    let arr = match val {
+        Value::Num(arr) if arr.shape.last() == Some(&2) => arr,
+        val => {
+            return Err(env.error(format!(
+                "Cannot differentiate a function that uses {} arrays",
+                val.type_name()
+            )))
+        }
+    };
+    let shape: Shape = arr.shape[..arr.rank() - 1].into();
+    let primal: EcoVec<f64> = arr.data.iter().step_by(2).copied().collect();
+    let tangent: EcoVec<f64> = arr.data.iter().skip(1).step_by(2).copied().collect();
+    Ok((
+        Array::new(shape.clone(), primal).into(),
+        Array::new(shape, tangent).into(),
+    ))
+}
+
+/// Join primal values and tangents into a dual array
+fn join(primal: Value, tangent: Value, env: &Uiua) -> UiuaResult<Value> {
+    let primal = to_nums(primal);
+    // The tangent may need to be broadcast to the primal's shape
+    let zeros = Array::new(
+        primal.shape.clone(),
+        EcoVec::from_elem(0.0, primal.data.len()),
+    );
+    let tangent = to_nums(Value::from(zeros).add(tangent, env)?);
+    let mut shape = primal.shape.clone();
+    shape.push(2);
+    let data: EcoVec<f64> = (primal.data.iter().zip(&tangent.data))
+        .flat_map(|(&primal, &tangent)| [primal, tangent])
+        .collect();
+    Ok(Array::new(shape, data).into())
+}
+
+fn to_nums(val: Value) -> Array<f64> {
+    match val {
+        Value::Num(arr) => arr,
+        Value::Byte(arr) => arr.convert(),
+        _ => unreachable!("dual values are always numbers"),
+    }
+}
+
+fn is_zero(val: &Value) -> bool {
+    match val {
+        Value::Num(arr) => arr.data.iter().all(|&x| x == 0.0),
+        _ => false,
+    }
+}
+
+fn ln(val: Value, env: &Uiua) -> UiuaResult<Value> {
+    Value::from(std::f64::consts::E).log(val, env)
+}
+
+/// Start differentiating with respect to a value
+pub fn seed(val: Value, env: &Uiua) -> UiuaResult<Value> {
+    to_dual(&val, 1.0).ok_or_else(|| {
+        env.error(format!(
+            "Cannot differentiate with respect to {} array",
+            val.type_name()
+        ))
+    })
+}
+
+/// Treat a value as a constant while differentiating
+pub fn constant(val: Value, env: &Uiua) -> UiuaResult<Value> {
+    to_dual(&val, 0.0).ok_or_else(|| {
+        env.error(format!(
+            "Cannot differentiate with a {} array argument",
+            val.type_name()
+        ))
+    })
+}
+
+/// Get the tangent of a dual array
+pub fn tangent(val: Value, env: &Uiua) -> UiuaResult<Value> {
+    Ok(split(val, env)?.1)
+}
+
+pub fn add(a: Value, b: Value, env: &Uiua) -> UiuaResult<Value> {
+    let ((a, da), (b, db)) = (split(a, env)?, split(b, env)?);
+    join(a.add(b, env)?, da.add(db, env)?, env)
+}
+
+pub fn sub(a: Value, b: Value, env: &Uiua) -> UiuaResult<Value> {
+    let ((a, da), (b, db)) = (split(a, env)?, split(b, env)?);
+    join(a.sub(b, env)?, da.sub(db, env)?, env)
+}
+
+pub fn mul(a: Value, b: Value, env: &Uiua) -> UiuaResult<Value> {
+    let ((a, da), (b, db)) = (split(a, env)?, split(b, env)?);
+    let tangent = (a.clone().mul(db, env)?).add(b.clone().mul(da, env)?, env)?;
+    join(a.mul(b, env)?, tangent, env)
+}
+
+pub fn div(a: Value, b: Value, env: &Uiua) -> UiuaResult<Value> {
+    let ((a, da), (b, db)) = (split(a, env)?, split(b, env)?);
+    // (a db - b da) / a²
+    let numerator = (b.clone().mul(da, env)?).sub(a.clone().mul(db, env)?, env)?;
+    let tangent = (a.clone().mul(a.clone(), env)?).div(numerator, env)?;
+    join(a.div(b, env)?, tangent, env)
+}
+
+pub fn pow(a: Value, b: Value, env: &Uiua) -> UiuaResult<Value> {
+    let ((a, da), (b, db)) = (split(a, env)?, split(b, env)?);
+    let primal = a.clone().pow(b.clone(), env)?;
+    // a b^(a - 1) db
+    let b_pow = (Value::from(1.0).sub(a.clone(), env)?).pow(b.clone(), env)?;
+    let mut tangent = (a.mul(b_pow, env)?).mul(db, env)?;
+    // The logarithm is only needed if the exponent is not constant
+    if !is_zero(&da) {
+        let log_term = (primal.clone().mul(ln(b, env)?, env)?).mul(da, env)?;
+        tangent = tangent.add(log_term, env)?;
+    }
+    join(primal, tangent, env)
+}
+
+pub fn log(a: Value, b: Value, env: &Uiua) -> UiuaResult<Value> {
+    let ((a, da), (b, db)) = (split(a, env)?, split(b, env)?);
+    let primal = a.clone().log(b.clone(), env)?;
+    let ln_a = ln(a.clone(), env)?;
+    // db / (b ln a)
+    let mut tangent = (b.clone().mul(ln_a.clone(), env)?).div(db, env)?;
+    // The base is usually constant
+    if !is_zero(&da) {
+        // ln b da / (a (ln a)²)
+        let numerator = (ln(b, env)?).mul(da, env)?;
+        let denominator = a.mul(ln_a.clone().mul(ln_a, env)?, env)?;
+        tangent = (denominator.div(numerator, env)?).sub(tangent, env)?;
+    }
+    join(primal, tangent, env)
+}
+
+pub fn neg(a: Value, env: &Uiua) -> UiuaResult<Value> {
+    let (a, da) = split(a, env)?;
+    join(a.neg(env)?, da.neg(env)?, env)
+}
+
+pub fn abs(a: Value, env: &Uiua) -> UiuaResult<Value> {
+    let (a, da) = split(a, env)?;
+    let tangent = a.clone().sign(env)?.mul(da, env)?;
+    join(a.abs(env)?, tangent, env)
+}
+
+pub fn sqrt(a: Value, env: &Uiua) -> UiuaResult<Value> {
+    let (a, da) = split(a, env)?;
+    let primal = a.sqrt(env)?;
+    let tangent = (Value::from(2.0).mul(primal.clone(), env)?).div(da, env)?;
+    join(primal, tangent, env)
+}
+
+pub fn sin(a: Value, env: &Uiua) -> UiuaResult<Value> {
+    let (a, da) = split(a, env)?;
+    let tangent = a.clone().cos(env)?.mul(da, env)?;
+    join(a.sin(env)?, tangent, env)
+}
+
+pub fn cos(a: Value, env: &Uiua) -> UiuaResult<Value> {
+    let (a, da) = split(a, env)?;
+    let tangent = a.clone().sin(env)?.neg(env)?.mul(da, env)?;
+    join(a.cos(env)?, tangent, env)
+}
+
+/// [`table`](crate::Primitive::Table) on dual arrays
+///
+/// The elements of a dual array are the rows of its flattened form,
+/// so this is [`cross`](crate::Primitive::Cross) on the flattened arrays.
+pub fn dual_table(env: &mut Uiua) -> UiuaResult {
+    let f = env.pop_function()?;
+    let mut xs = env.pop(1)?;
+    let mut ys = env.pop(2)?;
+    let mut shape = Shape::new();
+    for dual in [&mut xs, &mut ys] {
+        let dual_shape = dual.shape_mut();
+        let Some(primal_shape) = dual_shape.split_last().map(|(_, shape)| shape) else {
+            return Err(env.error("Cannot differentiate a function that uses a scalar in table"));
+        };
+        shape.extend_from_slice(primal_shape);
+        *dual_shape = [primal_shape.iter().product(), 2].into_iter().collect();
+    }
+    env.push(ys);
+    env.push(xs);
+    env.push_func(f);
+    table::cross(env)?;
+    let mut crossed = env.pop("dual table result")?;
+    shape.extend_from_slice(&crossed.shape()[2..]);
+    *crossed.shape_mut() = shape;
+    crossed.validate_shape();
+    env.push(crossed);
+    Ok(())
+}
+
+/// [`reduce`](crate::Primitive::Reduce) on dual arrays
+pub fn dual_reduce(env: &mut Uiua) -> UiuaResult {
+    let f = env.pop_function()?;
+    let xs = env.pop(1)?;
+    env.push(scalar_as_row(xs));
+    env.push_func(f);
+    reduce::reduce(env)
+}
+
+/// [`rows`](crate::Primitive::Rows) on dual arrays
+pub fn dual_rows(env: &mut Uiua) -> UiuaResult {
+    let f = env.pop_function()?;
+    let mut args = Vec::with_capacity(f.signature().args);
+    for i in 0..f.signature().args {
+        args.push(env.pop(i + 1)?);
+    }
+    for arg in args.into_iter().rev() {
+        env.push(scalar_as_row(arg));
+    }
+    env.push_func(f);
+    zip::rows(env)
+}
+
+/// Give a scalar's dual array an axis of length 1
+///
+/// `reduce` and `rows` treat a scalar as a single row, but a scalar's
+/// dual array has a row for its primal value and one for its tangent.
+fn scalar_as_row(mut dual: Value) -> Value {
+    if dual.rank() == 1 {
+        *dual.shape_mut() = [1, 2].into_iter().collect();
+    }
+    dual
+}
+
+#[test]
+fn derivative_errors() {
+    let mut env = Uiua::with_native_sys();
+    let err = env.load_str("∂⇌ [1 2 3]").unwrap_err();
+    assert!(err.message().ends_with("Cannot differentiate ⇌"));
+    let err = env.load_str("∂(⊂1) 2").unwrap_err();
+    assert!(err.message().ends_with("Cannot differentiate ⊂"));
+    let err = env.load_str("∂(+@a) 2").unwrap_err();
+    assert!(err.message().contains("character"));
+}
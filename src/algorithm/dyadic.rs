@@ -432,6 +432,32 @@ impl Value {
             Value::Box(a) => a.uncouple(env).map(|(a, b)| (a.into(), b.into())),
         }
     }
+    /// Split the value into the two values that were joined to make it
+    ///
+    /// `a_shape` and `b_shape` are the shapes of the joined values.
+    pub(crate) fn unjoin(
+        self,
+        a_shape: &Self,
+        b_shape: &Self,
+        env: &Uiua,
+    ) -> UiuaResult<(Self, Self)> {
+        let a_shape = a_shape.as_nats(env, "Shape must be a list of natural numbers")?;
+        let b_shape = b_shape.as_nats(env, "Shape must be a list of natural numbers")?;
+        match self {
+            Value::Num(arr) => arr
+                .unjoin(&a_shape, &b_shape, env)
+                .map(|(a, b)| (a.into(), b.into())),
+            Value::Byte(arr) => arr
+                .unjoin(&a_shape, &b_shape, env)
+                .map(|(a, b)| (a.into(), b.into())),
+            Value::Char(arr) => arr
+                .unjoin(&a_shape, &b_shape, env)
+                .map(|(a, b)| (a.into(), b.into())),
+            Value::Box(arr) => arr
+                .unjoin(&a_shape, &b_shape, env)
+                .map(|(a, b)| (a.into(), b.into())),
+        }
+    }
 }
 
 impl<T: ArrayValue> Array<T> {
@@ -482,6 +508,53 @@ impl<T: ArrayValue> Array<T> {
         let second = rows.next().unwrap();
         Ok((first, second))
     }
+    /// Split the array into the two arrays that were joined to make it
+    ///
+    /// An array with a lower rank than the joined array was joined as a single row.
+    pub(crate) fn unjoin(
+        self,
+        a_shape: &[usize],
+        b_shape: &[usize],
+        env: &Uiua,
+    ) -> UiuaResult<(Self, Self)> {
+        if self.rank() == 0 {
+            return Err(env.error("Cannot unjoin a scalar"));
+        }
+        let row_count = self.row_count();
+        let a_is_row = a_shape.len() < self.rank();
+        let b_is_row = b_shape.len() < self.rank();
+        let a_len = match (a_is_row, b_is_row) {
+            (true, true) => (row_count == 2).then_some(1),
+            (true, false) => (row_count >= 1).then_some(1),
+            (false, true) => row_count.checked_sub(1),
+            (false, false) => (a_shape[0] <= row_count).then_some(a_shape[0]),
+        };
+        let Some(a_len) = a_len else {
+            return Err(env.error(format!(
+                "Cannot unjoin array with {} row{} into arrays of shapes {} and {}",
+                row_count,
+                if row_count == 1 { "" } else { "s" },
+                FormatShape(a_shape),
+                FormatShape(b_shape),
+            )));
+        };
+        let b_len = row_count - a_len;
+        let row_shape = &self.shape[1..];
+        let part_shape = |len: usize, is_row: bool| -> Shape {
+            if is_row {
+                row_shape.into()
+            } else {
+                Some(len)
+                    .into_iter()
+                    .chain(row_shape.iter().copied())
+                    .collect()
+            }
+        };
+        let split = a_len * self.row_len();
+        let a = Array::new(part_shape(a_len, a_is_row), self.data.slice(..split));
+        let b = Array::new(part_shape(b_len, b_is_row), self.data.slice(split..));
+        Ok((a, b))
+    }
 }
 
 impl Value {
@@ -517,6 +590,7 @@ impl Value {
         let Some(mut value) = row_values.next() else {
             return Ok(Value::default());
         };
+        value.strip_map_keys();
         let mut count = 1;
         for row in row_values {
             count += 1;
@@ -579,6 +653,7 @@ impl Value {
     /// `reshape` this value with another
     pub fn reshape(&mut self, shape: &Self, env: &Uiua) -> UiuaResult {
         if let Ok(n) = shape.as_nat(env, "") {
+            env.check_value_alloc(self, n.saturating_mul(self.element_count()))?;
             match self {
                 Value::Num(a) => a.reshape_scalar(n),
                 Value::Byte(a) => a.reshape_scalar(n),
@@ -691,6 +766,7 @@ impl<T: ArrayValue> Array<T> {
                 return Err(env.error(format!("Cannot reshape array with {n} negative dimensions")))
             }
         };
+        env.check_alloc::<T>(shape.iter().fold(1, |len, &dim| len.saturating_mul(dim)))?;
         let target_len: usize = shape.iter().product();
         if self.data.len() < target_len {
             if let Some(fill) = env.fill::<T>() {
@@ -720,6 +796,53 @@ impl<T: ArrayValue> Array<T> {
     }
 }
 
+impl Value {
+    pub(crate) fn unreshape(self, shape: &Self, into: Self, env: &Uiua) -> UiuaResult<Self> {
+        let shape = shape.as_nats(env, "Shape must be a list of natural numbers")?;
+        Ok(match (self, into) {
+            (Value::Num(a), Value::Num(b)) => Value::Num(a.unreshape(&shape, b, env)?),
+            (Value::Byte(a), Value::Byte(b)) => Value::Byte(a.unreshape(&shape, b, env)?),
+            (Value::Char(a), Value::Char(b)) => Value::Char(a.unreshape(&shape, b, env)?),
+            (Value::Box(a), Value::Box(b)) => Value::Box(a.unreshape(&shape, b, env)?),
+            (Value::Num(a), Value::Byte(b)) => Value::Num(a.unreshape(&shape, b.convert(), env)?),
+            (Value::Byte(a), Value::Num(b)) => Value::Num(a.convert().unreshape(&shape, b, env)?),
+            (a, b) => {
+                return Err(env.error(format!(
+                    "Cannot unreshape {} into {}",
+                    a.type_name(),
+                    b.type_name()
+                )))
+            }
+        })
+    }
+}
+
+impl<T: ArrayValue> Array<T> {
+    /// Put a reshaped array's elements back into the array it was reshaped from
+    ///
+    /// Elements that the reshape dropped are left as they were.
+    fn unreshape(self, shape: &[usize], into: Self, env: &Uiua) -> UiuaResult<Self> {
+        let mut from = self;
+        let count: usize = shape.iter().product();
+        if from.data.len() != count {
+            return Err(env.error(format!(
+                "Attempted to undo reshape, but the reshaped array's \
+                element count was modified from {} to {}",
+                count,
+                from.data.len()
+            )));
+        }
+        if count > into.data.len() {
+            return Err(env.error(
+                "Attempted to undo reshape, but the reshape repeated elements, \
+                so it is unclear which ones to keep",
+            ));
+        }
+        from.data.extend_from_slice(&into.data[count..]);
+        Ok(Array::new(into.shape, from.data))
+    }
+}
+
 impl Value {
     /// Use this value as counts to `keep` another
     pub fn keep(&self, kept: Self, env: &Uiua) -> UiuaResult<Self> {
@@ -728,6 +851,14 @@ impl Value {
             "Keep amount must be a natural number \
             or list of natural numbers",
         )?;
+        let kept_rows = if self.rank() == 0 {
+            counts[0].saturating_mul(kept.row_count().max(1))
+        } else {
+            counts
+                .iter()
+                .fold(0, |sum: usize, &n| sum.saturating_add(n))
+        };
+        env.check_value_alloc(&kept, kept_rows.saturating_mul(kept.row_len()))?;
         Ok(if self.rank() == 0 {
             match kept {
                 Value::Num(a) => a.scalar_keep(counts[0]).into(),
@@ -751,7 +882,7 @@ impl Value {
             or list of natural numbers",
         )?;
         if self.rank() == 0 {
-            return Err(env.error("Cannot invert scalar keep"));
+            return kept.scalar_unkeep(counts[0], into, env);
         }
         Ok(match (kept, into) {
             (Value::Num(a), Value::Num(b)) => a.unkeep(&counts, b, env)?.into(),
@@ -768,6 +899,41 @@ impl Value {
             )?,
         })
     }
+    /// Undo a `keep` with a scalar count, which repeats the whole array
+    fn scalar_unkeep(self, count: usize, into: Self, env: &Uiua) -> UiuaResult<Self> {
+        let len = into.row_count();
+        if count == 0 || len == 0 {
+            return Ok(into);
+        }
+        let rows: Vec<Value> = self.into_rows().collect();
+        if rows.len() != count * len {
+            return Err(env.error(
+                "Kept array's length was changed, \
+                so the keep cannot be inverted",
+            ));
+        }
+        if (rows.chunks_exact(len)).any(|copy| copy != &rows[..len]) {
+            return Err(env.error(
+                "Copies of a kept row are no longer the same, \
+                so the keep cannot be inverted",
+            ));
+        }
+        let mut rows = rows.into_iter().take(len);
+        let unkept = if into.rank() == 0 {
+            rows.next().unwrap()
+        } else {
+            Value::from_row_values(rows, env)?
+        };
+        if unkept.shape() != into.shape() {
+            return Err(env.error(format!(
+                "Kept array's shape was changed from {} to {}, \
+                so the keep cannot be inverted",
+                into.format_shape(),
+                unkept.format_shape()
+            )));
+        }
+        Ok(unkept)
+    }
 }
 
 impl<T: ArrayValue> Array<T> {
@@ -907,21 +1073,21 @@ impl<T: ArrayValue> Array<T> {
         Ok(self)
     }
     pub(crate) fn unkeep(self, counts: &[usize], into: Self, env: &Uiua) -> UiuaResult<Self> {
-        if counts.iter().any(|&n| n > 1) {
-            return Err(env.error("Cannot invert keep with non-boolean counts"));
-        }
         let mut new_rows: Vec<_> = Vec::with_capacity(counts.len());
         let mut transformed = self.into_rows();
+        let mut next_row = || {
+            transformed.next().ok_or_else(|| {
+                env.error(
+                    "Kept array has fewer rows than it was created with, \
+                    so the keep cannot be inverted",
+                )
+            })
+        };
         for (count, into_row) in counts.iter().zip(into.into_rows()) {
             if *count == 0 {
                 new_rows.push(into_row);
             } else {
-                let new_row = transformed.next().ok_or_else(|| {
-                    env.error(
-                        "Kept array has fewer rows than it was created with, \
-                        so the keep cannot be inverted",
-                    )
-                })?;
+                let new_row = next_row()?;
                 if new_row.shape != into_row.shape {
                     return Err(env.error(format!(
                         "Kept array's shape was changed from {} to {}, \
@@ -930,9 +1096,24 @@ impl<T: ArrayValue> Array<T> {
                         new_row.format_shape()
                     )));
                 }
+                // Every copy of a row must still be the same
+                for _ in 1..*count {
+                    if next_row()? != new_row {
+                        return Err(env.error(
+                            "Copies of a kept row are no longer the same, \
+                            so the keep cannot be inverted",
+                        ));
+                    }
+                }
                 new_rows.push(new_row);
             }
         }
+        if transformed.next().is_some() {
+            return Err(env.error(
+                "Kept array has more rows than it was created with, \
+                so the keep cannot be inverted",
+            ));
+        }
         Self::from_row_arrays(new_rows, env)
     }
 }
@@ -1645,11 +1826,21 @@ impl<T: ArrayValue> Array<T> {
         into: Self,
         env: &Uiua,
     ) -> UiuaResult<Self> {
-        if indices_shape.len() > 1 {
-            Err(env.error("Cannot undo multi-dimensional selection"))
-        } else {
-            self.unselect(indices, into, env)
+        let row_shape = into.shape.get(1..).unwrap_or_default();
+        if !self.shape.starts_with(indices_shape) || &self.shape[indices_shape.len()..] != row_shape
+        {
+            return Err(env.error(
+                "Attempted to undo selection, but \
+                the shape of the selected array changed",
+            ));
         }
+        // Undo the selection of all the indices as a list
+        let mut selected = self.clone();
+        selected.shape = Some(indices.len())
+            .into_iter()
+            .chain(row_shape.iter().copied())
+            .collect();
+        selected.unselect(indices, into, env)
     }
     fn select(&self, indices: &[isize], env: &Uiua) -> UiuaResult<Self> {
         let mut selected = CowSlice::with_capacity(self.row_len() * indices.len());
@@ -1783,6 +1974,11 @@ impl<T: ArrayValue> Array<T> {
                 return Ok(Self::new(new_shape, CowSlice::new()));
             }
         }
+        env.check_alloc::<T>(
+            new_shape
+                .iter()
+                .fold(1, |len, &dim| len.saturating_mul(dim)),
+        )?;
         // Make a new window shape with the same rank as the windowed array
         let mut true_size: Vec<usize> = Vec::with_capacity(self.shape.len());
         true_size.extend(size_spec);
@@ -0,0 +1,106 @@
+//! The `expect` test assertion
+
+use crate::{value::Value, Uiua, UiuaError, UiuaResult};
+
+/// Throw an error describing how two values differ if they do not match
+///
+/// The numeric fill value, if set, is the tolerance for comparing numbers.
+pub fn expect(env: &mut Uiua) -> UiuaResult {
+    let expected = env.pop("expected value")?;
+    let actual = env.pop("actual value")?;
+    let tolerance = env.num_fill().unwrap_or(0.0);
+    if let Some(difference) = difference(&expected, &actual, tolerance) {
+        let message = format!(
+            "Expected:\n{}\nActual:\n{}\n{difference}",
+            expected.show(),
+            actual.show()
+        );
+        return Err(UiuaError::Throw(
+            Value::from(message).into(),
+            env.span().clone(),
+        ));
+    }
+    Ok(())
+}
+
+/// Describe the first difference between two values, if there is one
+fn difference(expected: &Value, actual: &Value, tolerance: f64) -> Option<String> {
+    let numbers = |val: &Value| -> Option<Vec<f64>> {
+        match val {
+            Value::Num(arr) => Some(arr.data.to_vec()),
+            Value::Byte(arr) => Some(arr.data.iter().map(|&b| b as f64).collect()),
+            _ => None,
+        }
+    };
+    let is_number = |val: &Value| matches!(val, Value::Num(_) | Value::Byte(_));
+    if expected.type_name() != actual.type_name() && !(is_number(expected) && is_number(actual)) {
+        return Some(format!(
+            "Types differ: expected {} array but got {} array",
+            expected.type_name(),
+            actual.type_name()
+        ));
+    }
+    if expected.shape() != actual.shape() {
+        return Some(format!(
+            "Shapes differ: expected {} but got {}",
+            expected.format_shape(),
+            actual.format_shape()
+        ));
+    }
+    let shape = expected.shape();
+    let differs_at = |i: usize, a: Value, b: Value| {
+        let (a, b) = (a.show(), b.show());
+        Some(if shape.is_empty() {
+            format!("Expected {a} but got {b}")
+        } else {
+            let index = format_index(shape, i);
+            format!("First difference at index {index}: expected {a} but got {b}")
+        })
+    };
+    match (expected, actual) {
+        (Value::Char(a), Value::Char(b)) => {
+            let (i, (a, b)) =
+                (a.data.iter().zip(&b.data).enumerate()).find(|(_, (a, b))| a != b)?;
+            differs_at(i, (*a).into(), (*b).into())
+        }
+        (Value::Box(a), Value::Box(b)) => {
+            a.data
+                .iter()
+                .zip(&b.data)
+                .enumerate()
+                .find_map(|(i, (a, b))| {
+                    let inner = difference(&a.0, &b.0, tolerance)?;
+                    let index = format_index(shape, i);
+                    Some(format!("In the box at index {index}. {inner}"))
+                })
+        }
+        _ => {
+            let (a, b) = (numbers(expected)?, numbers(actual)?);
+            let (i, (a, b)) = (a.into_iter().zip(b).enumerate())
+                .find(|(_, (a, b))| !numbers_match(*a, *b, tolerance))?;
+            let mut message = differs_at(i, a.into(), b.into())?;
+            if tolerance > 0.0 {
+                message.push_str(&format!(
+                    ", which is more than {} apart",
+                    Value::from(tolerance).show()
+                ));
+            }
+            Some(message)
+        }
+    }
+}
+
+fn numbers_match(a: f64, b: f64, tolerance: f64) -> bool {
+    a == b || (a.is_nan() && b.is_nan()) || (a - b).abs() <= tolerance
+}
+
+/// Format the multidimensional index of an element of an array's data
+fn format_index(shape: &[usize], mut i: usize) -> String {
+    let mut index = vec![0; shape.len()];
+    for (dim, &len) in index.iter_mut().zip(shape).rev() {
+        *dim = i % len;
+        i /= len;
+    }
+    let index: Vec<String> = index.iter().map(|dim| dim.to_string()).collect();
+    format!("[{}]", index.join(" "))
+}
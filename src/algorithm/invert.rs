@@ -19,14 +19,12 @@ impl Function {
     ///
     /// `g_sig` should be the signature of `under`'s second function
     pub fn under(&self, g_sig: Signature) -> Option<(Self, Self)> {
-        if let Some(f) = self.inverse() {
-            Some((self.clone(), f))
-        } else {
-            let (befores, afters) = under_instrs(&self.instrs, g_sig)?;
-            let before = Function::new_inferred(self.id.clone(), befores).ok()?;
-            let after = Function::new_inferred(self.id.clone(), afters).ok()?;
-            Some((before, after))
-        }
+        // This falls back to the inverse, but some functions have
+        // a better way to be undone than their inverse
+        let (befores, afters) = under_instrs(&self.instrs, g_sig)?;
+        let before = Function::new_inferred(self.id.clone(), befores).ok()?;
+        let after = Function::new_inferred(self.id.clone(), afters).ok()?;
+        Some((before, after))
     }
 }
 
@@ -87,6 +85,9 @@ fn prim_inverse(prim: Primitive, span: usize) -> Option<Instr> {
         Unbox => Instr::Prim(Box, span),
         Where => Instr::ImplPrim(InvWhere, span),
         Utf => Instr::ImplPrim(InvUtf, span),
+        Parse => Instr::ImplPrim(InvParse, span),
+        Sys(SysOp::JsonEncode) => Instr::Prim(Sys(SysOp::JsonDecode), span),
+        Sys(SysOp::JsonDecode) => Instr::Prim(Sys(SysOp::JsonEncode), span),
         _ => return None,
     })
 }
@@ -103,6 +104,7 @@ fn impl_prim_inverse(prim: ImplPrimitive, span: usize) -> Option<Instr> {
         InvTrace => Instr::Prim(Trace, span),
         InvWhere => Instr::Prim(Where, span),
         InvUtf => Instr::Prim(Utf, span),
+        InvParse => Instr::Prim(Parse, span),
         _ => return None,
     })
 }
@@ -114,8 +116,16 @@ fn invert_instr_fragment(mut instrs: &[Instr]) -> Option<Vec<Instr>> {
         [Prim(prim, span)] => {
             return Some(match prim {
                 Primitive::Sqrt => vec![Instr::push(2.0), Instr::Prim(Primitive::Pow, *span)],
+                // Split off the first row
+                Primitive::Join => vec![
+                    Instr::Prim(Dup, *span),
+                    Instr::push(1.0),
+                    Instr::Prim(Drop, *span),
+                    Instr::Prim(Flip, *span),
+                    Instr::Prim(First, *span),
+                ],
                 prim => vec![prim_inverse(*prim, *span)?],
-            })
+            });
         }
         [ImplPrim(prim, span)] => return impl_prim_inverse(*prim, *span).map(|instr| vec![instr]),
         [PushFunc(val)] => {
@@ -270,6 +280,62 @@ fn under_instrs_impl(instrs: &[Instr], g_sig: Signature) -> Option<(Vec<Instr>,
             [Dup.i(), PushTempUnderN(1).i(), Log.i()],
             [PopTempUnderN(1).i(), Flip.i(), Pow.i()],
         ),
+        &(
+            Val,
+            (
+                [Flip, Join],
+                [
+                    Flip.i(),
+                    Over.i(),
+                    Shape.i(),
+                    Over.i(),
+                    Shape.i(),
+                    PushTempUnderN(2).i(),
+                    Join.i(),
+                ],
+                [PopTempUnderN(2).i(), Unjoin.i(), Flip.i(), Pop.i()],
+            ),
+        ),
+        &(
+            [Flip, Join],
+            [
+                Flip.i(),
+                Over.i(),
+                Shape.i(),
+                Over.i(),
+                Shape.i(),
+                PushTempUnderN(2).i(),
+                Join.i(),
+            ],
+            [PopTempUnderN(2).i(), Unjoin.i(), Flip.i()],
+        ),
+        &(
+            Val,
+            (
+                [Join],
+                [
+                    Over.i(),
+                    Shape.i(),
+                    Over.i(),
+                    Shape.i(),
+                    PushTempUnderN(2).i(),
+                    Join.i(),
+                ],
+                [PopTempUnderN(2).i(), Unjoin.i(), Pop.i()],
+            ),
+        ),
+        &(
+            [Join],
+            [
+                Over.i(),
+                Shape.i(),
+                Over.i(),
+                Shape.i(),
+                PushTempUnderN(2).i(),
+                Join.i(),
+            ],
+            [PopTempUnderN(2).i(), Unjoin.i()],
+        ),
         // It is important that this comes after the things above
         &UnderPatternFn(under_from_inverse_pattern),
         &UnderPatternFn(under_temp_pattern),
@@ -324,6 +390,33 @@ fn under_instrs_impl(instrs: &[Instr], g_sig: Signature) -> Option<(Vec<Instr>,
             [Dup.i(), PushTempUnderN(1).i(), Shape.i()],
             [PopTempUnderN(1).i(), Flip.i(), Reshape.i()],
         ),
+        &(
+            Val,
+            (
+                [Reshape],
+                [
+                    Over.i(),
+                    PushTempUnderN(1).i(),
+                    Reshape.i(),
+                    Dup.i(),
+                    Shape.i(),
+                    PushTempUnderN(1).i(),
+                ],
+                [PopTempUnderN(1).i(), PopTempUnderN(1).i(), Unreshape.i()],
+            ),
+        ),
+        &(
+            [Reshape],
+            [
+                Over.i(),
+                PushTempUnderN(1).i(),
+                Reshape.i(),
+                Dup.i(),
+                Shape.i(),
+                PushTempUnderN(1).i(),
+            ],
+            [PopTempUnderN(1).i(), PopTempUnderN(1).i(), Unreshape.i()],
+        ),
         &(
             [Deshape],
             [Dup.i(), Shape.i(), PushTempUnderN(1).i(), Deshape.i()],
@@ -354,6 +447,16 @@ fn under_instrs_impl(instrs: &[Instr], g_sig: Signature) -> Option<(Vec<Instr>,
             [Sys(SysOp::TcpAccept).i(), Dup.i(), PushTempUnderN(1).i()],
             [PopTempUnderN(1).i(), Sys(SysOp::Close).i()],
         ),
+        &(
+            [Sys(SysOp::UdpBind)],
+            [Sys(SysOp::UdpBind).i(), Dup.i(), PushTempUnderN(1).i()],
+            [PopTempUnderN(1).i(), Sys(SysOp::Close).i()],
+        ),
+        &(
+            [Sys(SysOp::FfiLoad)],
+            [Sys(SysOp::FfiLoad).i(), Dup.i(), PushTempUnderN(1).i()],
+            [PopTempUnderN(1).i(), Sys(SysOp::Close).i()],
+        ),
     ];
 
     let mut befores = Vec::new();
@@ -475,7 +578,7 @@ impl AsInstr for Call {
     }
 }
 
-trait InvertPattern {
+pub(crate) trait InvertPattern {
     fn invert_extract<'a>(&self, input: &'a [Instr]) -> Option<(&'a [Instr], Vec<Instr>)>;
 }
 
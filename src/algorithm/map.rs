@@ -0,0 +1,366 @@
+//! Maps
+//!
+//! A map is an array whose rows are its values, along with a [`MapKeys`] that
+//! holds a key for each row. The keys are indexed in a hash map, so looking up
+//! a key does not search the array.
+//!
+//! Only the map primitives return maps. Other primitives treat a map as an array
+//! of its values, and the keys are removed from whatever they return.
+
+use std::{collections::HashMap, iter::repeat_n, mem::take, sync::Arc};
+
+use crate::{
+    array::{Array, ArrayValue},
+    boxed::Boxed,
+    cowslice::CowSlice,
+    value::Value,
+    PrimClass, Primitive, Uiua, UiuaResult,
+};
+
+/// The keys of a map
+#[derive(Debug, Clone)]
+pub(crate) struct MapKeys {
+    /// The key of each row
+    keys: Value,
+    /// The row of each key
+    indices: HashMap<Value, usize>,
+}
+
+impl MapKeys {
+    fn new(keys: Value) -> Result<Self, String> {
+        let mut indices = HashMap::with_capacity(keys.row_count());
+        for (i, key) in keys.rows().enumerate() {
+            if indices.insert(index_key(key.clone()), i).is_some() {
+                return Err(format!("Duplicate key {key:?} in map"));
+            }
+        }
+        Ok(Self { keys, indices })
+    }
+    /// Get the keys
+    pub(crate) fn keys(&self) -> &Value {
+        &self.keys
+    }
+    /// Get the row of a key
+    fn get(&self, key: Value) -> Option<usize> {
+        let key = coerce_key(key, &self.keys);
+        self.indices.get(&index_key(key)).copied()
+    }
+}
+
+/// Get the form of a key used in the index
+///
+/// Numbers and bytes compare equal, so they must hash the same.
+fn index_key(key: Value) -> Value {
+    match key {
+        Value::Byte(arr) => Value::Num(arr.convert()),
+        key => key,
+    }
+}
+
+/// Box or unbox a key to match the keys of a map
+fn coerce_key(key: Value, keys: &Value) -> Value {
+    match (keys, key) {
+        (Value::Box(_), Value::Box(key)) => Value::Box(key),
+        (Value::Box(_), key) => Boxed(key).into(),
+        (_, Value::Box(key)) => key.into_unboxed().unwrap_or_else(Value::Box),
+        (_, key) => key,
+    }
+}
+
+/// Make a value from rows, or box them if they do not fit together
+fn rows_or_boxed(rows: Vec<Value>, env: &Uiua) -> Value {
+    if !rows.iter().any(Value::is_map) {
+        if let Ok(value) = Value::from_row_values(rows.iter().cloned(), env) {
+            return value;
+        }
+    }
+    rows.into_iter().map(Boxed).collect::<Array<_>>().into()
+}
+
+/// Make a row of fill values for a missing key
+fn fill_row<T: ArrayValue>(arr: &Array<T>, env: &Uiua) -> Option<Value>
+where
+    Array<T>: Into<Value>,
+{
+    let fill = T::get_fill(env)?;
+    let shape = &arr.shape()[1..];
+    let data: CowSlice<T> = repeat_n(fill, shape.iter().product()).collect();
+    Some(Array::new(shape, data).into())
+}
+
+impl<T: ArrayValue> Array<T> {
+    fn set_row(&mut self, i: usize, row: Self) {
+        let row_len = self.row_len();
+        self.data.as_mut_slice()[i * row_len..(i + 1) * row_len].clone_from_slice(&row.data);
+    }
+    fn remove_row(&mut self, i: usize) {
+        let row_len = self.row_len();
+        let (start, end) = (i * row_len, (i + 1) * row_len);
+        self.data = (self.data[..start].iter())
+            .chain(&self.data[end..])
+            .cloned()
+            .collect();
+        self.shape[0] -= 1;
+    }
+}
+
+impl Value {
+    pub(crate) fn map_keys(&self) -> Option<&Arc<MapKeys>> {
+        match self {
+            Value::Num(arr) => arr.map_keys.as_ref(),
+            Value::Byte(arr) => arr.map_keys.as_ref(),
+            Value::Char(arr) => arr.map_keys.as_ref(),
+            Value::Box(arr) => arr.map_keys.as_ref(),
+        }
+    }
+    fn map_keys_mut(&mut self) -> &mut Option<Arc<MapKeys>> {
+        match self {
+            Value::Num(arr) => &mut arr.map_keys,
+            Value::Byte(arr) => &mut arr.map_keys,
+            Value::Char(arr) => &mut arr.map_keys,
+            Value::Box(arr) => &mut arr.map_keys,
+        }
+    }
+    /// Check if the value is a map
+    pub fn is_map(&self) -> bool {
+        self.map_keys()
+            .is_some_and(|keys| keys.keys.row_count() == self.row_count())
+    }
+    /// Make the value a map with the given keys
+    pub(crate) fn set_map_keys(&mut self, keys: Value) -> Result<(), String> {
+        if keys.row_count() != self.row_count() {
+            return Err(format!(
+                "Cannot make a map with {} keys and {} values",
+                keys.row_count(),
+                self.row_count()
+            ));
+        }
+        *self.map_keys_mut() = Some(Arc::new(MapKeys::new(keys)?));
+        Ok(())
+    }
+    /// Remove the keys if the value is a map
+    pub(crate) fn strip_map_keys(&mut self) {
+        *self.map_keys_mut() = None;
+    }
+    /// Get the keys of a map, or `None` for an empty array
+    fn expect_map(&self, env: &Uiua) -> UiuaResult<Option<&MapKeys>> {
+        match self.map_keys() {
+            Some(keys) if keys.keys.row_count() == self.row_count() => Ok(Some(keys)),
+            _ if self.rank() > 0 && self.row_count() == 0 => Ok(None),
+            _ => Err(env.error(format!(
+                "Expected a map, but got an array of shape {}",
+                self.format_shape()
+            ))),
+        }
+    }
+    /// Create a map from a table of key-value pairs
+    pub fn map(self, env: &Uiua) -> UiuaResult<Self> {
+        if self.rank() > 0 && self.row_count() == 0 {
+            return Ok(Value::default());
+        }
+        let table = match self {
+            Value::Box(table) if table.rank() == 2 && table.shape()[1] == 2 => table,
+            value => {
+                return Err(env.error(format!(
+                    "Map table must be a box array with 2 columns, \
+                    but it is a {} array of shape {}",
+                    value.type_name(),
+                    value.format_shape()
+                )))
+            }
+        };
+        let (keys, values): (Vec<Value>, Vec<Value>) = (table.data.chunks_exact(2))
+            .map(|pair| (pair[0].as_value().clone(), pair[1].as_value().clone()))
+            .unzip();
+        let keys = rows_or_boxed(keys, env);
+        let mut values = rows_or_boxed(values, env);
+        values.set_map_keys(keys).map_err(|e| env.error(e))?;
+        Ok(values)
+    }
+    /// Insert a key-value pair into a map
+    pub fn insert(&mut self, key: Value, mut value: Value, env: &Uiua) -> UiuaResult {
+        if self.expect_map(env)?.is_none() {
+            // An empty array becomes a map with one pair
+            let mut key = key;
+            key.strip_map_keys();
+            key.shape_mut().insert(0, 1);
+            value.strip_map_keys();
+            value.shape_mut().insert(0, 1);
+            *self = value;
+            return self.set_map_keys(key).map_err(|e| env.error(e));
+        }
+        let mut keys = self.map_keys_mut().take().unwrap();
+        let keys_mut = Arc::make_mut(&mut keys);
+        if let Some(i) = keys_mut.get(key.clone()) {
+            self.put_row(Some(i), value, env)?;
+        } else {
+            self.put_row(None, value, env)?;
+            let key = coerce_key(key, &keys_mut.keys);
+            let was_boxed = matches!(keys_mut.keys, Value::Box(_));
+            keys_mut.keys.put_row(None, key.clone(), env)?;
+            if !was_boxed && matches!(keys_mut.keys, Value::Box(_)) {
+                // The keys were boxed, so the index must be too
+                *keys_mut = MapKeys::new(take(&mut keys_mut.keys)).map_err(|e| env.error(e))?;
+            } else {
+                (keys_mut.indices).insert(index_key(key), keys_mut.keys.row_count() - 1);
+            }
+        }
+        *self.map_keys_mut() = Some(keys);
+        Ok(())
+    }
+    /// Set a row, or append it if there is no index
+    ///
+    /// If the row does not fit with the others, all the rows are boxed.
+    fn put_row(&mut self, i: Option<usize>, mut row: Value, env: &Uiua) -> UiuaResult {
+        row.strip_map_keys();
+        if row.shape() != &self.shape()[1..] || row.type_name() != self.type_name() {
+            if !matches!(self, Value::Box(_)) {
+                *self = take(self)
+                    .into_rows()
+                    .map(Boxed)
+                    .collect::<Array<_>>()
+                    .into();
+            }
+            if !(matches!(row, Value::Box(_)) && row.rank() == 0) {
+                row = Boxed(row).into();
+            }
+        }
+        let Some(i) = i else {
+            return self.append(row, env);
+        };
+        match (&mut *self, row) {
+            (Value::Num(a), Value::Num(b)) => a.set_row(i, b),
+            (Value::Byte(a), Value::Byte(b)) => a.set_row(i, b),
+            (Value::Char(a), Value::Char(b)) => a.set_row(i, b),
+            (Value::Box(a), Value::Box(b)) => a.set_row(i, b),
+            (Value::Num(a), Value::Byte(b)) => a.set_row(i, b.convert()),
+            (Value::Byte(a), Value::Num(b)) => {
+                let mut a = a.convert_ref();
+                a.set_row(i, b);
+                *self = a.into();
+            }
+            (a, b) => unreachable!(
+                "{} row does not fit in {} array",
+                b.type_name(),
+                a.type_name()
+            ),
+        }
+        Ok(())
+    }
+    /// Get the value of a key in a map
+    ///
+    /// If the key is not in the map, the fill value is used if there is one.
+    pub fn get(&self, key: Value, env: &Uiua) -> UiuaResult<Self> {
+        let keys = self.expect_map(env)?;
+        if let Some(i) = keys.and_then(|keys| keys.get(key.clone())) {
+            return Ok(self.row(i));
+        }
+        let default = match self {
+            Value::Num(arr) => fill_row(arr, env),
+            Value::Byte(arr) => fill_row(arr, env),
+            Value::Char(arr) => fill_row(arr, env),
+            Value::Box(arr) => fill_row(arr, env),
+        };
+        default.ok_or_else(|| env.error(format!("Key {key:?} not found in map")))
+    }
+    /// Check if a map has a key
+    pub fn has_key(&self, key: Value, env: &Uiua) -> UiuaResult<bool> {
+        let keys = self.expect_map(env)?;
+        Ok(keys.is_some_and(|keys| keys.get(key).is_some()))
+    }
+    /// Remove a key and its value from a map
+    ///
+    /// Nothing happens if the key is not in the map.
+    pub fn remove(&mut self, key: Value, env: &Uiua) -> UiuaResult {
+        if self.expect_map(env)?.is_none() {
+            return Ok(());
+        }
+        let mut keys = self.map_keys_mut().take().unwrap();
+        let keys_mut = Arc::make_mut(&mut keys);
+        let key = coerce_key(key, &keys_mut.keys);
+        if let Some(i) = keys_mut.get(key.clone()) {
+            self.remove_row(i);
+            keys_mut.keys.remove_row(i);
+            keys_mut.indices.remove(&index_key(key));
+            for index in keys_mut.indices.values_mut() {
+                if *index > i {
+                    *index -= 1;
+                }
+            }
+        }
+        *self.map_keys_mut() = Some(keys);
+        Ok(())
+    }
+    fn remove_row(&mut self, i: usize) {
+        match self {
+            Value::Num(arr) => arr.remove_row(i),
+            Value::Byte(arr) => arr.remove_row(i),
+            Value::Char(arr) => arr.remove_row(i),
+            Value::Box(arr) => arr.remove_row(i),
+        }
+    }
+    /// Get the keys of a map
+    pub fn keys(&self, env: &Uiua) -> UiuaResult<Self> {
+        Ok(match self.expect_map(env)? {
+            Some(keys) => keys.keys.clone(),
+            None => Value::default(),
+        })
+    }
+    /// Get the values of a map
+    pub fn values(mut self, env: &Uiua) -> UiuaResult<Self> {
+        self.expect_map(env)?;
+        self.strip_map_keys();
+        Ok(self)
+    }
+}
+
+impl Primitive {
+    /// Check if the primitive can return a map
+    pub(crate) fn keeps_map_keys(&self) -> bool {
+        use Primitive::*;
+        self.modifier_args().is_some()
+            || matches!(self.class(), PrimClass::Stack | PrimClass::Planet)
+            || matches!(self, Unbox | Wait | Recv | TryRecv | Map | Insert | Remove)
+    }
+}
+
+impl Uiua {
+    /// Remove the keys of any maps in the top `n` values on the stack
+    pub(crate) fn strip_map_keys(&mut self, n: usize) {
+        for value in self.stack.iter_mut().rev().take(n) {
+            value.strip_map_keys();
+        }
+    }
+}
+
+#[test]
+fn map_keys() {
+    let mut env = Uiua::with_native_sys();
+    env.load_str("insert 3 4 insert 1 2 []").unwrap();
+    let map = env.pop("map").unwrap();
+    assert!(map.is_map());
+    assert_eq!(map.map_keys().unwrap().indices.len(), 2);
+
+    // Keys are removed by other primitives
+    env.load_str("⇌ insert 3 4 insert 1 2 []").unwrap();
+    assert!(!env.pop("reversed").unwrap().is_map());
+
+    // Numbers and bytes are the same key
+    env.load_str("get =1 1 insert 1 5 []").unwrap();
+    assert_eq!(env.pop_num().unwrap(), 5.0);
+}
+
+#[test]
+fn map_key_ordering() {
+    use std::cmp::Ordering;
+
+    let mut env = Uiua::with_native_sys();
+    env.load_str("insert 1 3 []\ninsert 2 3 []").unwrap();
+    let b = env.pop("second map").unwrap();
+    let a = env.pop("first map").unwrap();
+    // Maps with the same values but different keys are not equal, so they must not compare equal
+    assert_ne!(a, b);
+    assert_eq!(a.cmp(&b), Ordering::Less);
+    assert_eq!(b.cmp(&a), Ordering::Greater);
+    assert_eq!(a.cmp(&a.clone()), Ordering::Equal);
+}
@@ -0,0 +1,139 @@
+//! Memoization
+//!
+//! [`memo`](crate::Primitive::Memo) caches a function's outputs keyed by its arguments.
+//! The cache lasts as long as the runtime and is shared with the threads it spawns.
+//! With [`Uiua::with_memo_limit`], results stop being cached once the cache is full.
+
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use crate::{function::Function, Uiua, UiuaResult, Value};
+
+/// The cached results of memoized functions
+#[derive(Debug, Default)]
+pub(crate) struct MemoCache {
+    /// The most results that can be cached
+    limit: Option<usize>,
+    /// The number of results cached
+    len: usize,
+    /// The outputs of each function for each list of arguments
+    functions: HashMap<FunctionKey, HashMap<Vec<Value>, Vec<Value>>>,
+}
+
+/// A function compared by its address rather than its contents
+///
+/// Holding on to the function keeps its address from being reused.
+#[derive(Debug)]
+struct FunctionKey(Arc<Function>);
+
+impl PartialEq for FunctionKey {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for FunctionKey {}
+
+impl Hash for FunctionKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state)
+    }
+}
+
+impl MemoCache {
+    fn get(&self, f: &Arc<Function>, args: &[Value]) -> Option<Vec<Value>> {
+        (self.functions.get(&FunctionKey(f.clone())))?
+            .get(args)
+            .cloned()
+    }
+    fn insert(&mut self, f: Arc<Function>, args: Vec<Value>, outputs: Vec<Value>) {
+        if self.limit.is_some_and(|limit| self.len >= limit) {
+            return;
+        }
+        if self
+            .functions
+            .entry(FunctionKey(f))
+            .or_default()
+            .insert(args, outputs)
+            .is_none()
+        {
+            self.len += 1;
+        }
+    }
+}
+
+/// Call a function, or push its cached outputs if it has been called with the same arguments before
+pub(crate) fn memo(env: &mut Uiua) -> UiuaResult {
+    crate::profile_function!();
+    let f = env.pop_function()?;
+    let sig = f.signature();
+    let mut args = Vec::with_capacity(sig.args);
+    for i in 0..sig.args {
+        args.push(env.pop(i + 1)?);
+    }
+    let cached = env.memo.lock().get(&f, &args);
+    let outputs = if let Some(outputs) = cached {
+        outputs
+    } else {
+        for arg in args.iter().rev() {
+            env.push(arg.clone());
+        }
+        env.call(f.clone())?;
+        let mut outputs = Vec::with_capacity(sig.outputs);
+        for _ in 0..sig.outputs {
+            outputs.push(env.pop("memoized function result")?);
+        }
+        env.memo.lock().insert(f, args, outputs.clone());
+        outputs
+    };
+    for output in outputs.into_iter().rev() {
+        env.push(output);
+    }
+    Ok(())
+}
+
+impl Uiua {
+    /// Limit the number of results cached by `memo`
+    ///
+    /// Once the limit is reached, memoized functions still run, but their new results are not cached.
+    ///
+    /// ```
+    /// # use uiua::*;
+    /// let mut env = Uiua::with_native_sys().with_memo_limit(100);
+    /// env.load_str("F ← memo(×2)\nF 5 F 5").unwrap();
+    /// assert_eq!(env.memo_len(), 1);
+    /// ```
+    pub fn with_memo_limit(self, entries: usize) -> Self {
+        self.memo.lock().limit = Some(entries);
+        self
+    }
+    /// Get the number of results cached by `memo`
+    pub fn memo_len(&self) -> usize {
+        self.memo.lock().len
+    }
+}
+
+#[test]
+fn memo_cache() {
+    // Memoizing random gives the same number for the same arguments
+    let mut env = Uiua::with_native_sys();
+    env.load_str("F ← memo(+⚂)\n=F 1 F 1\n=F 1 F 2").unwrap();
+    assert_eq!(env.pop_num().unwrap(), 0.0);
+    assert_eq!(env.pop_num().unwrap(), 1.0);
+    assert_eq!(env.memo_len(), 2);
+
+    // Spawned threads share the cache
+    let mut env = Uiua::with_native_sys();
+    env.load_str("F ← memo(+⚂)\n=wait spawn F 1 F 1").unwrap();
+    assert_eq!(env.pop_num().unwrap(), 1.0);
+
+    // Nothing more is cached once the limit is reached
+    let mut env = Uiua::with_native_sys().with_memo_limit(1);
+    env.load_str("F ← memo(+⚂)\n=F 1 F 1\n=F 2 F 2").unwrap();
+    assert_eq!(env.pop_num().unwrap(), 0.0);
+    assert_eq!(env.pop_num().unwrap(), 1.0);
+    assert_eq!(env.memo_len(), 1);
+}
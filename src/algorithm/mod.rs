@@ -12,16 +12,56 @@ use crate::{
     Uiua, UiuaError, UiuaResult,
 };
 
+pub(crate) mod derivative;
 mod dyadic;
+pub(crate) mod expect;
 pub mod fork;
 pub(crate) mod invert;
 pub mod loops;
+pub(crate) mod map;
+pub(crate) mod memo;
 mod monadic;
 pub mod pervade;
 pub mod reduce;
 pub mod table;
+pub mod windows;
 pub mod zip;
 
+/// The number of elements a native loop processes between checks for interruption
+pub(crate) const INTERRUPT_INTERVAL: usize = 1 << 16;
+
+/// Counts the elements processed by a native loop so that it can be interrupted
+///
+/// Loops that call Uiua functions are interrupted by the instruction loop, but
+/// loops over array data never return to it. Checking after every element
+/// would slow them down, so they check every [`INTERRUPT_INTERVAL`] elements.
+pub(crate) struct InterruptCheck<'a> {
+    env: &'a Uiua,
+    since_check: usize,
+}
+
+impl<'a> InterruptCheck<'a> {
+    pub fn new(env: &'a Uiua) -> Self {
+        InterruptCheck {
+            env,
+            since_check: 0,
+        }
+    }
+    /// Get the runtime being checked
+    pub fn env(&self) -> &'a Uiua {
+        self.env
+    }
+    /// Record that some elements were processed, checking for interruption if enough have been
+    pub fn tick(&mut self, elements: usize) -> UiuaResult {
+        self.since_check += elements;
+        if self.since_check < INTERRUPT_INTERVAL {
+            return Ok(());
+        }
+        self.since_check = 0;
+        self.env.check_interrupt()
+    }
+}
+
 fn max_shape(a: &[usize], b: &[usize]) -> Shape {
     let shape_len = a.len().max(b.len());
     let mut new_shape = Shape::with_capacity(shape_len);
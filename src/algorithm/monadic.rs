@@ -38,6 +38,10 @@ impl Value {
             .map_err(|e| env.error(format!("Cannot parse into number: {}", e)))?
             .into())
     }
+    pub(crate) fn inv_parse(&self, env: &Uiua) -> UiuaResult<Self> {
+        let n = self.as_num(env, "Cannot invert parse of a non-number")?;
+        Ok(n.to_string().into())
+    }
 }
 
 impl<T: ArrayValue> Array<T> {
@@ -85,6 +89,7 @@ fn range(shape: &[usize], env: &Uiua) -> UiuaResult<CowSlice<f64>> {
         }
         len = new;
     }
+    env.check_alloc::<f64>(len)?;
     let mut data: EcoVec<f64> = EcoVec::with_capacity(len);
     let mut curr = vec![0; shape.len()];
     loop {
@@ -282,6 +287,34 @@ impl Value {
     pub fn fall(&self, env: &Uiua) -> UiuaResult<Vec<usize>> {
         self.generic_ref_env_deep(Array::fall, Array::fall, Array::fall, Array::fall, env)
     }
+    /// Sort the rows of the value in ascending order
+    ///
+    /// This is equivalent to `select rise duplicate`
+    pub fn sort_up(self, env: &Uiua) -> UiuaResult<Self> {
+        let indices = self.rise(env)?;
+        self.sort_by_indices(indices, env)
+    }
+    /// Sort the rows of the value in descending order
+    ///
+    /// This is equivalent to `select fall duplicate`
+    pub fn sort_down(self, env: &Uiua) -> UiuaResult<Self> {
+        let indices = self.fall(env)?;
+        self.sort_by_indices(indices, env)
+    }
+    fn sort_by_indices(mut self, indices: Vec<usize>, env: &Uiua) -> UiuaResult<Self> {
+        // Grading can look inside a boxed scalar, and the grade of an array
+        // with no elements is empty, so defer to `select` in those cases
+        if self.rank() == 0 || indices.len() != self.row_count() {
+            return Value::from(Array::from(indices)).select(&self, env);
+        }
+        match &mut self {
+            Value::Num(a) => a.reorder_rows(&indices),
+            Value::Byte(a) => a.reorder_rows(&indices),
+            Value::Char(a) => a.reorder_rows(&indices),
+            Value::Box(a) => a.reorder_rows(&indices),
+        }
+        Ok(self)
+    }
     /// `classify` the rows of the value
     pub fn classify(&self, env: &Uiua) -> UiuaResult<Self> {
         self.generic_ref_env_deep(
@@ -343,6 +376,14 @@ impl<T: ArrayValue> Array<T> {
         });
         Ok(indices)
     }
+    /// Reorder the rows of the array so that row `i` is the old row `indices[i]`
+    fn reorder_rows(&mut self, indices: &[usize]) {
+        let data = indices
+            .iter()
+            .flat_map(|&i| self.row_slice(i).iter().cloned())
+            .collect();
+        self.data = data;
+    }
     /// `classify` the rows of the array
     pub fn classify(&self, env: &Uiua) -> UiuaResult<Vec<usize>> {
         if self.rank() == 0 {
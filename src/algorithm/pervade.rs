@@ -10,7 +10,7 @@ use std::{
 
 use crate::{array::*, cowslice::CowSlice, Uiua, UiuaError, UiuaResult};
 
-use super::fill_array_shapes;
+use super::{fill_array_shapes, InterruptCheck, INTERRUPT_INTERVAL};
 
 #[allow(clippy::len_without_is_empty)]
 pub trait Arrayish {
@@ -136,7 +136,8 @@ where
     fill_array_shapes(&mut a, &mut b, env)?;
     let shape = Shape::from(a.shape().max(b.shape()));
     let mut data = CowSlice::with_capacity(a.element_count().max(b.element_count()));
-    bin_pervade_recursive(&a, &b, &mut data, env, f).map_err(Into::into)?;
+    let mut check = InterruptCheck::new(env);
+    bin_pervade_recursive(&a, &b, &mut data, &mut check, f)?;
     Ok(Array::new(shape, data))
 }
 
@@ -144,41 +145,52 @@ fn bin_pervade_recursive<A, B, C, F>(
     a: &A,
     b: &B,
     c: &mut CowSlice<C>,
-    env: &Uiua,
+    check: &mut InterruptCheck,
     f: F,
-) -> Result<(), F::Error>
+) -> UiuaResult
 where
     A: Arrayish,
     B: Arrayish,
     C: ArrayValue,
     F: PervasiveFn<A::Value, B::Value, Output = C> + Clone,
+    F::Error: Into<UiuaError>,
 {
+    let env = check.env();
     match (a.shape(), b.shape()) {
-        ([], []) => c.modify(|c| {
-            c.push(f.call(a.data()[0].clone(), b.data()[0].clone(), env)?);
-            Ok(())
-        })?,
+        ([], []) => {
+            c.modify(|c| {
+                c.push(f.call(a.data()[0].clone(), b.data()[0].clone(), env)?);
+                Ok::<_, F::Error>(())
+            })
+            .map_err(Into::into)?;
+            check.tick(1)?;
+        }
         (ash, bsh) if ash == bsh => {
-            c.try_extend(
-                a.data()
-                    .iter()
-                    .zip(b.data())
-                    .map(|(a, b)| f.call(a.clone(), b.clone(), env)),
-            )?;
+            for (a, b) in
+                (a.data().chunks(INTERRUPT_INTERVAL)).zip(b.data().chunks(INTERRUPT_INTERVAL))
+            {
+                c.try_extend(
+                    a.iter()
+                        .zip(b)
+                        .map(|(a, b)| f.call(a.clone(), b.clone(), env)),
+                )
+                .map_err(Into::into)?;
+                check.tick(a.len())?;
+            }
         }
         ([], bsh) => {
             for brow in b.rows() {
-                bin_pervade_recursive(a, &(&bsh[1..], brow), c, env, f.clone())?;
+                bin_pervade_recursive(a, &(&bsh[1..], brow), c, check, f.clone())?;
             }
         }
         (ash, []) => {
             for arow in a.rows() {
-                bin_pervade_recursive(&(&ash[1..], arow), b, c, env, f.clone())?;
+                bin_pervade_recursive(&(&ash[1..], arow), b, c, check, f.clone())?;
             }
         }
         (ash, bsh) => {
             for (arow, brow) in a.rows().zip(b.rows()) {
-                bin_pervade_recursive(&(&ash[1..], arow), &(&bsh[1..], brow), c, env, f.clone())?;
+                bin_pervade_recursive(&(&ash[1..], arow), &(&bsh[1..], brow), c, check, f.clone())?;
             }
         }
     }
@@ -197,77 +209,132 @@ where
     fill_array_shapes(a, &mut b, env)?;
     let ash = a.shape.as_slice();
     let bsh = b.shape.as_slice();
+    let mut check = InterruptCheck::new(env);
     // Try to avoid copying when possible
     if ash == bsh {
         if a.data.is_copy_of(&b.data) {
             drop(b);
             let a_data = a.data.as_mut_slice();
-            for a in a_data {
-                *a = f(*a, *a);
+            for a in a_data.chunks_mut(INTERRUPT_INTERVAL) {
+                for a in a.iter_mut() {
+                    *a = f(*a, *a);
+                }
+                check.tick(a.len())?;
             }
         } else if b.data.is_unique() {
             let a_data = a.data.as_slice();
             let b_data = b.data.as_mut_slice();
-            for (a, b) in a_data.iter().zip(b_data) {
-                *b = f(*a, *b);
-            }
+            bin_pervade_same_shape_mut_right(a_data, b_data, &mut check, f)?;
             *a = b;
         } else {
             let a_data = a.data.as_mut_slice();
             let b_data = b.data.as_slice();
-            for (a, b) in a_data.iter_mut().zip(b_data) {
-                *a = f(*a, *b);
-            }
+            bin_pervade_same_shape_mut_left(a_data, b_data, &mut check, f)?;
         }
     } else {
         match ash.len().cmp(&bsh.len()) {
             Ordering::Greater => {
                 let a_data = a.data.as_mut_slice();
                 let b_data = b.data.as_slice();
-                bin_pervade_recursive_mut_left(a_data, ash, b_data, bsh, f);
+                bin_pervade_recursive_mut_left(a_data, ash, b_data, bsh, &mut check, f)?;
             }
             Ordering::Less => {
                 let a_data = a.data.as_slice();
                 let b_data = b.data.as_mut_slice();
-                bin_pervade_recursive_mut_right(a_data, ash, b_data, bsh, f);
+                bin_pervade_recursive_mut_right(a_data, ash, b_data, bsh, &mut check, f)?;
                 *a = b;
             }
             Ordering::Equal => {
                 let a_data = a.data.as_mut_slice();
                 let b_data = b.data.as_mut_slice();
-                bin_pervade_recursive_mut(a_data, ash, b_data, bsh, f);
+                bin_pervade_recursive_mut(a_data, ash, b_data, bsh, &mut check, f)?;
             }
         }
     }
     Ok(())
 }
 
+fn bin_pervade_same_shape_mut_left<T: Copy>(
+    a_data: &mut [T],
+    b_data: &[T],
+    check: &mut InterruptCheck,
+    f: impl Fn(T, T) -> T,
+) -> UiuaResult {
+    for (a, b) in (a_data.chunks_mut(INTERRUPT_INTERVAL)).zip(b_data.chunks(INTERRUPT_INTERVAL)) {
+        for (a, b) in a.iter_mut().zip(b) {
+            *a = f(*a, *b);
+        }
+        check.tick(a.len())?;
+    }
+    Ok(())
+}
+
+fn bin_pervade_same_shape_mut_right<T: Copy>(
+    a_data: &[T],
+    b_data: &mut [T],
+    check: &mut InterruptCheck,
+    f: impl Fn(T, T) -> T,
+) -> UiuaResult {
+    for (a, b) in (a_data.chunks(INTERRUPT_INTERVAL)).zip(b_data.chunks_mut(INTERRUPT_INTERVAL)) {
+        for (a, b) in a.iter().zip(b.iter_mut()) {
+            *b = f(*a, *b);
+        }
+        check.tick(a.len())?;
+    }
+    Ok(())
+}
+
+fn bin_pervade_scalar_mut_left<T: Copy>(
+    a_data: &mut [T],
+    b_scalar: T,
+    check: &mut InterruptCheck,
+    f: impl Fn(T, T) -> T,
+) -> UiuaResult {
+    for a in a_data.chunks_mut(INTERRUPT_INTERVAL) {
+        for a in a.iter_mut() {
+            *a = f(*a, b_scalar);
+        }
+        check.tick(a.len())?;
+    }
+    Ok(())
+}
+
+fn bin_pervade_scalar_mut_right<T: Copy>(
+    a_scalar: T,
+    b_data: &mut [T],
+    check: &mut InterruptCheck,
+    f: impl Fn(T, T) -> T,
+) -> UiuaResult {
+    for b in b_data.chunks_mut(INTERRUPT_INTERVAL) {
+        for b in b.iter_mut() {
+            *b = f(a_scalar, *b);
+        }
+        check.tick(b.len())?;
+    }
+    Ok(())
+}
+
 fn bin_pervade_recursive_mut<T>(
     a_data: &mut [T],
     a_shape: &[usize],
     b_data: &mut [T],
     b_shape: &[usize],
+    check: &mut InterruptCheck,
     f: impl Fn(T, T) -> T + Copy,
-) -> bool
+) -> UiuaResult<bool>
 where
     T: ArrayValue + Copy,
 {
-    match (a_shape, b_shape) {
+    Ok(match (a_shape, b_shape) {
         ([], []) => {
             panic!("should never call `bin_pervade_recursive_mut` with scalars")
         }
         (_, []) => {
-            let b_scalar = b_data[0];
-            for a in a_data {
-                *a = f(*a, b_scalar);
-            }
+            bin_pervade_scalar_mut_left(a_data, b_data[0], check, f)?;
             true
         }
         ([], _) => {
-            let a_scalar = a_data[0];
-            for b in b_data {
-                *b = f(a_scalar, *b);
-            }
+            bin_pervade_scalar_mut_right(a_data[0], b_data, check, f)?;
             false
         }
         (ash, bsh) => {
@@ -277,11 +344,11 @@ where
                 .chunks_exact_mut(a_row_len)
                 .zip(b_data.chunks_exact_mut(b_row_len))
             {
-                bin_pervade_recursive_mut(a, &ash[1..], b, &bsh[1..], f);
+                bin_pervade_recursive_mut(a, &ash[1..], b, &bsh[1..], check, f)?;
             }
             ash.len() > bsh.len()
         }
-    }
+    })
 }
 
 fn bin_pervade_recursive_mut_left<T>(
@@ -289,20 +356,17 @@ fn bin_pervade_recursive_mut_left<T>(
     a_shape: &[usize],
     b_data: &[T],
     b_shape: &[usize],
+    check: &mut InterruptCheck,
     f: impl Fn(T, T) -> T + Copy,
-) where
+) -> UiuaResult
+where
     T: ArrayValue + Copy,
 {
     match (a_shape, b_shape) {
         ([], _) => {
             panic!("should never call `bin_pervade_recursive_mut_left` with scalar left")
         }
-        (_, []) => {
-            let b_scalar = b_data[0];
-            for a in a_data {
-                *a = f(*a, b_scalar);
-            }
-        }
+        (_, []) => bin_pervade_scalar_mut_left(a_data, b_data[0], check, f)?,
         (ash, bsh) => {
             let a_row_len = a_data.len() / ash[0];
             let b_row_len = b_data.len() / bsh[0];
@@ -310,10 +374,11 @@ fn bin_pervade_recursive_mut_left<T>(
                 .chunks_exact_mut(a_row_len)
                 .zip(b_data.chunks_exact(b_row_len))
             {
-                bin_pervade_recursive_mut_left(a, &ash[1..], b, &bsh[1..], f);
+                bin_pervade_recursive_mut_left(a, &ash[1..], b, &bsh[1..], check, f)?;
             }
         }
     }
+    Ok(())
 }
 
 fn bin_pervade_recursive_mut_right<T>(
@@ -321,20 +386,17 @@ fn bin_pervade_recursive_mut_right<T>(
     a_shape: &[usize],
     b_data: &mut [T],
     b_shape: &[usize],
+    check: &mut InterruptCheck,
     f: impl Fn(T, T) -> T + Copy,
-) where
+) -> UiuaResult
+where
     T: ArrayValue + Copy,
 {
     match (a_shape, b_shape) {
         (_, []) => {
             panic!("should never call `bin_pervade_recursive_mut_right` with scalar right")
         }
-        ([], _) => {
-            let a_scalar = a_data[0];
-            for b in b_data {
-                *b = f(a_scalar, *b);
-            }
-        }
+        ([], _) => bin_pervade_scalar_mut_right(a_data[0], b_data, check, f)?,
         (ash, bsh) => {
             let a_row_len = a_data.len() / ash[0];
             let b_row_len = b_data.len() / bsh[0];
@@ -342,10 +404,11 @@ fn bin_pervade_recursive_mut_right<T>(
                 .chunks_exact(a_row_len)
                 .zip(b_data.chunks_exact_mut(b_row_len))
             {
-                bin_pervade_recursive_mut_right(a, &ash[1..], b, &bsh[1..], f);
+                bin_pervade_recursive_mut_right(a, &ash[1..], b, &bsh[1..], check, f)?;
             }
         }
     }
+    Ok(())
 }
 
 pub mod not {
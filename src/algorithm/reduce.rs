@@ -8,10 +8,11 @@ use crate::{
     algorithm::{
         loops::{flip, rank_list, rank_to_depth},
         pervade::*,
+        InterruptCheck, INTERRUPT_INTERVAL,
     },
     array::{Array, ArrayValue, Shape},
     cowslice::cowslice,
-    function::{Function, Signature},
+    function::{Function, FunctionId, Instr, Signature},
     value::Value,
     Primitive, Uiua, UiuaResult,
 };
@@ -35,25 +36,29 @@ pub fn reduce(env: &mut Uiua) -> UiuaResult {
             env.push(xs);
         }
         (Some((prim, flipped)), Value::Num(nums)) => env.push(match prim {
-            Primitive::Add => fast_reduce(nums, 0.0, add::num_num),
-            Primitive::Sub if flipped => fast_reduce(nums, 0.0, flip(sub::num_num)),
-            Primitive::Sub => fast_reduce(nums, 0.0, sub::num_num),
-            Primitive::Mul => fast_reduce(nums, 1.0, mul::num_num),
-            Primitive::Div if flipped => fast_reduce(nums, 1.0, flip(div::num_num)),
-            Primitive::Div => fast_reduce(nums, 1.0, div::num_num),
-            Primitive::Max => fast_reduce(nums, f64::NEG_INFINITY, max::num_num),
-            Primitive::Min => fast_reduce(nums, f64::INFINITY, min::num_num),
+            Primitive::Add => fast_reduce(nums, 0.0, env, add::num_num)?,
+            Primitive::Sub if flipped => fast_reduce(nums, 0.0, env, flip(sub::num_num))?,
+            Primitive::Sub => fast_reduce(nums, 0.0, env, sub::num_num)?,
+            Primitive::Mul => fast_reduce(nums, 1.0, env, mul::num_num)?,
+            Primitive::Div if flipped => fast_reduce(nums, 1.0, env, flip(div::num_num))?,
+            Primitive::Div => fast_reduce(nums, 1.0, env, div::num_num)?,
+            Primitive::Max => fast_reduce(nums, f64::NEG_INFINITY, env, max::num_num)?,
+            Primitive::Min => fast_reduce(nums, f64::INFINITY, env, min::num_num)?,
             _ => return generic_fold_right_1(f, Value::Num(nums), None, env),
         }),
         (Some((prim, flipped)), Value::Byte(bytes)) => env.push(match prim {
-            Primitive::Add => fast_reduce(bytes.convert(), 0.0, add::num_num),
-            Primitive::Sub if flipped => fast_reduce(bytes.convert(), 0.0, flip(sub::num_num)),
-            Primitive::Sub => fast_reduce(bytes.convert(), 0.0, sub::num_num),
-            Primitive::Mul => fast_reduce(bytes.convert(), 1.0, mul::num_num),
-            Primitive::Div if flipped => fast_reduce(bytes.convert(), 1.0, flip(div::num_num)),
-            Primitive::Div => fast_reduce(bytes.convert(), 1.0, div::num_num),
-            Primitive::Max => fast_reduce(bytes.convert(), f64::NEG_INFINITY, max::num_num),
-            Primitive::Min => fast_reduce(bytes.convert(), f64::INFINITY, min::num_num),
+            Primitive::Add => fast_reduce(bytes.convert(), 0.0, env, add::num_num)?,
+            Primitive::Sub if flipped => {
+                fast_reduce(bytes.convert(), 0.0, env, flip(sub::num_num))?
+            }
+            Primitive::Sub => fast_reduce(bytes.convert(), 0.0, env, sub::num_num)?,
+            Primitive::Mul => fast_reduce(bytes.convert(), 1.0, env, mul::num_num)?,
+            Primitive::Div if flipped => {
+                fast_reduce(bytes.convert(), 1.0, env, flip(div::num_num))?
+            }
+            Primitive::Div => fast_reduce(bytes.convert(), 1.0, env, div::num_num)?,
+            Primitive::Max => fast_reduce(bytes.convert(), f64::NEG_INFINITY, env, max::num_num)?,
+            Primitive::Min => fast_reduce(bytes.convert(), f64::INFINITY, env, min::num_num)?,
             _ => return generic_fold_right_1(f, Value::Byte(bytes), None, env),
         }),
         (_, xs) => generic_fold_right_1(f, xs, None, env)?,
@@ -61,16 +66,46 @@ pub fn reduce(env: &mut Uiua) -> UiuaResult {
     Ok(())
 }
 
-pub fn fast_reduce<T>(mut arr: Array<T>, identity: T, f: impl Fn(T, T) -> T) -> Array<T>
+/// Reduce a range with addition, using the closed form for scalar ranges
+pub fn range_sum(env: &mut Uiua) -> UiuaResult {
+    let max = env.pop(1)?;
+    if max.rank() == 0 {
+        if let Ok(n) = max.as_nat(env, "") {
+            let n = n as u128;
+            env.push((n * n.saturating_sub(1) / 2) as f64);
+            return Ok(());
+        }
+    }
+    env.push(max.range(env)?);
+    let span = env.span_index();
+    env.function_stack.push(Arc::new(Function::new(
+        FunctionId::Primitive(Primitive::Add),
+        vec![Instr::Prim(Primitive::Add, span)],
+        Signature::new(2, 1),
+    )));
+    reduce(env)
+}
+
+pub fn fast_reduce<T>(
+    mut arr: Array<T>,
+    identity: T,
+    env: &Uiua,
+    f: impl Fn(T, T) -> T,
+) -> UiuaResult<Array<T>>
 where
     T: ArrayValue + Copy,
 {
-    match arr.shape.len() {
+    let mut check = InterruptCheck::new(env);
+    Ok(match arr.shape.len() {
         0 => arr,
         1 => {
             let data = arr.data.as_mut_slice();
-            let reduced = data.iter().copied().reduce(f);
-            if let Some(reduced) = reduced {
+            if let Some((&first, rest)) = data.split_first() {
+                let mut reduced = first;
+                for chunk in rest.chunks(INTERRUPT_INTERVAL) {
+                    reduced = chunk.iter().copied().fold(reduced, &f);
+                    check.tick(chunk.len())?;
+                }
                 data[0] = reduced;
                 arr.data.truncate(1);
             } else {
@@ -83,27 +118,27 @@ where
             let row_len = arr.row_len();
             if row_len == 0 {
                 arr.shape.remove(0);
-                return Array::new(arr.shape, EcoVec::new());
+                return Ok(Array::new(arr.shape, EcoVec::new()));
             }
             let row_count = arr.row_count();
             if row_count == 0 {
                 arr.shape.remove(0);
                 let data = cowslice![identity; row_len];
-                return Array::new(arr.shape, data);
+                return Ok(Array::new(arr.shape, data));
             }
             let sliced = arr.data.as_mut_slice();
             let (acc, rest) = sliced.split_at_mut(row_len);
-            rest.chunks_exact(row_len).fold(acc, |acc, row| {
+            for row in rest.chunks_exact(row_len) {
                 for (a, b) in acc.iter_mut().zip(row) {
                     *a = f(*a, *b);
                 }
-                acc
-            });
+                check.tick(row_len)?;
+            }
             arr.data.truncate(row_len);
             arr.shape.remove(0);
             arr
         }
-    }
+    })
 }
 
 fn generic_fold_right_1(
@@ -9,6 +9,7 @@ use crate::{
     algorithm::{
         loops::{rank_list, rank_to_depth},
         pervade::*,
+        InterruptCheck,
     },
     array::{Array, ArrayValue, Shape},
     function::Function,
@@ -23,45 +24,40 @@ pub fn table(env: &mut Uiua) -> UiuaResult {
     let f = env.pop_function()?;
     let xs = env.pop(1)?;
     let ys = env.pop(2)?;
+    env.check_alloc::<f64>(xs.element_count().saturating_mul(ys.element_count()))?;
     match (f.as_flipped_primitive(), xs, ys) {
         (Some((prim, flipped)), Value::Num(xs), Value::Num(ys)) => {
-            if let Err((xs, ys)) = table_nums(prim, flipped, xs, ys, env) {
-                return generic_table(f, Value::Num(xs), Value::Num(ys), env);
-            }
+            table_nums(f, prim, flipped, xs, ys, env)?;
         }
         (Some((prim, flipped)), Value::Num(xs), Value::Byte(ys)) => {
             let ys = ys.convert();
-            if let Err((xs, ys)) = table_nums(prim, flipped, xs, ys, env) {
-                return generic_table(f, Value::Num(xs), Value::Num(ys), env);
-            }
+            table_nums(f, prim, flipped, xs, ys, env)?;
         }
         (Some((prim, flipped)), Value::Byte(xs), Value::Num(ys)) => {
             let xs = xs.convert();
-            if let Err((xs, ys)) = table_nums(prim, flipped, xs, ys, env) {
-                return generic_table(f, Value::Num(xs), Value::Num(ys), env);
-            }
+            table_nums(f, prim, flipped, xs, ys, env)?;
         }
         (Some((prim, flipped)), Value::Byte(xs), Value::Byte(ys)) => match prim {
-            Primitive::Eq => env.push(fast_table(xs, ys, is_eq::generic)),
-            Primitive::Ne => env.push(fast_table(xs, ys, is_ne::generic)),
-            Primitive::Lt if flipped => env.push(fast_table(xs, ys, flip(is_lt::generic))),
-            Primitive::Lt => env.push(fast_table(xs, ys, is_lt::generic)),
-            Primitive::Gt if flipped => env.push(fast_table(xs, ys, flip(is_gt::generic))),
-            Primitive::Gt => env.push(fast_table(xs, ys, is_gt::generic)),
-            Primitive::Le if flipped => env.push(fast_table(xs, ys, flip(is_le::generic))),
-            Primitive::Le => env.push(fast_table(xs, ys, is_le::generic)),
-            Primitive::Ge if flipped => env.push(fast_table(xs, ys, flip(is_ge::generic))),
-            Primitive::Ge => env.push(fast_table(xs, ys, is_ge::generic)),
-            Primitive::Add => env.push(fast_table(xs, ys, add::byte_byte)),
-            Primitive::Sub if flipped => env.push(fast_table(xs, ys, flip(sub::byte_byte))),
-            Primitive::Sub => env.push(fast_table(xs, ys, sub::byte_byte)),
-            Primitive::Mul => env.push(fast_table(xs, ys, mul::byte_byte)),
-            Primitive::Div if flipped => env.push(fast_table(xs, ys, flip(div::byte_byte))),
-            Primitive::Div => env.push(fast_table(xs, ys, flip(div::byte_byte))),
-            Primitive::Min => env.push(fast_table(xs, ys, min::byte_byte)),
-            Primitive::Max => env.push(fast_table(xs, ys, max::byte_byte)),
+            Primitive::Eq => env.push(fast_table(xs, ys, env, is_eq::generic)?),
+            Primitive::Ne => env.push(fast_table(xs, ys, env, is_ne::generic)?),
+            Primitive::Lt if flipped => env.push(fast_table(xs, ys, env, flip(is_lt::generic))?),
+            Primitive::Lt => env.push(fast_table(xs, ys, env, is_lt::generic)?),
+            Primitive::Gt if flipped => env.push(fast_table(xs, ys, env, flip(is_gt::generic))?),
+            Primitive::Gt => env.push(fast_table(xs, ys, env, is_gt::generic)?),
+            Primitive::Le if flipped => env.push(fast_table(xs, ys, env, flip(is_le::generic))?),
+            Primitive::Le => env.push(fast_table(xs, ys, env, is_le::generic)?),
+            Primitive::Ge if flipped => env.push(fast_table(xs, ys, env, flip(is_ge::generic))?),
+            Primitive::Ge => env.push(fast_table(xs, ys, env, is_ge::generic)?),
+            Primitive::Add => env.push(fast_table(xs, ys, env, add::byte_byte)?),
+            Primitive::Sub if flipped => env.push(fast_table(xs, ys, env, flip(sub::byte_byte))?),
+            Primitive::Sub => env.push(fast_table(xs, ys, env, sub::byte_byte)?),
+            Primitive::Mul => env.push(fast_table(xs, ys, env, mul::byte_byte)?),
+            Primitive::Div if flipped => env.push(fast_table(xs, ys, env, flip(div::byte_byte))?),
+            Primitive::Div => env.push(fast_table(xs, ys, env, flip(div::byte_byte))?),
+            Primitive::Min => env.push(fast_table(xs, ys, env, min::byte_byte)?),
+            Primitive::Max => env.push(fast_table(xs, ys, env, max::byte_byte)?),
             Primitive::Join | Primitive::Couple => {
-                env.push(fast_table_join_or_couple(xs, ys, flipped))
+                env.push(fast_table_join_or_couple(xs, ys, flipped, env)?)
             }
             _ => generic_table(f, Value::Byte(xs), Value::Byte(ys), env)?,
         },
@@ -70,35 +66,37 @@ pub fn table(env: &mut Uiua) -> UiuaResult {
     Ok(())
 }
 
-#[allow(clippy::result_large_err)]
 fn table_nums(
+    f: Arc<Function>,
     prim: Primitive,
     flipped: bool,
     xs: Array<f64>,
     ys: Array<f64>,
     env: &mut Uiua,
-) -> Result<(), (Array<f64>, Array<f64>)> {
+) -> UiuaResult {
     match prim {
-        Primitive::Eq => env.push(fast_table(xs, ys, is_eq::num_num)),
-        Primitive::Ne => env.push(fast_table(xs, ys, is_ne::num_num)),
-        Primitive::Lt if flipped => env.push(fast_table(xs, ys, flip(is_lt::num_num))),
-        Primitive::Lt => env.push(fast_table(xs, ys, is_lt::num_num)),
-        Primitive::Gt if flipped => env.push(fast_table(xs, ys, flip(is_gt::num_num))),
-        Primitive::Gt => env.push(fast_table(xs, ys, is_gt::num_num)),
-        Primitive::Le if flipped => env.push(fast_table(xs, ys, flip(is_le::num_num))),
-        Primitive::Le => env.push(fast_table(xs, ys, is_le::num_num)),
-        Primitive::Ge if flipped => env.push(fast_table(xs, ys, flip(is_ge::num_num))),
-        Primitive::Ge => env.push(fast_table(xs, ys, is_ge::num_num)),
-        Primitive::Add => env.push(fast_table(xs, ys, add::num_num)),
-        Primitive::Sub if flipped => env.push(fast_table(xs, ys, flip(sub::num_num))),
-        Primitive::Sub => env.push(fast_table(xs, ys, sub::num_num)),
-        Primitive::Mul => env.push(fast_table(xs, ys, mul::num_num)),
-        Primitive::Div if flipped => env.push(fast_table(xs, ys, flip(div::num_num))),
-        Primitive::Div => env.push(fast_table(xs, ys, flip(div::num_num))),
-        Primitive::Min => env.push(fast_table(xs, ys, min::num_num)),
-        Primitive::Max => env.push(fast_table(xs, ys, max::num_num)),
-        Primitive::Join | Primitive::Couple => env.push(fast_table_join_or_couple(xs, ys, flipped)),
-        _ => return Err((xs, ys)),
+        Primitive::Eq => env.push(fast_table(xs, ys, env, is_eq::num_num)?),
+        Primitive::Ne => env.push(fast_table(xs, ys, env, is_ne::num_num)?),
+        Primitive::Lt if flipped => env.push(fast_table(xs, ys, env, flip(is_lt::num_num))?),
+        Primitive::Lt => env.push(fast_table(xs, ys, env, is_lt::num_num)?),
+        Primitive::Gt if flipped => env.push(fast_table(xs, ys, env, flip(is_gt::num_num))?),
+        Primitive::Gt => env.push(fast_table(xs, ys, env, is_gt::num_num)?),
+        Primitive::Le if flipped => env.push(fast_table(xs, ys, env, flip(is_le::num_num))?),
+        Primitive::Le => env.push(fast_table(xs, ys, env, is_le::num_num)?),
+        Primitive::Ge if flipped => env.push(fast_table(xs, ys, env, flip(is_ge::num_num))?),
+        Primitive::Ge => env.push(fast_table(xs, ys, env, is_ge::num_num)?),
+        Primitive::Add => env.push(fast_table(xs, ys, env, add::num_num)?),
+        Primitive::Sub if flipped => env.push(fast_table(xs, ys, env, flip(sub::num_num))?),
+        Primitive::Sub => env.push(fast_table(xs, ys, env, sub::num_num)?),
+        Primitive::Mul => env.push(fast_table(xs, ys, env, mul::num_num)?),
+        Primitive::Div if flipped => env.push(fast_table(xs, ys, env, flip(div::num_num))?),
+        Primitive::Div => env.push(fast_table(xs, ys, env, flip(div::num_num))?),
+        Primitive::Min => env.push(fast_table(xs, ys, env, min::num_num)?),
+        Primitive::Max => env.push(fast_table(xs, ys, env, max::num_num)?),
+        Primitive::Join | Primitive::Couple => {
+            env.push(fast_table_join_or_couple(xs, ys, flipped, env)?)
+        }
+        _ => return generic_table(f, Value::Num(xs), Value::Num(ys), env),
     }
     Ok(())
 }
@@ -106,27 +104,37 @@ fn table_nums(
 fn fast_table<A: ArrayValue, B: ArrayValue, C: ArrayValue>(
     a: Array<A>,
     b: Array<B>,
+    env: &Uiua,
     f: impl Fn(A, B) -> C,
-) -> Array<C> {
+) -> UiuaResult<Array<C>> {
     let mut new_data = EcoVec::with_capacity(a.data.len() * b.data.len());
+    let mut check = InterruptCheck::new(env);
     for x in a.data {
         for y in b.data.iter().cloned() {
             new_data.push(f(x.clone(), y));
         }
+        check.tick(b.data.len())?;
     }
     let mut new_shape = a.shape;
     new_shape.extend_from_slice(&b.shape);
-    Array::new(new_shape, new_data)
+    Ok(Array::new(new_shape, new_data))
 }
 
-fn fast_table_join_or_couple<T: ArrayValue>(a: Array<T>, b: Array<T>, flipped: bool) -> Array<T> {
+fn fast_table_join_or_couple<T: ArrayValue>(
+    a: Array<T>,
+    b: Array<T>,
+    flipped: bool,
+    env: &Uiua,
+) -> UiuaResult<Array<T>> {
     let mut new_data = EcoVec::with_capacity(a.data.len() * b.data.len() * 2);
+    let mut check = InterruptCheck::new(env);
     if flipped {
         for x in a.data {
             for y in b.data.iter().cloned() {
                 new_data.push(y);
                 new_data.push(x.clone());
             }
+            check.tick(b.data.len())?;
         }
     } else {
         for x in a.data {
@@ -134,12 +142,13 @@ fn fast_table_join_or_couple<T: ArrayValue>(a: Array<T>, b: Array<T>, flipped: b
                 new_data.push(x.clone());
                 new_data.push(y);
             }
+            check.tick(b.data.len())?;
         }
     }
     let mut new_shape = a.shape;
     new_shape.extend_from_slice(&b.shape);
     new_shape.push(2);
-    Array::new(new_shape, new_data)
+    Ok(Array::new(new_shape, new_data))
 }
 
 fn generic_table(f: Arc<Function>, xs: Value, ys: Value, env: &mut Uiua) -> UiuaResult {
@@ -271,3 +280,20 @@ fn multi_combinate_recursive(
         Ok(res.finish())
     }
 }
+
+#[test]
+fn native_loops_can_be_interrupted() {
+    use crate::algorithm::reduce::fast_reduce;
+
+    let env = Uiua::with_native_sys();
+    let xs: Array<f64> = (0..1000).map(|i| i as f64).collect();
+    let tabled = fast_table(xs.clone(), xs.clone(), &env, add::num_num).unwrap();
+    env.interrupt_handle().interrupt();
+    let err = fast_table(xs.clone(), xs, &env, add::num_num).unwrap_err();
+    assert!(err.is_interrupted());
+    let err = fast_reduce(tabled.clone(), 0.0, &env, add::num_num).unwrap_err();
+    assert!(err.is_interrupted());
+    let mut ys = tabled.clone();
+    let err = bin_pervade_mut(&mut ys, tabled, &env, add::num_num).unwrap_err();
+    assert!(err.is_interrupted());
+}
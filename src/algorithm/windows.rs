@@ -0,0 +1,195 @@
+//! Fused algorithms for modifiers applied to windows
+//!
+//! `≡f◫` and `/f◫` with a scalar window size are evaluated by sliding over
+//! the source array rather than building every window first.
+
+use std::{borrow::Cow, collections::VecDeque, sync::Arc};
+
+use crate::{
+    algorithm::{pervade::*, reduce, zip},
+    array::{Array, ArrayValue, Shape},
+    cowslice::CowSlice,
+    function::{Function, Instr},
+    value::Value,
+    Primitive, Uiua, UiuaResult,
+};
+
+/// `rows` over `windows`
+pub fn rows_windows(env: &mut Uiua) -> UiuaResult {
+    crate::profile_function!();
+    let f = env.pop_function()?;
+    let size = env.pop(1)?;
+    let xs = env.pop(2)?;
+    let Some(size) = window_size(&size, &xs, env).filter(|_| f.signature() == (1, 1)) else {
+        return unfused(f, size, xs, zip::rows, env);
+    };
+    let count = xs.row_count() + 1 - size;
+    // Reductions over each window
+    if let [Instr::PushFunc(g), Instr::Prim(Primitive::Reduce, _)] = f.instrs.as_slice() {
+        if let Some((prim, _)) = g.as_flipped_primitive() {
+            if let Some(reduced) = sliding_reduce(prim, &xs, size) {
+                env.push(reduced);
+                return Ok(());
+            }
+        }
+    }
+    // Any other function is called on each window as it is made
+    let mut new_rows = Value::builder(count);
+    let mut windows = (0..count).map(|start| window(&xs, start, size));
+    for win in windows.by_ref() {
+        env.push(win);
+        let broke = env.call_catch_break(f.clone())?;
+        new_rows.add_row(env.pop("rows' function result")?, env)?;
+        if broke {
+            for win in windows {
+                new_rows.add_row(win, env)?;
+            }
+            break;
+        }
+    }
+    env.push(new_rows.finish());
+    Ok(())
+}
+
+/// `reduce` over `windows`
+pub fn reduce_windows(env: &mut Uiua) -> UiuaResult {
+    crate::profile_function!();
+    let f = env.pop_function()?;
+    let size = env.pop(1)?;
+    let xs = env.pop(2)?;
+    if let (Some(size), Some((prim, _))) = (window_size(&size, &xs, env), f.as_flipped_primitive())
+    {
+        // Reducing the windows folds each of the `size` positions
+        // in a window over every window
+        let width = xs.row_count() + 1 - size;
+        if let Some(reduced) = sliding_reduce(prim, &xs, width) {
+            env.push(reduced);
+            return Ok(());
+        }
+    }
+    unfused(f, size, xs, reduce::reduce, env)
+}
+
+/// Get the window size if it is a scalar that makes at least one non-empty window
+fn window_size(size: &Value, xs: &Value, env: &Uiua) -> Option<usize> {
+    if size.rank() != 0 || xs.rank() == 0 {
+        return None;
+    }
+    let size = size.as_nat(env, "").ok()?;
+    (size > 0 && size <= xs.row_count()).then_some(size)
+}
+
+/// Build the windows and run the modifier on them
+fn unfused(
+    f: Arc<Function>,
+    size: Value,
+    xs: Value,
+    modifier: fn(&mut Uiua) -> UiuaResult,
+    env: &mut Uiua,
+) -> UiuaResult {
+    let windows = size.windows(&xs, env)?;
+    env.push(windows);
+    env.function_stack.push(f);
+    modifier(env)
+}
+
+fn window(xs: &Value, start: usize, size: usize) -> Value {
+    match xs {
+        Value::Num(a) => window_array(a, start, size).into(),
+        Value::Byte(a) => window_array(a, start, size).into(),
+        Value::Char(a) => window_array(a, start, size).into(),
+        Value::Box(a) => window_array(a, start, size).into(),
+    }
+}
+
+fn window_array<T: ArrayValue>(arr: &Array<T>, start: usize, size: usize) -> Array<T> {
+    let row_len = arr.row_len();
+    let mut shape = arr.shape.clone();
+    shape[0] = size;
+    Array::new(
+        shape,
+        arr.data.slice(start * row_len..(start + size) * row_len),
+    )
+}
+
+/// Reduce every run of `width` consecutive rows with `add`, `maximum`, or `minimum`
+///
+/// Returns `None` if the array or function has no fast path
+fn sliding_reduce(prim: Primitive, xs: &Value, width: usize) -> Option<Array<f64>> {
+    let data: Cow<[f64]> = match xs {
+        Value::Num(a) => Cow::Borrowed(&a.data[..]),
+        Value::Byte(a) => Cow::Owned(a.data.iter().map(|&b| b as f64).collect()),
+        _ => return None,
+    };
+    let row_len = xs.row_len();
+    let count = xs.row_count() + 1 - width;
+    let mut shape = Shape::from(xs.shape());
+    shape[0] = count;
+    let mut out = vec![0.0; count * row_len];
+    let columns = (0..row_len).map(|c| {
+        let column = data[c..].iter().step_by(row_len.max(1)).copied();
+        (c, column.collect::<Vec<_>>())
+    });
+    match prim {
+        Primitive::Add => {
+            // Running sums are only used when they give the same result as
+            // summing each window in order
+            let exact = data
+                .iter()
+                .all(|n| n.fract() == 0.0 && !(*n == 0.0 && n.is_sign_negative()))
+                && data.iter().map(|n| n.abs()).sum::<f64>()
+                    <= (1u64 << f64::MANTISSA_DIGITS) as f64;
+            for (c, column) in columns {
+                if exact {
+                    let mut sum = column[..width]
+                        .iter()
+                        .copied()
+                        .reduce(add::num_num)
+                        .unwrap();
+                    out[c] = sum;
+                    for i in 1..count {
+                        sum += column[i + width - 1] - column[i - 1];
+                        out[i * row_len + c] = sum;
+                    }
+                } else {
+                    for i in 0..count {
+                        let window = column[i..i + width].iter().copied();
+                        out[i * row_len + c] = window.reduce(add::num_num).unwrap();
+                    }
+                }
+            }
+        }
+        Primitive::Max | Primitive::Min => {
+            let is_max = prim == Primitive::Max;
+            let f = if is_max { max::num_num } else { min::num_num };
+            // Whether an earlier value stays a candidate when a later one is added
+            let keep = |a: f64, b: f64| if is_max { a > b } else { a < b };
+            let has_nan = data.iter().any(|n| n.is_nan());
+            for (c, column) in columns {
+                if has_nan {
+                    for i in 0..count {
+                        let window = column[i..i + width].iter().copied();
+                        out[i * row_len + c] = window.reduce(f).unwrap();
+                    }
+                    continue;
+                }
+                // Monotonic deque of the indices of window extrema candidates
+                let mut deque = VecDeque::with_capacity(width);
+                for (i, &n) in column.iter().enumerate() {
+                    while deque.back().is_some_and(|&j| !keep(column[j], n)) {
+                        deque.pop_back();
+                    }
+                    deque.push_back(i);
+                    if deque[0] + width <= i {
+                        deque.pop_front();
+                    }
+                    if i + 1 >= width {
+                        out[(i + 1 - width) * row_len + c] = column[deque[0]];
+                    }
+                }
+            }
+        }
+        _ => return None,
+    }
+    Some(Array::new(shape, out.into_iter().collect::<CowSlice<_>>()))
+}
@@ -2,12 +2,14 @@ use std::{
     cmp::Ordering,
     fmt::{self, Debug, Display},
     hash::{Hash, Hasher},
+    sync::Arc,
 };
 
 use ecow::EcoVec;
 use tinyvec::{tiny_vec, TinyVec};
 
 use crate::{
+    algorithm::map::MapKeys,
     boxed::Boxed,
     cowslice::{cowslice, CowSlice},
     grid_fmt::GridFmt,
@@ -20,6 +22,8 @@ use crate::{
 pub struct Array<T> {
     pub(crate) shape: Shape,
     pub(crate) data: CowSlice<T>,
+    /// The keys if the array is a map
+    pub(crate) map_keys: Option<Arc<MapKeys>>,
 }
 
 /// Uiua's array shape type
@@ -30,6 +34,7 @@ impl<T: ArrayValue> Default for Array<T> {
         Self {
             shape: tiny_vec![0],
             data: CowSlice::new(),
+            map_keys: None,
         }
     }
 }
@@ -48,6 +53,9 @@ where
     Array<T>: GridFmt,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.map_keys.is_some() {
+            return write!(f, "\n{}", self.grid_string());
+        }
         match self.rank() {
             0 => write!(f, "{}", self.data[0]),
             1 => {
@@ -89,7 +97,11 @@ impl<T> Array<T> {
         let shape = shape.into();
         let data = data.into();
         validate_shape(&shape, &data);
-        Self { shape, data }
+        Self {
+            shape,
+            data,
+            map_keys: None,
+        }
     }
     #[track_caller]
     #[inline(always)]
@@ -194,6 +206,7 @@ impl<T: ArrayValue> Array<T> {
         Array {
             shape: self.shape,
             data: self.data.into_iter().map(f).collect(),
+            map_keys: self.map_keys,
         }
     }
     /// Convert the elements of the array with a fallible function
@@ -204,6 +217,7 @@ impl<T: ArrayValue> Array<T> {
         Ok(Array {
             shape: self.shape,
             data: self.data.into_iter().map(f).collect::<Result<_, _>>()?,
+            map_keys: self.map_keys,
         })
     }
     /// Convert the elements of the array without consuming it
@@ -219,6 +233,7 @@ impl<T: ArrayValue> Array<T> {
         Array {
             shape: self.shape.clone(),
             data: self.data.iter().cloned().map(f).collect(),
+            map_keys: self.map_keys.clone(),
         }
     }
     /// Consume the array and get an iterator over its rows
@@ -256,6 +271,11 @@ impl<T: ArrayValue + ArrayCmp<U>, U: ArrayValue> PartialEq<Array<U>> for Array<T
         if self.shape() != other.shape() {
             return false;
         }
+        if self.map_keys.as_ref().map(|keys| keys.keys())
+            != other.map_keys.as_ref().map(|keys| keys.keys())
+        {
+            return false;
+        }
         self.data
             .iter()
             .zip(&other.data)
@@ -277,7 +297,13 @@ impl<T: ArrayValue + ArrayCmp<U>, U: ArrayValue> PartialOrd<Array<U>> for Array<
             .zip(&other.data)
             .map(|(a, b)| a.array_cmp(b))
             .find(|o| o != &Ordering::Equal)
-            .unwrap_or_else(|| self.data.len().cmp(&other.data.len()));
+            .unwrap_or_else(|| self.data.len().cmp(&other.data.len()))
+            // Arrays are only equal if their shapes and map keys are
+            .then_with(|| self.shape().cmp(other.shape()))
+            .then_with(|| {
+                (self.map_keys.as_ref().map(|keys| keys.keys()))
+                    .cmp(&other.map_keys.as_ref().map(|keys| keys.keys()))
+            });
         Some(cmp)
     }
 }
@@ -291,6 +317,9 @@ impl<T: ArrayValue> Ord for Array<T> {
 impl<T: ArrayValue> Hash for Array<T> {
     fn hash<H: Hasher>(&self, hasher: &mut H) {
         self.shape.hash(hasher);
+        if let Some(keys) = &self.map_keys {
+            keys.keys().hash(hasher);
+        }
         self.data.iter().for_each(|x| x.array_hash(hasher));
     }
 }
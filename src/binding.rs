@@ -0,0 +1,398 @@
+//! Calling Uiua bindings from Rust
+//!
+//! [`Uiua::get_binding`] gets a [`UiuaFn`] handle to a binding that has been loaded,
+//! and [`Uiua::call_binding`] calls a binding by name. Arguments are anything that
+//! implements [`IntoValues`] and results are anything that implements [`FromValues`].
+//!
+//! Arguments and results are in the order they would be written in Uiua code, so the
+//! first argument is on top of the stack when the function is called, and the first
+//! result is the one on top of the stack when it returns.
+
+use std::{fmt, sync::Arc};
+
+use tinyvec::tiny_vec;
+
+use crate::{
+    cowslice::CowSlice,
+    function::{Function, FunctionId, Instr, Signature},
+    run::Global,
+    Array, Boxed, Ident, Uiua, UiuaResult, Value,
+};
+
+/// A trait for types that can be converted into a [`Value`]
+pub trait IntoValue {
+    /// Convert into a value
+    fn into_value(self) -> Value;
+}
+
+/// A trait for types that can be converted from a [`Value`]
+pub trait FromValue: Sized {
+    /// Convert from a value
+    ///
+    /// The runtime is used for error reporting.
+    fn from_value(value: Value, env: &Uiua) -> UiuaResult<Self>;
+}
+
+/// A trait for types that can be used as the arguments of a Uiua function
+///
+/// This is implemented for every [`IntoValue`] type, for tuples of them, and for `Vec<Value>`.
+pub trait IntoValues {
+    /// Convert into a list of values, with the first argument first
+    fn into_values(self) -> Vec<Value>;
+}
+
+/// A trait for types that can be used as the results of a Uiua function
+///
+/// This is implemented for every [`FromValue`] type, for tuples of them, and for `Vec<Value>`.
+pub trait FromValues: Sized {
+    /// The number of values expected, or `None` if any number is allowed
+    const COUNT: Option<usize>;
+    /// Convert from a list of values, with the first result first
+    ///
+    /// The runtime is used for error reporting.
+    fn from_values(values: Vec<Value>, env: &Uiua) -> UiuaResult<Self>;
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        self.into()
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        self.into()
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        self.into()
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        self.into()
+    }
+}
+
+impl IntoValue for Vec<f64> {
+    fn into_value(self) -> Value {
+        self.into_iter().collect()
+    }
+}
+
+/// Rows that all have the same length become a rank 2 array. Otherwise, each row is boxed.
+impl IntoValue for Vec<Vec<f64>> {
+    fn into_value(self) -> Value {
+        let row_len = self.first().map_or(0, Vec::len);
+        if self.iter().all(|row| row.len() == row_len) {
+            let shape = tiny_vec![self.len(), row_len];
+            let data: CowSlice<f64> = self.into_iter().flatten().collect();
+            Array::new(shape, data).into()
+        } else {
+            Array::from_iter(self.into_iter().map(|row| Boxed(row.into_value()))).into()
+        }
+    }
+}
+
+/// Each string is boxed
+impl IntoValue for Vec<String> {
+    fn into_value(self) -> Value {
+        Array::<Boxed>::from_iter(self).into()
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value, _: &Uiua) -> UiuaResult<Self> {
+        Ok(value)
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: Value, env: &Uiua) -> UiuaResult<Self> {
+        value.unpacked().as_num(env, "")
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value, env: &Uiua) -> UiuaResult<Self> {
+        value.unpacked().as_bool(env, "")
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value, env: &Uiua) -> UiuaResult<Self> {
+        value.as_string(env, "")
+    }
+}
+
+impl FromValue for Vec<f64> {
+    fn from_value(value: Value, env: &Uiua) -> UiuaResult<Self> {
+        value
+            .unpacked()
+            .as_nums(env, "Expected value to be a list of numbers")
+    }
+}
+
+impl FromValue for Vec<Vec<f64>> {
+    fn from_value(value: Value, env: &Uiua) -> UiuaResult<Self> {
+        let requirement = "Expected value to be a list of lists of numbers";
+        if value.rank() == 0 {
+            return Err(env.error(format!("{requirement}, but it is a scalar")));
+        }
+        (value.into_rows())
+            .map(|row| row.unpacked().as_nums(env, requirement))
+            .collect()
+    }
+}
+
+impl FromValue for Vec<String> {
+    fn from_value(value: Value, env: &Uiua) -> UiuaResult<Self> {
+        let requirement = "Expected value to be a list of strings";
+        if value.rank() == 0 {
+            return Err(env.error(format!("{requirement}, but it is a scalar")));
+        }
+        (value.into_rows())
+            .map(|row| row.as_string(env, requirement))
+            .collect()
+    }
+}
+
+impl<T: IntoValue> IntoValues for T {
+    fn into_values(self) -> Vec<Value> {
+        vec![self.into_value()]
+    }
+}
+
+impl IntoValues for Vec<Value> {
+    fn into_values(self) -> Vec<Value> {
+        self
+    }
+}
+
+impl<T: FromValue> FromValues for T {
+    const COUNT: Option<usize> = Some(1);
+    fn from_values(values: Vec<Value>, env: &Uiua) -> UiuaResult<Self> {
+        T::from_value(values.into_iter().next().unwrap_or_default(), env)
+    }
+}
+
+impl FromValues for Vec<Value> {
+    const COUNT: Option<usize> = None;
+    fn from_values(values: Vec<Value>, _: &Uiua) -> UiuaResult<Self> {
+        Ok(values)
+    }
+}
+
+macro_rules! tuple_values {
+    ($count:literal, $($name:ident),*) => {
+        impl<$($name: IntoValue),*> IntoValues for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn into_values(self) -> Vec<Value> {
+                let ($($name,)*) = self;
+                vec![$($name.into_value()),*]
+            }
+        }
+        impl<$($name: FromValue),*> FromValues for ($($name,)*) {
+            const COUNT: Option<usize> = Some($count);
+            #[allow(non_snake_case, unused_variables, unused_mut, clippy::unused_unit)]
+            fn from_values(values: Vec<Value>, env: &Uiua) -> UiuaResult<Self> {
+                let mut values = values.into_iter();
+                $(let $name = $name::from_value(values.next().unwrap_or_default(), env)?;)*
+                Ok(($($name,)*))
+            }
+        }
+    };
+}
+
+tuple_values!(0,);
+tuple_values!(1, A);
+tuple_values!(2, A, B);
+tuple_values!(3, A, B, C);
+tuple_values!(4, A, B, C, D);
+tuple_values!(5, A, B, C, D, E);
+tuple_values!(6, A, B, C, D, E, F);
+
+/// A handle to a Uiua binding that can be called from Rust
+///
+/// Get one with [`Uiua::get_binding`].
+#[derive(Clone)]
+pub struct UiuaFn {
+    name: Ident,
+    function: Arc<Function>,
+}
+
+impl fmt::Debug for UiuaFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UiuaFn({} {})", self.name, self.function.signature())
+    }
+}
+
+impl UiuaFn {
+    /// Get the binding's name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Get the binding's signature
+    ///
+    /// Bindings to values have a signature of `|0.1`.
+    pub fn signature(&self) -> Signature {
+        self.function.signature()
+    }
+    /// Call the binding
+    ///
+    /// The stack is left as it was before the call, even if the call fails.
+    ///
+    /// # Errors
+    /// Returns an error if the number of arguments or results does not match the binding's
+    /// signature, if the binding's code fails, or if a result cannot be converted.
+    pub fn call<A: IntoValues, R: FromValues>(&self, env: &mut Uiua, args: A) -> UiuaResult<R> {
+        let sig = self.signature();
+        let args = args.into_values();
+        if args.len() != sig.args {
+            return Err(env.error(format!(
+                "{} expects {} argument{}, but {} {} given",
+                self.name,
+                sig.args,
+                if sig.args == 1 { "" } else { "s" },
+                args.len(),
+                if args.len() == 1 { "was" } else { "were" }
+            )));
+        }
+        if let Some(count) = R::COUNT.filter(|&count| count != sig.outputs) {
+            return Err(env.error(format!(
+                "{} returns {} value{}, but {count} {} expected",
+                self.name,
+                sig.outputs,
+                if sig.outputs == 1 { "" } else { "s" },
+                if count == 1 { "was" } else { "were" }
+            )));
+        }
+        let start_height = env.stack.len();
+        env.stack.extend(args.into_iter().rev());
+        if let Err(e) = env.call(self.function.clone()) {
+            env.stack.truncate(start_height);
+            return Err(e);
+        }
+        let mut outputs = env.stack.split_off(start_height.min(env.stack.len()));
+        outputs.reverse();
+        R::from_values(outputs, env)
+    }
+}
+
+impl Uiua {
+    /// Get a handle to a binding in the current scope
+    ///
+    /// Returns `None` if there is no binding with the given name.
+    ///
+    /// ```
+    /// # use uiua::*;
+    /// let mut env = Uiua::with_native_sys();
+    /// env.load_str("Avg ← ÷⧻:/+.").unwrap();
+    /// let avg = env.get_binding("Avg").unwrap();
+    /// assert_eq!(avg.signature(), Signature::new(1, 1));
+    /// let res: f64 = avg.call(&mut env, vec![1.0, 2.0, 6.0]).unwrap();
+    /// assert_eq!(res, 3.0);
+    /// ```
+    pub fn get_binding(&self, name: &str) -> Option<UiuaFn> {
+        let idx = *self.scope.names.get(name)?;
+        let function = match self.globals.lock().get(idx)?.clone() {
+            Global::Func(f) => f,
+            Global::Val(val) => Arc::new(Function::new(
+                FunctionId::Named(name.into()),
+                vec![Instr::push(val)],
+                Signature::new(0, 1),
+            )),
+        };
+        Some(UiuaFn {
+            name: name.into(),
+            function,
+        })
+    }
+    /// Call a binding in the current scope by name
+    ///
+    /// See [`UiuaFn::call`].
+    ///
+    /// ```
+    /// # use uiua::*;
+    /// let mut env = Uiua::with_native_sys();
+    /// env.load_str("Greet ← ⊂⊂:\", \"").unwrap();
+    /// let res: Vec<Value> = env.call_binding("Greet", ("Hello", "World")).unwrap();
+    /// assert_eq!(res, [Value::from("Hello, World")]);
+    /// let res: String = env.call_binding("Greet", ("Hi", "you")).unwrap();
+    /// assert_eq!(res, "Hi, you");
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if there is no binding with the given name
+    /// or if [`UiuaFn::call`] fails.
+    pub fn call_binding<A: IntoValues, R: FromValues>(
+        &mut self,
+        name: &str,
+        args: A,
+    ) -> UiuaResult<R> {
+        let f = (self.get_binding(name))
+            .ok_or_else(|| self.error(format!("Unknown binding {name}")))?;
+        f.call(self, args)
+    }
+}
+
+#[test]
+fn call_binding() {
+    let mut env = Uiua::with_native_sys();
+    env.load_str(
+        "\
+Scale ← ×
+Split ← ⊃↙↘
+Flip ← ⇌
+Words ← ⊜□≠@ .
+Half ← 0.5
+Not ← ¬",
+    )
+    .unwrap();
+    env.push(42);
+    let res: Vec<f64> = env.call_binding("Scale", (2.0, vec![1.0, 2.0])).unwrap();
+    assert_eq!(res, [2.0, 4.0]);
+    let (first, rest): (Vec<f64>, Vec<f64>) = env
+        .call_binding("Split", (2.0, vec![1.0, 2.0, 3.0]))
+        .unwrap();
+    assert_eq!((first, rest), (vec![1.0, 2.0], vec![3.0]));
+    let matrix = vec![vec![1.0, 2.0], vec![3.0, 4.0]];
+    let res: Vec<Vec<f64>> = env.call_binding("Flip", matrix).unwrap();
+    assert_eq!(res, [vec![3.0, 4.0], vec![1.0, 2.0]]);
+    let ragged = vec![vec![1.0], vec![2.0, 3.0]];
+    let res: Vec<Vec<f64>> = env.call_binding("Flip", ragged).unwrap();
+    assert_eq!(res, [vec![2.0, 3.0], vec![1.0]]);
+    let res: Vec<String> = env.call_binding("Words", "hello uiua world").unwrap();
+    assert_eq!(res, ["hello", "uiua", "world"]);
+    let res: Vec<String> = env
+        .call_binding("Flip", vec!["a".to_string(), "bc".into()])
+        .unwrap();
+    assert_eq!(res, ["bc", "a"]);
+    let half: f64 = env.call_binding("Half", ()).unwrap();
+    assert_eq!(half, 0.5);
+    let res: bool = env.call_binding("Not", true).unwrap();
+    assert!(!res);
+
+    let err = (env.call_binding::<_, f64>("Scale", 2.0)).unwrap_err();
+    assert!(err
+        .message()
+        .contains("expects 2 arguments, but 1 was given"));
+    let err = (env.call_binding::<_, f64>("Split", (2.0, vec![1.0]))).unwrap_err();
+    assert!(err
+        .message()
+        .contains("returns 2 values, but 1 was expected"));
+    let err = (env.call_binding::<_, Vec<Value>>("Missing", ())).unwrap_err();
+    assert!(err.message().contains("Unknown binding Missing"));
+    assert!(env.call_binding::<_, f64>("Scale", ("a", 1.0)).is_err());
+    assert_eq!(env.pop_num().unwrap(), 42.0);
+    assert!(env.take_stack().is_empty());
+}
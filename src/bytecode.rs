@@ -0,0 +1,1027 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    mem::replace,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+use enum_iterator::all;
+
+use crate::{
+    array::{Array, Shape},
+    boxed::Boxed,
+    cowslice::CowSlice,
+    function::*,
+    lex::{CodeSpan, Loc, Span},
+    primitive::{ImplPrimitive, Primitive},
+    run::{Global, Import, RunMode},
+    value::Value,
+    Ident, SysOp, Uiua, UiuaError, UiuaResult,
+};
+
+const MAGIC: &[u8] = b"UIUAC\0";
+/// The deepest that boxes and maps can be nested in a decoded value
+const MAX_VALUE_DEPTH: usize = 256;
+
+/// Uiua code that has been compiled ahead of time
+///
+/// A module is created with [`Uiua::compile_file`] or [`Uiua::compile_str`] and run with
+/// [`Uiua::load_module`]. It can be converted to and from a compact binary format with
+/// [`CompiledModule::to_bytes`] and [`CompiledModule::from_bytes`], so it can be run
+/// later without its source code.
+///
+/// Compiling a module evaluates its bindings but not its other top-level code.
+/// Bound values are stored in the module, so a binding that uses [`random`](Primitive::Rand)
+/// or [`now`](Primitive::Now) keeps the value it had at compile time.
+/// Compiling a binding whose value comes from a system function that does IO is an error.
+///
+/// ```
+/// use uiua::*;
+///
+/// let mut uiua = Uiua::with_native_sys();
+/// let module = uiua.compile_str("Double ← ×2\nDouble 21").unwrap();
+/// assert!(uiua.take_stack().is_empty());
+/// let bytes = module.to_bytes().unwrap();
+///
+/// let mut uiua = Uiua::with_native_sys();
+/// uiua.load_module(&CompiledModule::from_bytes(&bytes).unwrap()).unwrap();
+/// assert_eq!(uiua.pop_int().unwrap(), 42);
+/// ```
+#[derive(Clone)]
+pub struct CompiledModule {
+    pub(crate) steps: Vec<Step>,
+    /// The spans referenced by the module's instructions
+    pub(crate) spans: Vec<Span>,
+    /// The imports that were compiled into the module, along with their keys
+    pub(crate) deps: Vec<(PathBuf, u64)>,
+}
+
+/// A top-level step of a compiled module
+#[derive(Clone)]
+pub(crate) enum Step {
+    /// Run some instructions
+    Run(Vec<Instr>),
+    /// Bind a name
+    Bind(Ident, Global),
+    /// Run some steps in a test scope
+    Scope(Vec<Step>),
+}
+
+/// The steps taken while loading a module
+#[derive(Clone)]
+pub(crate) struct Recording {
+    pub steps: Vec<Step>,
+    pub deps: Vec<(PathBuf, u64)>,
+    /// The scope depth of the module
+    pub depth: usize,
+    /// Whether the compiled module may behave differently from its source when run later
+    pub uncacheable: bool,
+}
+
+impl Recording {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            steps: Vec::new(),
+            deps: Vec::new(),
+            depth,
+            uncacheable: false,
+        }
+    }
+}
+
+/// Hash some bytes with FNV-1a, which unlike the std hasher is stable across Rust versions
+fn fnv1a<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Get a hash of the primitive tables
+///
+/// Primitives are stored by their index in the tables, so a module compiled
+/// by a build whose primitives differ cannot be decoded.
+fn primitives_hash() -> u64 {
+    static HASH: OnceLock<u64> = OnceLock::new();
+    *HASH.get_or_init(|| {
+        let mut names = String::new();
+        for prim in all::<Primitive>() {
+            names.push_str(&format!("{prim:?}\n"));
+        }
+        for prim in all::<ImplPrimitive>() {
+            names.push_str(&format!("{prim:?}\n"));
+        }
+        fnv1a(names.as_bytes())
+    })
+}
+
+/// Get the key used to identify a module in the module cache
+pub(crate) fn module_key(input: &str, mode: RunMode, optimize: bool) -> u64 {
+    let prims = primitives_hash().to_le_bytes();
+    fnv1a(
+        (input.as_bytes().iter())
+            .chain(&[mode as u8, optimize as u8])
+            .chain(&prims),
+    )
+}
+
+/// Find a primitive whose result can change between runs
+pub(crate) fn impure_primitive(instrs: &[Instr]) -> Option<Primitive> {
+    instrs.iter().find_map(|instr| match instr {
+        Instr::Prim(prim @ (Primitive::Rand | Primitive::Now), _) => Some(*prim),
+        Instr::Prim(prim @ Primitive::Sys(op), _) if *op != SysOp::Import && !op.is_pure() => {
+            Some(*prim)
+        }
+        Instr::PushFunc(f) => impure_primitive(&f.instrs),
+        _ => None,
+    })
+}
+
+impl Uiua {
+    /// Compile a Uiua file into a [`CompiledModule`]
+    ///
+    /// Bindings are evaluated, but other top-level code is not run.
+    pub fn compile_file<P: AsRef<Path>>(&mut self, path: P) -> UiuaResult<CompiledModule> {
+        let path = path.as_ref();
+        let input = fs::read_to_string(path).map_err(|e| UiuaError::Load(path.into(), e.into()))?;
+        self.compile_impl(&input, Some(path))
+    }
+    /// Compile a string into a [`CompiledModule`]
+    ///
+    /// Bindings are evaluated, but other top-level code is not run.
+    pub fn compile_str(&mut self, input: &str) -> UiuaResult<CompiledModule> {
+        self.compile_impl(input, None)
+    }
+    fn compile_impl(&mut self, input: &str, path: Option<&Path>) -> UiuaResult<CompiledModule> {
+        let compile_only = replace(&mut self.compile_only, true);
+        let depth = self.higher_scopes.len();
+        let outer = self.recording.replace(Recording::new(depth));
+        let res = match path {
+            Some(path) => self.load_str_path(input, path),
+            None => self.load_str(input),
+        };
+        self.compile_only = compile_only;
+        let recording = replace(&mut self.recording, outer).unwrap();
+        res?;
+        Ok(self.finish_recording(recording))
+    }
+    /// Run a [`CompiledModule`]
+    pub fn load_module(&mut self, module: &CompiledModule) -> UiuaResult {
+        self.execution_start = instant::now();
+        let base = {
+            let mut spans = self.spans.lock();
+            let base = spans.len();
+            spans.extend(module.spans.iter().cloned());
+            base
+        };
+        let steps = SpanMap::new(|span| base + span).steps(&module.steps);
+        self.run_steps(steps)
+    }
+    /// Load a [`CompiledModule`] from a file and run it
+    pub fn load_module_file<P: AsRef<Path>>(&mut self, path: P) -> UiuaResult {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|e| UiuaError::Load(path.into(), e.into()))?;
+        let module = CompiledModule::from_bytes(&bytes).map_err(|e| {
+            UiuaError::Load(
+                path.into(),
+                io::Error::new(io::ErrorKind::InvalidData, e).into(),
+            )
+        })?;
+        self.load_module(&module)
+    }
+    pub(crate) fn run_steps(&mut self, steps: Vec<Step>) -> UiuaResult {
+        for step in steps {
+            match step {
+                Step::Run(instrs) => self.exec_global_instrs(instrs)?,
+                Step::Bind(name, Global::Val(value)) => {
+                    self.compile_bind_value(name, value, Span::Builtin)?
+                }
+                Step::Bind(name, Global::Func(f)) => {
+                    self.compile_bind_function(name, f, Span::Builtin)?
+                }
+                Step::Scope(steps) => {
+                    self.in_scope(|env| env.run_steps(steps))?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Load an imported module, using the module cache if possible
+    pub(crate) fn load_import(&mut self, input: &str, path: &Path, key: u64) -> UiuaResult<Import> {
+        if let Some(module) = self.cached_module(key) {
+            let outer = self.recording.take();
+            self.current_imports.lock().insert(path.into());
+            let res = self.in_scope(|env| env.load_module(&module));
+            self.current_imports.lock().remove(path);
+            self.recording = outer;
+            return Ok(Import {
+                names: res?,
+                deps: module.deps,
+            });
+        }
+        let recording = (self.module_cache.is_some() || self.recording.is_some())
+            .then(|| Recording::new(self.higher_scopes.len() + 1));
+        let outer = replace(&mut self.recording, recording);
+        let compile_only = replace(&mut self.compile_only, false);
+        let res = self.in_scope(|env| env.load_str_path(input, path).map(drop));
+        self.compile_only = compile_only;
+        let recording = replace(&mut self.recording, outer);
+        let names = res?;
+        let Some(recording) = recording else {
+            return Ok(Import {
+                names,
+                deps: Vec::new(),
+            });
+        };
+        let deps = recording.deps.clone();
+        if let (Some(dir), false) = (&self.module_cache, recording.uncacheable) {
+            if let Ok(bytes) = self.finish_recording(recording).to_bytes() {
+                _ = fs::create_dir_all(dir);
+                _ = fs::write(dir.join(format!("{key:016x}.uac")), bytes);
+            }
+        }
+        Ok(Import { names, deps })
+    }
+    fn cached_module(&self, key: u64) -> Option<CompiledModule> {
+        let dir = self.module_cache.as_ref()?;
+        let bytes = fs::read(dir.join(format!("{key:016x}.uac"))).ok()?;
+        let module = CompiledModule::from_bytes(&bytes).ok()?;
+        let up_to_date = module.deps.iter().all(|(path, dep_key)| {
+            (self.backend.file_read_all(&path.to_string_lossy()).ok())
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .is_some_and(|input| module_key(&input, self.mode, self.optimize) == *dep_key)
+        });
+        up_to_date.then_some(module)
+    }
+    /// Turn a recording into a module with its own span table
+    fn finish_recording(&self, recording: Recording) -> CompiledModule {
+        let all_spans = self.spans.lock();
+        let mut spans = Vec::new();
+        let mut indices = HashMap::new();
+        let steps = SpanMap::new(|span| {
+            *indices.entry(span).or_insert_with(|| {
+                spans.push(all_spans[span].clone());
+                spans.len() - 1
+            })
+        })
+        .steps(&recording.steps);
+        CompiledModule {
+            steps,
+            spans,
+            deps: recording.deps,
+        }
+    }
+}
+
+/// Maps the span indices of instructions, preserving shared functions
+struct SpanMap<F> {
+    f: F,
+    functions: HashMap<*const Function, Arc<Function>>,
+}
+
+impl<F: FnMut(usize) -> usize> SpanMap<F> {
+    fn new(f: F) -> Self {
+        Self {
+            f,
+            functions: HashMap::new(),
+        }
+    }
+    fn steps(&mut self, steps: &[Step]) -> Vec<Step> {
+        (steps.iter())
+            .map(|step| match step {
+                Step::Run(instrs) => Step::Run(self.instrs(instrs)),
+                Step::Bind(name, Global::Val(value)) => {
+                    Step::Bind(name.clone(), Global::Val(value.clone()))
+                }
+                Step::Bind(name, Global::Func(f)) => {
+                    Step::Bind(name.clone(), Global::Func(self.function(f)))
+                }
+                Step::Scope(steps) => Step::Scope(self.steps(steps)),
+            })
+            .collect()
+    }
+    fn instrs(&mut self, instrs: &[Instr]) -> Vec<Instr> {
+        (instrs.iter())
+            .map(|instr| {
+                let mut instr = instr.clone();
+                if let Some(span) = instr.span_mut() {
+                    *span = (self.f)(*span);
+                }
+                if let Instr::PushFunc(f) = &mut instr {
+                    *f = self.function(f);
+                }
+                instr
+            })
+            .collect()
+    }
+    fn function(&mut self, f: &Arc<Function>) -> Arc<Function> {
+        if let Some(mapped) = self.functions.get(&Arc::as_ptr(f)) {
+            return mapped.clone();
+        }
+        let instrs = self.instrs(&f.instrs);
+        let mapped = Arc::new(Function::new(f.id.clone(), instrs, f.signature()));
+        self.functions.insert(Arc::as_ptr(f), mapped.clone());
+        mapped
+    }
+}
+
+impl CompiledModule {
+    /// Serialize the module
+    ///
+    /// # Errors
+    /// Fails if the module contains functions defined in Rust
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut enc = Encoder::default();
+        let mut spans = Vec::new();
+        write_usize(&mut spans, self.spans.len());
+        for span in &self.spans {
+            match span {
+                Span::Builtin => spans.push(0),
+                Span::Code(span) => {
+                    spans.push(1);
+                    enc.code_span(&mut spans, span);
+                }
+            }
+        }
+        let mut steps = Vec::new();
+        enc.steps(&mut steps, &self.steps)?;
+
+        let mut bytes = MAGIC.to_vec();
+        write_str(&mut bytes, env!("CARGO_PKG_VERSION"));
+        bytes.extend(primitives_hash().to_le_bytes());
+        write_usize(&mut bytes, enc.paths.len());
+        for path in &enc.paths {
+            write_str(&mut bytes, &path.to_string_lossy());
+        }
+        write_usize(&mut bytes, enc.inputs.len());
+        for input in &enc.inputs {
+            write_str(&mut bytes, input);
+        }
+        bytes.extend(spans);
+        write_usize(&mut bytes, enc.function_count);
+        bytes.extend(enc.functions);
+        write_usize(&mut bytes, self.deps.len());
+        for (path, key) in &self.deps {
+            write_str(&mut bytes, &path.to_string_lossy());
+            bytes.extend(key.to_le_bytes());
+        }
+        bytes.extend(steps);
+        Ok(bytes)
+    }
+    /// Deserialize a module
+    ///
+    /// # Errors
+    /// Fails if the bytes are not a module compiled by this version of Uiua
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let rest = bytes
+            .strip_prefix(MAGIC)
+            .ok_or("Not a compiled Uiua module")?;
+        let mut dec = Decoder {
+            bytes: rest,
+            paths: Vec::new(),
+            inputs: Vec::new(),
+            span_count: 0,
+            functions: Vec::new(),
+            value_depth: 0,
+        };
+        dec.module()
+            .map_err(|e| format!("Invalid compiled module: {e}"))
+    }
+}
+
+fn write_usize(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_usize(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_shape(out: &mut Vec<u8>, shape: &[usize]) {
+    write_usize(out, shape.len());
+    for &dim in shape {
+        write_usize(out, dim);
+    }
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    if let Some(keys) = value.map_keys() {
+        out.push(4);
+        write_value(out, keys.keys());
+        let mut values = value.clone();
+        values.strip_map_keys();
+        write_value(out, &values);
+        return;
+    }
+    match value {
+        Value::Num(arr) => {
+            out.push(0);
+            write_shape(out, &arr.shape);
+            for n in arr.data.iter() {
+                out.extend(n.to_le_bytes());
+            }
+        }
+        Value::Byte(arr) => {
+            out.push(1);
+            write_shape(out, &arr.shape);
+            out.extend_from_slice(&arr.data);
+        }
+        Value::Char(arr) => {
+            out.push(2);
+            write_shape(out, &arr.shape);
+            for &c in arr.data.iter() {
+                write_usize(out, c as usize);
+            }
+        }
+        Value::Box(arr) => {
+            out.push(3);
+            write_shape(out, &arr.shape);
+            for Boxed(value) in arr.data.iter() {
+                write_value(out, value);
+            }
+        }
+    }
+}
+
+fn prim_index(prim: Primitive) -> usize {
+    all::<Primitive>().position(|p| p == prim).unwrap()
+}
+
+fn impl_prim_index(prim: ImplPrimitive) -> usize {
+    all::<ImplPrimitive>().position(|p| p == prim).unwrap()
+}
+
+#[derive(Default)]
+struct Encoder {
+    paths: Vec<Arc<Path>>,
+    path_indices: HashMap<*const Path, usize>,
+    inputs: Vec<Arc<str>>,
+    input_indices: HashMap<*const str, usize>,
+    functions: Vec<u8>,
+    function_count: usize,
+    function_indices: HashMap<*const Function, usize>,
+}
+
+impl Encoder {
+    fn code_span(&mut self, out: &mut Vec<u8>, span: &CodeSpan) {
+        match &span.path {
+            Some(path) => {
+                let index = *self
+                    .path_indices
+                    .entry(Arc::as_ptr(path))
+                    .or_insert_with(|| {
+                        self.paths.push(path.clone());
+                        self.paths.len() - 1
+                    });
+                write_usize(out, index + 1);
+            }
+            None => write_usize(out, 0),
+        }
+        let index = *self
+            .input_indices
+            .entry(Arc::as_ptr(&span.input))
+            .or_insert_with(|| {
+                self.inputs.push(span.input.clone());
+                self.inputs.len() - 1
+            });
+        write_usize(out, index);
+        for loc in [&span.start, &span.end] {
+            write_usize(out, loc.char_pos);
+            write_usize(out, loc.byte_pos);
+            write_usize(out, loc.line);
+            write_usize(out, loc.col);
+        }
+    }
+    fn steps(&mut self, out: &mut Vec<u8>, steps: &[Step]) -> Result<(), String> {
+        write_usize(out, steps.len());
+        for step in steps {
+            match step {
+                Step::Run(instrs) => {
+                    out.push(0);
+                    self.instrs(out, instrs)?;
+                }
+                Step::Bind(name, global) => {
+                    out.push(1);
+                    write_str(out, name);
+                    match global {
+                        Global::Val(value) => {
+                            out.push(0);
+                            write_value(out, value);
+                        }
+                        Global::Func(f) => {
+                            out.push(1);
+                            let index = self.function(f)?;
+                            write_usize(out, index);
+                        }
+                    }
+                }
+                Step::Scope(steps) => {
+                    out.push(2);
+                    self.steps(out, steps)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Add a function and the functions it uses to the function table
+    fn function(&mut self, f: &Arc<Function>) -> Result<usize, String> {
+        if let Some(&index) = self.function_indices.get(&Arc::as_ptr(f)) {
+            return Ok(index);
+        }
+        let mut body = Vec::new();
+        match &f.id {
+            FunctionId::Named(name) => {
+                body.push(0);
+                write_str(&mut body, name);
+            }
+            FunctionId::Anonymous(span) => {
+                body.push(1);
+                self.code_span(&mut body, span);
+            }
+            FunctionId::Primitive(prim) => {
+                body.push(2);
+                write_usize(&mut body, prim_index(*prim));
+            }
+            FunctionId::Main => body.push(3),
+            FunctionId::Unnamed => body.push(4),
+        }
+        let sig = f.signature();
+        write_usize(&mut body, sig.args);
+        write_usize(&mut body, sig.outputs);
+        self.instrs(&mut body, &f.instrs)?;
+        self.functions.extend(body);
+        let index = self.function_count;
+        self.function_count += 1;
+        self.function_indices.insert(Arc::as_ptr(f), index);
+        Ok(index)
+    }
+    fn instrs(&mut self, out: &mut Vec<u8>, instrs: &[Instr]) -> Result<(), String> {
+        write_usize(out, instrs.len());
+        for instr in instrs {
+            match instr {
+                Instr::Push(value) => {
+                    out.push(0);
+                    write_value(out, value);
+                }
+                Instr::BeginArray => out.push(1),
+                Instr::EndArray { boxed, span } => {
+                    out.push(2);
+                    out.push(*boxed as u8);
+                    write_usize(out, *span);
+                }
+                Instr::Prim(prim, span) => {
+                    out.push(3);
+                    write_usize(out, prim_index(*prim));
+                    write_usize(out, *span);
+                }
+                Instr::ImplPrim(prim, span) => {
+                    out.push(4);
+                    write_usize(out, impl_prim_index(*prim));
+                    write_usize(out, *span);
+                }
+                Instr::Call(span) => {
+                    out.push(5);
+                    write_usize(out, *span);
+                }
+                Instr::PushFunc(f) => {
+                    let index = self.function(f)?;
+                    out.push(6);
+                    write_usize(out, index);
+                }
+                Instr::Switch { count, span } => {
+                    out.push(7);
+                    write_usize(out, *count);
+                    write_usize(out, *span);
+                }
+                Instr::Dynamic(_) => {
+                    return Err("Functions defined in Rust cannot be serialized".into())
+                }
+                Instr::Format { parts, span } => {
+                    out.push(8);
+                    write_usize(out, parts.len());
+                    for part in parts.iter() {
+                        write_str(out, part);
+                    }
+                    write_usize(out, *span);
+                }
+                Instr::PushTempFunctions(count) => {
+                    out.push(9);
+                    write_usize(out, *count);
+                }
+                Instr::PopTempFunctions(count) => {
+                    out.push(10);
+                    write_usize(out, *count);
+                }
+                Instr::GetTempFunction { offset, sig, span } => {
+                    out.push(11);
+                    write_usize(out, *offset);
+                    write_usize(out, sig.args);
+                    write_usize(out, sig.outputs);
+                    write_usize(out, *span);
+                }
+                Instr::PushTempUnder { count, span } => {
+                    out.push(12);
+                    write_usize(out, *count);
+                    write_usize(out, *span);
+                }
+                Instr::PopTempUnder { count, span } => {
+                    out.push(13);
+                    write_usize(out, *count);
+                    write_usize(out, *span);
+                }
+                Instr::PushTempInline { count, span } => {
+                    out.push(14);
+                    write_usize(out, *count);
+                    write_usize(out, *span);
+                }
+                Instr::PopTempInline { count, span } => {
+                    out.push(15);
+                    write_usize(out, *count);
+                    write_usize(out, *span);
+                }
+                Instr::CopyTempInline {
+                    offset,
+                    count,
+                    span,
+                } => {
+                    out.push(16);
+                    write_usize(out, *offset);
+                    write_usize(out, *count);
+                    write_usize(out, *span);
+                }
+                Instr::DropTempInline { count, span } => {
+                    out.push(17);
+                    write_usize(out, *count);
+                    write_usize(out, *span);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    paths: Vec<Arc<Path>>,
+    inputs: Vec<Arc<str>>,
+    span_count: usize,
+    functions: Vec<Arc<Function>>,
+    value_depth: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if n > self.bytes.len() {
+            return Err("unexpected end of data".into());
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+    fn usize(&mut self) -> Result<usize, String> {
+        let mut n = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift >= usize::BITS {
+                return Err("number is too large".into());
+            }
+            n |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+            shift += 7;
+        }
+    }
+    /// Read a length, checking that there are at least enough bytes left for its items
+    fn len(&mut self, min_item_size: usize) -> Result<usize, String> {
+        let len = self.usize()?;
+        if len.saturating_mul(min_item_size) > self.bytes.len() {
+            return Err("unexpected end of data".into());
+        }
+        Ok(len)
+    }
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    fn str(&mut self) -> Result<&'a str, String> {
+        let len = self.usize()?;
+        std::str::from_utf8(self.take(len)?).map_err(|e| e.to_string())
+    }
+    fn span(&mut self) -> Result<usize, String> {
+        let span = self.usize()?;
+        if span >= self.span_count {
+            return Err(format!("invalid span {span}"));
+        }
+        Ok(span)
+    }
+    fn module(&mut self) -> Result<CompiledModule, String> {
+        let version = self.str()?;
+        if version != env!("CARGO_PKG_VERSION") {
+            return Err(format!(
+                "it was compiled with Uiua {version}, but this is Uiua {}",
+                env!("CARGO_PKG_VERSION")
+            ));
+        }
+        if self.u64()? != primitives_hash() {
+            return Err("it was compiled by a build of Uiua with different primitives".into());
+        }
+        for _ in 0..self.len(1)? {
+            let path = Path::new(self.str()?).into();
+            self.paths.push(path);
+        }
+        for _ in 0..self.len(1)? {
+            let input = self.str()?.into();
+            self.inputs.push(input);
+        }
+        let span_count = self.len(1)?;
+        let mut spans = Vec::with_capacity(span_count);
+        for _ in 0..span_count {
+            spans.push(match self.u8()? {
+                0 => Span::Builtin,
+                1 => Span::Code(self.code_span()?),
+                tag => return Err(format!("invalid span tag {tag}")),
+            });
+        }
+        self.span_count = span_count;
+        for _ in 0..self.len(1)? {
+            let f = self.function()?;
+            self.functions.push(f.into());
+        }
+        let mut deps = Vec::new();
+        for _ in 0..self.len(1)? {
+            let path = PathBuf::from(self.str()?);
+            deps.push((path, self.u64()?));
+        }
+        let steps = self.steps(true)?;
+        if !self.bytes.is_empty() {
+            return Err("trailing data".into());
+        }
+        Ok(CompiledModule { steps, spans, deps })
+    }
+    fn code_span(&mut self) -> Result<CodeSpan, String> {
+        let path = match self.usize()? {
+            0 => None,
+            i => Some(self.paths.get(i - 1).ok_or("invalid path")?.clone()),
+        };
+        let index = self.usize()?;
+        let input = self.inputs.get(index).ok_or("invalid input")?.clone();
+        let mut loc = || -> Result<Loc, String> {
+            Ok(Loc {
+                char_pos: self.usize()?,
+                byte_pos: self.usize()?,
+                line: self.usize()?,
+                col: self.usize()?,
+            })
+        };
+        let start = loc()?;
+        let end = loc()?;
+        if start.byte_pos > end.byte_pos || end.byte_pos > input.len() {
+            return Err("invalid span".into());
+        }
+        Ok(CodeSpan {
+            start,
+            end,
+            path,
+            input,
+        })
+    }
+    /// Read steps, which may contain scopes only at the top level
+    fn steps(&mut self, top_level: bool) -> Result<Vec<Step>, String> {
+        let len = self.len(1)?;
+        let mut steps = Vec::with_capacity(len);
+        for _ in 0..len {
+            steps.push(match self.u8()? {
+                0 => Step::Run(self.instrs()?),
+                1 => {
+                    let name = self.str()?.into();
+                    let global = match self.u8()? {
+                        0 => Global::Val(self.value()?),
+                        1 => Global::Func(self.function_ref()?),
+                        tag => return Err(format!("invalid global tag {tag}")),
+                    };
+                    Step::Bind(name, global)
+                }
+                2 if top_level => Step::Scope(self.steps(false)?),
+                2 => return Err("nested scope".into()),
+                tag => return Err(format!("invalid step tag {tag}")),
+            });
+        }
+        Ok(steps)
+    }
+    fn function_ref(&mut self) -> Result<Arc<Function>, String> {
+        let index = self.usize()?;
+        (self.functions.get(index).cloned()).ok_or_else(|| format!("invalid function {index}"))
+    }
+    fn function(&mut self) -> Result<Function, String> {
+        let id = match self.u8()? {
+            0 => FunctionId::Named(self.str()?.into()),
+            1 => FunctionId::Anonymous(self.code_span()?),
+            2 => FunctionId::Primitive(self.prim()?),
+            3 => FunctionId::Main,
+            4 => FunctionId::Unnamed,
+            tag => return Err(format!("invalid function id tag {tag}")),
+        };
+        let sig = Signature::new(self.usize()?, self.usize()?);
+        Ok(Function::new(id, self.instrs()?, sig))
+    }
+    fn prim(&mut self) -> Result<Primitive, String> {
+        let index = self.usize()?;
+        all::<Primitive>()
+            .nth(index)
+            .ok_or_else(|| format!("invalid primitive {index}"))
+    }
+    fn instrs(&mut self) -> Result<Vec<Instr>, String> {
+        let len = self.len(1)?;
+        let mut instrs = Vec::with_capacity(len);
+        for _ in 0..len {
+            instrs.push(match self.u8()? {
+                0 => Instr::Push(self.value()?.into()),
+                1 => Instr::BeginArray,
+                2 => Instr::EndArray {
+                    boxed: self.u8()? != 0,
+                    span: self.span()?,
+                },
+                3 => Instr::Prim(self.prim()?, self.span()?),
+                4 => {
+                    let index = self.usize()?;
+                    let prim = all::<ImplPrimitive>()
+                        .nth(index)
+                        .ok_or_else(|| format!("invalid implementation primitive {index}"))?;
+                    Instr::ImplPrim(prim, self.span()?)
+                }
+                5 => Instr::Call(self.span()?),
+                6 => Instr::PushFunc(self.function_ref()?),
+                7 => Instr::Switch {
+                    count: self.usize()?,
+                    span: self.span()?,
+                },
+                8 => {
+                    let len = self.len(1)?;
+                    if len == 0 {
+                        return Err("empty format string".into());
+                    }
+                    let mut parts = Vec::with_capacity(len);
+                    for _ in 0..len {
+                        parts.push(self.str()?.to_string());
+                    }
+                    Instr::Format {
+                        parts: parts.into(),
+                        span: self.span()?,
+                    }
+                }
+                9 => Instr::PushTempFunctions(self.usize()?),
+                10 => Instr::PopTempFunctions(self.usize()?),
+                11 => Instr::GetTempFunction {
+                    offset: self.usize()?,
+                    sig: Signature::new(self.usize()?, self.usize()?),
+                    span: self.span()?,
+                },
+                12 => Instr::PushTempUnder {
+                    count: self.usize()?,
+                    span: self.span()?,
+                },
+                13 => Instr::PopTempUnder {
+                    count: self.usize()?,
+                    span: self.span()?,
+                },
+                14 => Instr::PushTempInline {
+                    count: self.usize()?,
+                    span: self.span()?,
+                },
+                15 => Instr::PopTempInline {
+                    count: self.usize()?,
+                    span: self.span()?,
+                },
+                16 => Instr::CopyTempInline {
+                    offset: self.usize()?,
+                    count: self.usize()?,
+                    span: self.span()?,
+                },
+                17 => Instr::DropTempInline {
+                    count: self.usize()?,
+                    span: self.span()?,
+                },
+                tag => return Err(format!("invalid instruction tag {tag}")),
+            });
+        }
+        Ok(instrs)
+    }
+    fn shape(&mut self) -> Result<(Shape, usize), String> {
+        let rank = self.len(1)?;
+        let mut shape = Shape::new();
+        for _ in 0..rank {
+            shape.push(self.usize()?);
+        }
+        let len = (shape.iter())
+            .try_fold(1usize, |acc, &dim| acc.checked_mul(dim))
+            .ok_or("array is too large")?;
+        Ok((shape, len))
+    }
+    fn value(&mut self) -> Result<Value, String> {
+        if self.value_depth >= MAX_VALUE_DEPTH {
+            return Err("values are nested too deeply".into());
+        }
+        self.value_depth += 1;
+        let value = self.value_impl();
+        self.value_depth -= 1;
+        value
+    }
+    fn value_impl(&mut self) -> Result<Value, String> {
+        let tag = self.u8()?;
+        if tag == 4 {
+            let keys = self.value()?;
+            let mut values = self.value()?;
+            values.set_map_keys(keys)?;
+            return Ok(values);
+        }
+        let (shape, len) = self.shape()?;
+        let min_item_size = match tag {
+            0 => 8,
+            _ => 1,
+        };
+        if len.saturating_mul(min_item_size) > self.bytes.len() {
+            return Err("unexpected end of data".into());
+        }
+        Ok(match tag {
+            0 => {
+                let data = self.take(len * 8)?.chunks_exact(8);
+                let data: CowSlice<f64> = data
+                    .map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap()))
+                    .collect();
+                Array::new(shape, data).into()
+            }
+            1 => Array::new(shape, CowSlice::from(self.take(len)?)).into(),
+            2 => {
+                let data: CowSlice<char> = (0..len)
+                    .map(|_| {
+                        let c = u32::try_from(self.usize()?).ok().and_then(char::from_u32);
+                        c.ok_or_else(|| "invalid character".to_string())
+                    })
+                    .collect::<Result<_, _>>()?;
+                Array::new(shape, data).into()
+            }
+            3 => {
+                let data: CowSlice<Boxed> = (0..len)
+                    .map(|_| self.value().map(Boxed))
+                    .collect::<Result<_, _>>()?;
+                Array::new(shape, data).into()
+            }
+            tag => return Err(format!("invalid value tag {tag}")),
+        })
+    }
+}
+
+#[test]
+fn primitives_mismatch() {
+    let module = Uiua::with_native_sys().compile_str("1").unwrap();
+    let mut bytes = module.to_bytes().unwrap();
+    let hash_start = MAGIC.len() + 1 + env!("CARGO_PKG_VERSION").len();
+    bytes[hash_start] ^= 1;
+    let err = CompiledModule::from_bytes(&bytes).err().unwrap();
+    assert!(err.contains("different primitives"), "{err}");
+}
+
+#[test]
+fn nested_scopes() {
+    let module = CompiledModule {
+        steps: vec![Step::Scope(vec![Step::Scope(Vec::new())])],
+        spans: Vec::new(),
+        deps: Vec::new(),
+    };
+    let bytes = module.to_bytes().unwrap();
+    assert!(CompiledModule::from_bytes(&bytes).is_err());
+}
+
+#[test]
+fn deeply_nested_value() {
+    let bytes = [3, 0].repeat(100_000);
+    let mut dec = Decoder {
+        bytes: &bytes,
+        paths: Vec::new(),
+        inputs: Vec::new(),
+        span_count: 0,
+        functions: Vec::new(),
+        value_depth: 0,
+    };
+    assert!(dec.value().is_err());
+}
+
+#[test]
+fn io_binding_is_not_compiled() {
+    let mut uiua = Uiua::with_native_sys();
+    assert!(uiua.compile_str("X ← &var \"HOME\"").is_err());
+    assert!(uiua.compile_str("X ← ⚂").is_ok());
+    assert!(uiua.compile_str("X ← &jsond \"[1, 2]\"").is_ok());
+}
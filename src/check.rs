@@ -3,6 +3,7 @@ use std::{borrow::Cow, cmp::Ordering};
 use crate::{
     array::Array,
     function::{Function, FunctionId, Instr, Signature},
+    primitive::ImplPrimitive,
     value::Value,
     Primitive,
 };
@@ -146,28 +147,11 @@ impl<'a> VirtualEnv<'a> {
                 }
             }
             Instr::Dynamic(f) => self.handle_sig(f.signature)?,
+            Instr::Format { parts, .. } => self.handle_args_outputs(parts.len() - 1, 1)?,
             Instr::DropTempInline { .. } => {}
             Instr::Prim(prim, _) => match prim {
-                Reduce | Scan => {
-                    let sig = self.pop_func()?.signature();
-                    let outputs = match (sig.args, sig.outputs) {
-                        (0, _) => return Err(format!("{prim}'s function has no args")),
-                        (1, 0) => 0,
-                        (1, _) => return Err(format!("{prim}'s function's signature is {sig}")),
-                        (2, 1) => 1,
-                        _ => return Err(format!("{prim}'s function's signature is {sig}")),
-                    };
-                    self.handle_args_outputs(1, outputs)?;
-                }
-                Each | Rows | Distribute | Tribute => {
-                    let sig = self.pop_func()?.signature();
-                    if sig.outputs != 1 {
-                        return Err(format!(
-                            "{prim}'s function must have 1 output, but its signature is {sig}"
-                        ));
-                    }
-                    self.handle_sig(sig)?
-                }
+                Reduce | Scan => self.reduce(*prim)?,
+                Each | Rows | Distribute | Tribute => self.rows(*prim)?,
                 Table | Cross => {
                     let sig = self.pop_func()?.signature();
                     if sig != (2, 1) {
@@ -192,6 +176,10 @@ impl<'a> VirtualEnv<'a> {
                     };
                     self.handle_args_outputs(args, outputs)?;
                 }
+                Memo => {
+                    let sig = self.pop_func()?.signature();
+                    self.handle_sig(sig)?;
+                }
                 Spawn => {
                     let sig = self.pop_func()?.signature();
                     self.handle_args_outputs(sig.args, 1)?;
@@ -341,6 +329,10 @@ impl<'a> VirtualEnv<'a> {
                     }
                     self.handle_sig(f_sig)?;
                 }
+                Derivative => {
+                    let sig = self.pop_func()?.signature();
+                    self.handle_sig(sig)?;
+                }
                 Invert => {
                     let f = self.pop_func()?;
                     if let Some(inverted) = f.inverse() {
@@ -473,6 +465,8 @@ impl<'a> VirtualEnv<'a> {
                     }
                 }
             },
+            Instr::ImplPrim(ImplPrimitive::DualReduce, _) => self.reduce(Reduce)?,
+            Instr::ImplPrim(ImplPrimitive::DualRows, _) => self.rows(Rows)?,
             Instr::ImplPrim(prim, _) => {
                 let args = prim.args();
                 for _ in 0..prim.modifier_args().unwrap_or(0) {
@@ -521,6 +515,28 @@ impl<'a> VirtualEnv<'a> {
     fn handle_sig(&mut self, sig: Signature) -> Result<(), String> {
         self.handle_args_outputs(sig.args, sig.outputs)
     }
+    /// Handle a modifier that reduces an array
+    fn reduce(&mut self, prim: Primitive) -> Result<(), String> {
+        let sig = self.pop_func()?.signature();
+        let outputs = match (sig.args, sig.outputs) {
+            (0, _) => return Err(format!("{prim}'s function has no args")),
+            (1, 0) => 0,
+            (1, _) => return Err(format!("{prim}'s function's signature is {sig}")),
+            (2, 1) => 1,
+            _ => return Err(format!("{prim}'s function's signature is {sig}")),
+        };
+        self.handle_args_outputs(1, outputs)
+    }
+    /// Handle a modifier that iterates over arrays
+    fn rows(&mut self, prim: Primitive) -> Result<(), String> {
+        let sig = self.pop_func()?.signature();
+        if sig.outputs != 1 {
+            return Err(format!(
+                "{prim}'s function must have 1 output, but its signature is {sig}"
+            ));
+        }
+        self.handle_sig(sig)
+    }
 }
 
 fn instrs_contain_break(instrs: &[Instr]) -> bool {
@@ -1,22 +1,26 @@
 use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
+    mem::{replace, take},
     sync::Arc,
 };
 
 use crate::{
-    algorithm::invert::{invert_instrs, under_instrs},
+    algorithm::{
+        derivative::derivative_instrs,
+        invert::{invert_instrs, under_instrs},
+    },
     array::Array,
     ast::*,
     boxed::Boxed,
+    bytecode::{impure_primitive, Step},
     check::instrs_signature,
     function::*,
     lex::{CodeSpan, Sp, Span},
     parse::{count_placeholders, ident_modifier_args},
     primitive::{ImplPrimitive, Primitive},
     run::{Global, RunMode},
+    snapshot::snapshot_name,
     value::Value,
-    Diagnostic, DiagnosticKind, Ident, SysOp, UiuaError, UiuaResult,
+    Diagnostic, DiagnosticKind, Ident, Snapshot, SysOp, UiuaError, UiuaResult,
 };
 
 use crate::Uiua;
@@ -59,7 +63,33 @@ impl Uiua {
         }
         match item {
             Item::TestScope(items) => {
-                self.in_scope(|env| env.items(items, true))?;
+                let outer = self.recording.as_mut().map(|rec| take(&mut rec.steps));
+                let testing = self.mode == RunMode::Test && !self.compile_only;
+                let block = if testing { block_comment(&items) } else { None };
+                // Unnamed snapshots are numbered by their position in the file
+                let snapshot = (block.as_ref())
+                    .and_then(|(comment, _)| snapshot_name(comment))
+                    .map(|name| {
+                        self.snapshot_blocks += 1;
+                        if name.is_empty() {
+                            self.snapshot_blocks.to_string()
+                        } else {
+                            name.into()
+                        }
+                    });
+                self.in_scope(|env| {
+                    let Some((comment, line)) = block else {
+                        return env.items(items, true);
+                    };
+                    env.test_case(comment, line, |env| match snapshot {
+                        Some(name) => env.snapshot(name, line, items),
+                        None => env.items(items, true),
+                    })
+                })?;
+                if let (Some(rec), Some(outer)) = (&mut self.recording, outer) {
+                    let inner = replace(&mut rec.steps, outer);
+                    rec.steps.push(Step::Scope(inner));
+                }
             }
             Item::Words(words) => {
                 let can_run = match self.mode {
@@ -68,19 +98,18 @@ impl Uiua {
                     RunMode::All => true,
                 };
                 if can_run || words_have_import(&words) || words_are_export(&words) {
-                    let span = words
-                        .first()
-                        .unwrap()
-                        .span
-                        .clone()
-                        .merge(words.last().unwrap().span.clone());
-                    if count_placeholders(&words) > 0 {
-                        return Err(span
-                            .sp("Cannot use placeholder outside of function".into())
-                            .into());
+                    let is_test = self.mode == RunMode::Test
+                        && in_test
+                        && !self.compile_only
+                        && words.iter().any(|word| word.value.is_code());
+                    if is_test {
+                        let span = (words.first().unwrap().span.clone())
+                            .merge(words.last().unwrap().span.clone());
+                        let name = span.as_str().trim().to_string();
+                        self.test_case(name, span.start.line, |env| env.words_item(words))?;
+                    } else {
+                        self.words_item(words)?;
                     }
-                    let instrs = self.compile_words(words, true)?;
-                    self.exec_global_instrs(instrs)?;
                 }
             }
             Item::Binding(binding) => {
@@ -96,6 +125,43 @@ impl Uiua {
         }
         Ok(())
     }
+    fn words_item(&mut self, words: Vec<Sp<Word>>) -> UiuaResult {
+        let span = words
+            .first()
+            .unwrap()
+            .span
+            .clone()
+            .merge(words.last().unwrap().span.clone());
+        if count_placeholders(&words) > 0 {
+            return Err(span
+                .sp("Cannot use placeholder outside of function".into())
+                .into());
+        }
+        let instrs = self.compile_words(words, true)?;
+        if let Some(recording) = &mut self.recording {
+            recording.steps.push(Step::Run(instrs.clone()));
+        }
+        if !self.compile_only {
+            self.exec_global_instrs(instrs)?;
+        }
+        Ok(())
+    }
+    /// Run a snapshot test block, recording its output and final stack
+    fn snapshot(&mut self, name: String, line: usize, items: Vec<Item>) -> UiuaResult {
+        let start_height = self.stack.len();
+        self.snapshot_output = Some(String::new());
+        let res = self.items(items, true);
+        let output = self.snapshot_output.take().unwrap_or_default();
+        res?;
+        let stack = self.stack[start_height..].iter().map(Value::show).collect();
+        self.snapshots.push(Snapshot {
+            name,
+            line,
+            output,
+            stack,
+        });
+        Ok(())
+    }
     fn binding(&mut self, binding: Binding) -> UiuaResult {
         let name = binding.name.value;
         let span = &binding.name.span;
@@ -118,6 +184,11 @@ impl Uiua {
             Function::new(FunctionId::Named(name.clone()), instrs, sig)
         };
 
+        if let (Some(coverage), Some(last)) = (&self.coverage, binding.words.last()) {
+            let span = span.clone().merge(last.span.clone());
+            coverage.lock().register_binding(name.clone(), span);
+        }
+
         let placeholder_count = count_placeholders(&binding.words);
         // Compile the body
         let mut instrs = self.compile_words(binding.words, true)?;
@@ -148,6 +219,30 @@ impl Uiua {
                     && (sig.outputs > 0 || instrs.is_empty())
                     && placeholder_count == 0
                 {
+                    if let Some(recording) = &mut self.recording {
+                        if let Some(prim) = impure_primitive(&instrs) {
+                            recording.uncacheable = true;
+                            if self.compile_only {
+                                if let Primitive::Sys(_) = prim {
+                                    return Err(UiuaError::Run(Span::Code(span.clone()).sp(
+                                        format!(
+                                            "{prim} does IO, so `{name}` cannot be \
+                                            evaluated at compile time"
+                                        ),
+                                    )));
+                                }
+                                self.diagnostic_with_span(
+                                    format!(
+                                        "{prim} is evaluated at compile time, \
+                                        so `{name}` will keep its compile-time value"
+                                    ),
+                                    DiagnosticKind::Warning,
+                                    span.clone(),
+                                );
+                                self.flush_diagnostics();
+                            }
+                        }
+                    }
                     self.exec_global_instrs(instrs)?;
                     if let Some(f) = self.function_stack.pop() {
                         self.compile_bind_function(name, f, span.clone().into())?;
@@ -183,6 +278,10 @@ impl Uiua {
     ) -> UiuaResult {
         self.validate_binding_name(&name, &[], span)?;
         value.compress();
+        if let Some(recording) = &mut self.recording {
+            let step = Step::Bind(name.clone(), Global::Val(value.clone()));
+            recording.steps.push(step);
+        }
         let mut globals = self.globals.lock();
         let idx = globals.len();
         globals.push(Global::Val(value));
@@ -196,6 +295,10 @@ impl Uiua {
         span: Span,
     ) -> UiuaResult {
         self.validate_binding_name(&name, &function.instrs, span)?;
+        if let Some(recording) = &mut self.recording {
+            let step = Step::Bind(name.clone(), Global::Func(function.clone()));
+            recording.steps.push(step);
+        }
         let mut globals = self.globals.lock();
         let idx = globals.len();
         globals.push(Global::Func(function));
@@ -262,6 +365,20 @@ impl Uiua {
     fn push_instr(&mut self, instr: Instr) {
         use ImplPrimitive::*;
         use Primitive::*;
+        if let Some(coverage) = &self.coverage {
+            coverage.lock().register(&instr);
+        }
+        if !self.optimize || self.coverage.is_some() {
+            self.new_functions.last_mut().unwrap().push(instr);
+            return;
+        }
+        // Constant folding
+        if let Instr::Prim(prim, span) = instr {
+            if let Some(val) = self.fold_constant(prim, span) {
+                self.push_instr(Instr::push(val));
+                return;
+            }
+        }
         let instrs = self.new_functions.last_mut().unwrap();
         // Optimizations
         match (instrs.as_mut_slice(), instr) {
@@ -298,11 +415,104 @@ impl Uiua {
                 instrs.pop();
                 instrs.push(Instr::ImplPrim(Last, span))
             }
-            // // Coalesce inline stack ops
-            // ([.., Instr::])
+            // Select Rise Duplicate = SortUp
+            ([.., Instr::Prim(Dup, _), Instr::Prim(Rise, _)], Instr::Prim(Select, span)) => {
+                instrs.pop();
+                instrs.pop();
+                instrs.push(Instr::ImplPrim(SortUp, span))
+            }
+            // Select Fall Duplicate = SortDown
+            ([.., Instr::Prim(Dup, _), Instr::Prim(Fall, _)], Instr::Prim(Select, span)) => {
+                instrs.pop();
+                instrs.pop();
+                instrs.push(Instr::ImplPrim(SortDown, span))
+            }
+            // Reduce Add Range = RangeSum
+            ([.., Instr::Prim(Range, _), Instr::PushFunc(f)], Instr::Prim(Reduce, span))
+                if matches!(f.instrs.as_slice(), [Instr::Prim(Add, _)]) =>
+            {
+                instrs.pop();
+                instrs.pop();
+                instrs.push(Instr::ImplPrim(RangeSum, span))
+            }
+            // Rows Windows = RowsWindows
+            ([.., Instr::Prim(Windows, _), Instr::PushFunc(f)], Instr::Prim(Rows, span))
+                if f.signature() == (1, 1) =>
+            {
+                let f = instrs.pop().unwrap();
+                instrs.pop();
+                instrs.push(f);
+                instrs.push(Instr::ImplPrim(RowsWindows, span))
+            }
+            // Reduce Windows = ReduceWindows
+            ([.., Instr::Prim(Windows, _), Instr::PushFunc(f)], Instr::Prim(Reduce, span))
+                if matches!(f.as_flipped_primitive(), Some((Add | Max | Min, _))) =>
+            {
+                let f = instrs.pop().unwrap();
+                instrs.pop();
+                instrs.push(f);
+                instrs.push(Instr::ImplPrim(ReduceWindows, span))
+            }
+            // A pushed value that is immediately popped
+            ([.., Instr::Push(_)], Instr::Prim(Pop, _)) => {
+                instrs.pop();
+            }
+            // Pop Duplicate is a no-op if the previous instruction
+            // produced the duplicated value
+            ([.., prev, Instr::Prim(Dup, _)], Instr::Prim(Pop, _)) if produces_value(prev) => {
+                instrs.pop();
+            }
+            // Inline calls to small functions
+            // Calls are kept intact for the debugger, which stops at function frames,
+            // and for the profiler, which times them
+            ([.., Instr::PushFunc(f)], Instr::Call(_))
+                if self.debugger.is_none() && self.profiler.is_none() && can_inline(f) =>
+            {
+                let f = f.clone();
+                instrs.pop();
+                for instr in f.instrs.iter().cloned() {
+                    self.push_instr(instr);
+                }
+            }
             (_, instr) => instrs.push(instr),
         }
     }
+    /// Evaluate a pervasive primitive at compile time if all of its arguments
+    /// are constants
+    ///
+    /// On success, the argument instructions are removed and the result is
+    /// returned
+    fn fold_constant(&mut self, prim: Primitive, span: usize) -> Option<Value> {
+        if !prim.class().is_pervasive() || prim.outputs() != Some(1) {
+            return None;
+        }
+        let args = prim.args()? as usize;
+        let instrs = self.new_functions.last().unwrap();
+        if args == 0 || instrs.len() < args {
+            return None;
+        }
+        let mut values = Vec::with_capacity(args);
+        for instr in &instrs[instrs.len() - args..] {
+            let Instr::Push(val) = instr else {
+                return None;
+            };
+            values.push((**val).clone());
+        }
+        // Run in a fresh scope so that no fill values apply
+        let stack = replace(&mut self.stack, values);
+        let scope = take(&mut self.scope);
+        self.push_span(span, Some(prim));
+        let res = prim.run(self);
+        self.pop_span();
+        let folded = replace(&mut self.stack, stack);
+        self.scope = scope;
+        if res.is_err() || folded.len() != 1 {
+            return None;
+        }
+        let instrs = self.new_functions.last_mut().unwrap();
+        instrs.truncate(instrs.len() - args);
+        folded.into_iter().next()
+    }
     fn extend_instrs(&mut self, instrs: impl IntoIterator<Item = Instr>) {
         self.new_functions.last_mut().unwrap().extend(instrs);
     }
@@ -346,79 +556,22 @@ impl Uiua {
                     )));
                 }
             }
-            Word::FormatString(frags) => {
-                let signature = Signature::new(frags.len() - 1, 1);
-                let f = Function::new(
-                    FunctionId::Anonymous(word.span.clone()),
-                    vec![Instr::Dynamic(DynamicFunction {
-                        id: {
-                            let mut hasher = DefaultHasher::new();
-                            frags.hash(&mut hasher);
-                            hasher.finish()
-                        },
-                        f: Arc::new(move |env| {
-                            let mut formatted = String::new();
-                            for (i, frag) in frags.iter().enumerate() {
-                                if i > 0 {
-                                    let val = env.pop(format!("format argument {i}"))?;
-                                    formatted.push_str(&format!("{}", val));
-                                }
-                                formatted.push_str(frag);
-                            }
-                            env.push(formatted);
-                            Ok(())
-                        }),
-                        signature,
-                    })],
-                    signature,
-                );
-                self.push_instr(Instr::push_func(f));
-                if call {
-                    let span = self.add_span(word.span);
-                    self.push_instr(Instr::Call(span));
-                }
-            }
+            Word::FormatString(frags) => self.format_string(frags, word.span, call),
             Word::MultilineString(lines) => {
-                let signature = Signature::new(
-                    lines.iter().map(|l| l.value.len().saturating_sub(1)).sum(),
-                    1,
-                );
-                let f = Function::new(
-                    FunctionId::Anonymous(word.span.clone()),
-                    vec![Instr::Dynamic(DynamicFunction {
-                        id: {
-                            let mut hasher = DefaultHasher::new();
-                            lines.hash(&mut hasher);
-                            hasher.finish()
-                        },
-                        f: Arc::new(move |env| {
-                            let mut formatted = String::new();
-                            let mut i = 0;
-                            for (j, line) in lines.iter().enumerate() {
-                                if j > 0 {
-                                    formatted.push('\n');
-                                }
-                                for (k, frag) in line.value.iter().enumerate() {
-                                    if k > 0 {
-                                        let val = env.pop(format!("format argument {i}"))?;
-                                        formatted.push_str(&format!("{}", val));
-                                    }
-                                    formatted.push_str(frag);
-                                    i += 1;
-                                }
-                            }
-                            env.push(formatted);
-                            Ok(())
-                        }),
-                        signature,
-                    })],
-                    signature,
-                );
-                self.push_instr(Instr::push_func(f));
-                if call {
-                    let span = self.add_span(word.span);
-                    self.push_instr(Instr::Call(span));
+                let mut frags = vec![String::new()];
+                for (i, line) in lines.into_iter().enumerate() {
+                    if i > 0 {
+                        frags.last_mut().unwrap().push('\n');
+                    }
+                    for (j, frag) in line.value.into_iter().enumerate() {
+                        if j == 0 {
+                            frags.last_mut().unwrap().push_str(&frag);
+                        } else {
+                            frags.push(frag);
+                        }
+                    }
                 }
+                self.format_string(frags, word.span, call)
             }
             Word::Ident(ident) => self.ident(ident, word.span, call)?,
             Word::Strand(items) => {
@@ -522,15 +675,34 @@ impl Uiua {
         }
         Ok(())
     }
+    fn format_string(&mut self, frags: Vec<String>, span: CodeSpan, call: bool) {
+        let signature = Signature::new(frags.len() - 1, 1);
+        let instr = Instr::Format {
+            parts: frags.into(),
+            span: self.add_span(span.clone()),
+        };
+        if call {
+            self.push_instr(instr);
+        } else {
+            let f = Function::new(FunctionId::Anonymous(span), vec![instr], signature);
+            self.push_instr(Instr::push_func(f));
+        }
+    }
     fn ident(&mut self, ident: Ident, span: CodeSpan, call: bool) -> UiuaResult {
-        if let Some(idx) = self
-            .scope
-            .names
-            .get(&ident)
+        let local = self.scope.names.get(&ident);
+        if let (None, Some(recording)) = (local, &mut self.recording) {
+            // A module that uses names from the scope that imports it
+            // can compile differently depending on that scope
+            if self.higher_scopes.len() <= recording.depth {
+                recording.uncacheable = true;
+            }
+        }
+        if let Some(idx) = local
             .or_else(|| self.higher_scopes.last()?.names.get(&ident))
+            .copied()
         {
             // Name exists in scope
-            let global = self.globals.lock()[*idx].clone();
+            let global = self.globals.lock()[idx].clone();
             match global {
                 Global::Val(val) if call => self.push_instr(Instr::push(val)),
                 Global::Val(val) => {
@@ -856,6 +1028,33 @@ impl Uiua {
                         };
                     }
                 }
+                Primitive::Derivative => {
+                    let mut operands = modified
+                        .operands
+                        .clone()
+                        .into_iter()
+                        .filter(|word| word.value.is_code());
+                    let (instrs, sig) =
+                        self.compile_operand_words(vec![operands.next().unwrap()])?;
+                    if let Ok(sig) = sig {
+                        let span = self.add_span(modified.modifier.span.clone());
+                        let instrs = derivative_instrs(&instrs, sig, span).map_err(|e| {
+                            UiuaError::Run(Span::Code(modified.modifier.span.clone()).sp(e))
+                        })?;
+                        return if call {
+                            self.extend_instrs(instrs);
+                            Ok(())
+                        } else {
+                            let func = Function::new(
+                                FunctionId::Anonymous(modified.modifier.span),
+                                instrs,
+                                sig,
+                            );
+                            self.push_instr(Instr::push_func(func));
+                            Ok(())
+                        };
+                    }
+                }
                 Primitive::Under => {
                     let mut operands = modified
                         .operands
@@ -989,6 +1188,34 @@ fn words_look_pervasive(words: &[Sp<Word>]) -> bool {
     })
 }
 
+/// Whether an instruction is known to leave at least one value on top of the stack
+fn produces_value(instr: &Instr) -> bool {
+    match instr {
+        Instr::Push(_) | Instr::Format { .. } => true,
+        Instr::Prim(prim, _) => prim.outputs().is_some_and(|n| n > 0),
+        Instr::ImplPrim(prim, _) => prim.outputs() > 0,
+        _ => false,
+    }
+}
+
+/// The maximum number of instructions in a function that will be inlined
+const MAX_INLINE_LEN: usize = 8;
+
+/// Whether a call to a function can be replaced with the function's instructions
+fn can_inline(f: &Function) -> bool {
+    f.instrs.len() <= MAX_INLINE_LEN
+        && !f.instrs.iter().any(|instr| {
+            matches!(
+                instr,
+                Instr::PushTempFunctions(_)
+                    | Instr::PopTempFunctions(_)
+                    | Instr::GetTempFunction { .. }
+            )
+        })
+        && count_temp_functions(&f.instrs) == 0
+        && instrs_signature(&f.instrs).is_ok_and(|sig| sig == f.signature())
+}
+
 fn increment_placeholders(instrs: &mut [Instr]) {
     let mut curr = 0;
     for instr in instrs {
@@ -1012,3 +1239,15 @@ fn count_temp_functions(instrs: &[Instr]) -> usize {
     }
     count
 }
+
+/// Get the text and line of the comment that starts a test block, if there is one
+fn block_comment(items: &[Item]) -> Option<(String, usize)> {
+    let Some(Item::Words(words)) = items.first() else {
+        return None;
+    };
+    let word = words.iter().find(|w| !matches!(w.value, Word::Spaces))?;
+    let Word::Comment(comment) = &word.value else {
+        return None;
+    };
+    Some((comment.trim().into(), word.span.start.line))
+}
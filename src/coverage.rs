@@ -0,0 +1,302 @@
+//! Source-level code coverage
+//!
+//! With [`Uiua::with_coverage`], every instruction that is compiled is registered along
+//! with its span, and every time one is executed it is counted. Which branches of
+//! switch functions and [`if`](crate::Primitive::If)s are taken is counted as well.
+//! [`Uiua::coverage`] maps all of this back to the source lines and bindings.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    path::PathBuf,
+};
+
+use crate::{
+    function::Instr,
+    lex::{CodeSpan, Span},
+    Ident, Primitive, Uiua,
+};
+
+/// Execution counts collected while running with coverage enabled
+#[derive(Debug, Default)]
+pub(crate) struct CoverageData {
+    /// How many times the instructions with each span index were executed
+    spans: HashMap<usize, u64>,
+    /// How many times each branch of each branching instruction was taken, by span index
+    branches: HashMap<usize, Vec<u64>>,
+    /// The names and spans of the bindings that were compiled
+    bindings: Vec<(Ident, CodeSpan)>,
+}
+
+impl CoverageData {
+    /// Register a compiled instruction
+    pub fn register(&mut self, instr: &Instr) {
+        let Some(span) = instr.span() else {
+            return;
+        };
+        self.spans.entry(span).or_default();
+        let branch_count = match instr {
+            Instr::Switch { count, .. } => *count,
+            Instr::Prim(Primitive::If, _) => 2,
+            _ => return,
+        };
+        self.branches.entry(span).or_insert(vec![0; branch_count]);
+    }
+    /// Register a compiled binding
+    pub fn register_binding(&mut self, name: Ident, span: CodeSpan) {
+        self.bindings.push((name, span));
+    }
+    /// Count an executed instruction
+    pub fn hit(&mut self, span: usize) {
+        *self.spans.entry(span).or_default() += 1;
+    }
+    /// Count a taken branch
+    pub fn branch(&mut self, span: usize, branch: usize, count: usize) {
+        let counts = self.branches.entry(span).or_insert(vec![0; count]);
+        if let Some(taken) = counts.get_mut(branch) {
+            *taken += 1;
+        }
+    }
+}
+
+/// Coverage of the code that was compiled, grouped by file
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    /// The coverage of each file, ordered by path
+    pub files: Vec<FileCoverage>,
+}
+
+/// Coverage of a single file
+#[derive(Debug, Clone, Default)]
+pub struct FileCoverage {
+    /// The file's path, or `None` for code that was not loaded from a file
+    pub path: Option<PathBuf>,
+    /// The execution count of every line with code, by line number
+    pub lines: BTreeMap<usize, u64>,
+    /// The branching instructions in the file
+    pub branches: Vec<BranchCoverage>,
+    /// The bindings in the file
+    pub bindings: Vec<BindingCoverage>,
+}
+
+/// Coverage of a switch function or [`if`](crate::Primitive::If)
+#[derive(Debug, Clone)]
+pub struct BranchCoverage {
+    /// The line the branching instruction is on
+    pub line: usize,
+    /// A number that identifies the branching instruction
+    pub id: usize,
+    /// Whether the branching instruction was ever executed
+    pub executed: bool,
+    /// How many times each branch was taken
+    pub taken: Vec<u64>,
+}
+
+/// Coverage of a binding
+#[derive(Debug, Clone)]
+pub struct BindingCoverage {
+    /// The binding's name
+    pub name: Ident,
+    /// The first line of the binding
+    pub line: usize,
+    /// The last line of the binding
+    pub end_line: usize,
+    /// How many times the first line of the binding with code was executed
+    pub hits: u64,
+}
+
+impl Uiua {
+    /// Get the coverage of the code that has been compiled
+    ///
+    /// Returns `None` if coverage was not enabled with [`Uiua::with_coverage`].
+    pub fn coverage(&self) -> Option<Coverage> {
+        let data = self.coverage.as_ref()?.lock();
+        let spans = self.spans.lock();
+        let mut files: BTreeMap<Option<PathBuf>, FileCoverage> = BTreeMap::new();
+        let code_span = |i: usize| match spans.get(i) {
+            Some(Span::Code(span)) => Some(span.clone()),
+            _ => None,
+        };
+        fn file<'a>(
+            files: &'a mut BTreeMap<Option<PathBuf>, FileCoverage>,
+            span: &CodeSpan,
+        ) -> &'a mut FileCoverage {
+            let path = span.path.as_ref().map(|path| path.to_path_buf());
+            (files.entry(path.clone())).or_insert_with(|| FileCoverage {
+                path,
+                ..FileCoverage::default()
+            })
+        }
+        for (&i, &hits) in &data.spans {
+            let Some(span) = code_span(i) else {
+                continue;
+            };
+            let line = file(&mut files, &span).lines.entry(span.start.line);
+            let line = line.or_default();
+            *line = (*line).max(hits);
+        }
+        for (&i, taken) in &data.branches {
+            let Some(span) = code_span(i) else {
+                continue;
+            };
+            file(&mut files, &span).branches.push(BranchCoverage {
+                line: span.start.line,
+                id: i,
+                executed: data.spans.get(&i).is_some_and(|&hits| hits > 0),
+                taken: taken.clone(),
+            });
+        }
+        for (name, span) in &data.bindings {
+            let file = file(&mut files, span);
+            let range = span.start.line..=span.end.line;
+            let hits = (file.lines.range(range).next()).map_or(0, |(_, &hits)| hits);
+            file.bindings.push(BindingCoverage {
+                name: name.clone(),
+                line: span.start.line,
+                end_line: span.end.line,
+                hits,
+            });
+        }
+        let mut files: Vec<FileCoverage> = files.into_values().collect();
+        for file in &mut files {
+            file.branches.sort_by_key(|br| (br.line, br.id));
+            file.bindings.sort_by_key(|binding| binding.line);
+        }
+        Some(Coverage { files })
+    }
+}
+
+impl FileCoverage {
+    /// Get the number of lines with code that were executed and the total number of lines with code
+    pub fn line_counts(&self) -> (usize, usize) {
+        let hit = self.lines.values().filter(|&&hits| hits > 0).count();
+        (hit, self.lines.len())
+    }
+    /// Get the number of branches that were taken and the total number of branches
+    pub fn branch_counts(&self) -> (usize, usize) {
+        let taken = self.branches.iter().flat_map(|br| &br.taken);
+        let hit = taken.clone().filter(|&&n| n > 0).count();
+        (hit, taken.count())
+    }
+    /// Get the number of executed lines with code in a binding and the binding's total number of lines with code
+    pub fn binding_line_counts(&self, binding: &BindingCoverage) -> (usize, usize) {
+        let lines = self.lines.range(binding.line..=binding.end_line);
+        let hit = lines.clone().filter(|(_, &hits)| hits > 0).count();
+        (hit, lines.count())
+    }
+    fn display_path(&self) -> String {
+        (self.path.as_ref()).map_or_else(|| "<input>".into(), |path| path.display().to_string())
+    }
+}
+
+impl Coverage {
+    /// Format the coverage in the LCOV tracefile format
+    pub fn to_lcov(&self) -> String {
+        let mut lcov = String::new();
+        for file in &self.files {
+            _ = writeln!(lcov, "TN:");
+            _ = writeln!(lcov, "SF:{}", file.display_path());
+            for binding in &file.bindings {
+                _ = writeln!(lcov, "FN:{},{}", binding.line, binding.name);
+            }
+            for binding in &file.bindings {
+                _ = writeln!(lcov, "FNDA:{},{}", binding.hits, binding.name);
+            }
+            let fn_hit = file.bindings.iter().filter(|b| b.hits > 0).count();
+            _ = writeln!(lcov, "FNF:{}", file.bindings.len());
+            _ = writeln!(lcov, "FNH:{fn_hit}");
+            for br in &file.branches {
+                for (i, &taken) in br.taken.iter().enumerate() {
+                    let taken = if br.executed {
+                        taken.to_string()
+                    } else {
+                        "-".into()
+                    };
+                    _ = writeln!(lcov, "BRDA:{},{},{i},{taken}", br.line, br.id);
+                }
+            }
+            let (br_hit, br_total) = file.branch_counts();
+            _ = writeln!(lcov, "BRF:{br_total}");
+            _ = writeln!(lcov, "BRH:{br_hit}");
+            for (line, hits) in &file.lines {
+                _ = writeln!(lcov, "DA:{line},{hits}");
+            }
+            let (line_hit, line_total) = file.line_counts();
+            _ = writeln!(lcov, "LF:{line_total}");
+            _ = writeln!(lcov, "LH:{line_hit}");
+            _ = writeln!(lcov, "end_of_record");
+        }
+        lcov
+    }
+    /// Summarize the coverage of each file and binding
+    pub fn summary(&self) -> String {
+        fn counts(hit: usize, total: usize, what: &str) -> String {
+            let percent = if total == 0 {
+                100.0
+            } else {
+                hit as f64 / total as f64 * 100.0
+            };
+            format!("{hit}/{total} {what} ({percent:.1}%)")
+        }
+        let mut summary = String::new();
+        for file in &self.files {
+            let (line_hit, line_total) = file.line_counts();
+            _ = write!(
+                summary,
+                "{}: {}",
+                file.display_path(),
+                counts(line_hit, line_total, "lines")
+            );
+            let (br_hit, br_total) = file.branch_counts();
+            if br_total > 0 {
+                _ = write!(summary, ", {}", counts(br_hit, br_total, "branches"));
+            }
+            summary.push('\n');
+            for binding in &file.bindings {
+                let (hit, total) = file.binding_line_counts(binding);
+                _ = writeln!(
+                    summary,
+                    "  {}: {}",
+                    binding.name,
+                    counts(hit, total, "lines")
+                );
+            }
+        }
+        summary
+    }
+}
+
+#[test]
+fn coverage() {
+    let code = "\
+Sign ← (¯1|0|1)+1±
+Abs ← ?¯∘<0.
+Unused ← ×2
+Sign 5
+Abs 3";
+    let mut env = Uiua::with_native_sys().with_coverage(true);
+    env.load_str(code).unwrap();
+    let coverage = env.coverage().unwrap();
+    assert_eq!(coverage.files.len(), 1);
+    let file = &coverage.files[0];
+    assert_eq!(file.path, None);
+    let lines: Vec<_> = file
+        .lines
+        .iter()
+        .map(|(&line, &hits)| (line, hits))
+        .collect();
+    assert_eq!(lines, [(1, 1), (2, 1), (3, 0), (4, 1), (5, 1)]);
+    let taken: Vec<_> = file.branches.iter().map(|br| br.taken.clone()).collect();
+    assert_eq!(taken, [vec![0, 0, 1], vec![0, 1]]);
+    assert_eq!(file.branch_counts(), (2, 5));
+    let bindings: Vec<_> = (file.bindings.iter())
+        .map(|b| (b.name.as_ref(), b.hits))
+        .collect();
+    assert_eq!(bindings, [("Sign", 1), ("Abs", 1), ("Unused", 0)]);
+    let lcov = coverage.to_lcov();
+    assert!(lcov.contains("FNDA:0,Unused\n"));
+    assert!(lcov.contains("BRDA:1,"));
+    assert!(lcov.contains("DA:3,0\nDA:4,1\n"));
+    assert!(lcov.contains("LF:5\nLH:4\n"));
+    assert!(coverage.summary().contains("  Unused: 0/1 lines (0.0%)"));
+}
@@ -8,6 +8,8 @@ use std::{
     ptr,
 };
 
+use crate::memory::allocate;
+
 macro_rules! cowslice {
     ($($item:expr),* $(,)?) => {
         $crate::cowslice::CowSlice::from([$($item),*])
@@ -37,6 +39,7 @@ impl<T> CowSlice<T> {
         self.end = (self.start + len as u32).min(self.end);
     }
     pub fn with_capacity(capacity: usize) -> Self {
+        allocate::<T>(capacity);
         Self {
             data: EcoVec::with_capacity(capacity),
             start: 0,
@@ -60,6 +63,7 @@ impl<T> CowSlice<T> {
 impl<T: Clone> CowSlice<T> {
     pub fn as_mut_slice(&mut self) -> &mut [T] {
         if !self.data.is_unique() {
+            allocate::<T>(self.len());
             let mut new_data = EcoVec::with_capacity(self.len());
             new_data.extend_from_slice(&*self);
             self.data = new_data;
@@ -107,12 +111,15 @@ impl<T: Clone> CowSlice<T> {
         F: FnOnce(&mut EcoVec<T>) -> R,
     {
         if self.data.is_unique() && self.start == 0 && self.end == self.data.len() as u32 {
+            let capacity = self.data.capacity();
             let res = f(&mut self.data);
+            allocate::<T>(self.data.capacity().saturating_sub(capacity));
             self.end = self.data.len() as u32;
             res
         } else {
             let mut vec = EcoVec::from(&**self);
             let res = f(&mut vec);
+            allocate::<T>(vec.capacity());
             *self = vec.into();
             res
         }
@@ -198,6 +205,7 @@ impl<T: Clone> From<EcoVec<T>> for CowSlice<T> {
 
 impl<'a, T: Clone> From<&'a [T]> for CowSlice<T> {
     fn from(slice: &'a [T]) -> Self {
+        allocate::<T>(slice.len());
         Self {
             start: 0,
             end: slice.len() as u32,
@@ -208,6 +216,7 @@ impl<'a, T: Clone> From<&'a [T]> for CowSlice<T> {
 
 impl<T: Clone, const N: usize> From<[T; N]> for CowSlice<T> {
     fn from(array: [T; N]) -> Self {
+        allocate::<T>(N);
         Self {
             start: 0,
             end: N as u32,
@@ -309,6 +318,7 @@ impl<T: Clone> FromIterator<T> for CowSlice<T> {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
         let mut data = EcoVec::new();
         data.extend(iter);
+        allocate::<T>(data.capacity());
         data.into()
     }
 }
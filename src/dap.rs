@@ -0,0 +1,861 @@
+//! A Debug Adapter Protocol server built on the [`debug`](crate::debug) module
+//!
+//! The adapter hosts the runtime itself, so both `launch` and `attach` start the program named
+//! in their arguments. Program output is sent to the client as `output` events.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    env,
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use image::DynamicImage;
+use parking_lot::Mutex;
+use serde_json::{json, Value as Json};
+
+use crate::{
+    debug::*, AudioStreamFn, FfiSignature, FfiValue, Handle, NativeSys, SysBackend, Uiua,
+    UiuaError, Value,
+};
+
+const STACK_REF: i64 = 1;
+const UNDER_REF: i64 = 2;
+const INLINE_REF: i64 = 3;
+
+/// Run a Debug Adapter Protocol server over stdio
+pub fn run_debug_adapter() {
+    serve(BufReader::new(io::stdin()), io::stdout());
+}
+
+/// Run a Debug Adapter Protocol server that reads requests from `input` and writes to `output`
+fn serve(input: impl BufRead + Send + 'static, output: impl Write + Send + 'static) {
+    let session = Arc::new(Session::new(output));
+    let (launch_send, launch_recv) = channel();
+    let (command_send, command_recv) = channel();
+    let dispatcher = {
+        let session = session.clone();
+        thread::spawn(move || dispatch(&session, input, launch_send, command_send))
+    };
+    if let Ok(config) = launch_recv.recv() {
+        run_program(&session, config, command_recv);
+    }
+    _ = dispatcher.join();
+}
+
+struct LaunchConfig {
+    program: PathBuf,
+    args: Vec<String>,
+    cwd: Option<PathBuf>,
+    stop_on_entry: bool,
+}
+
+struct Session {
+    seq: AtomicI64,
+    out: Mutex<Box<dyn Write + Send>>,
+    breakpoints: Mutex<HashMap<PathBuf, Vec<usize>>>,
+    breakpoints_dirty: AtomicBool,
+    pause_requested: AtomicBool,
+    terminate: AtomicBool,
+    paused: AtomicBool,
+    break_on_throw: AtomicBool,
+    break_on_error: AtomicBool,
+    snapshot: Mutex<Option<Snapshot>>,
+}
+
+/// The state of the interpreter at a pause, rendered for the client
+struct Snapshot {
+    frames: Vec<Json>,
+    variables: HashMap<i64, Vec<Json>>,
+}
+
+impl Session {
+    fn new(output: impl Write + Send + 'static) -> Self {
+        Session {
+            seq: AtomicI64::new(1),
+            out: Mutex::new(Box::new(output)),
+            breakpoints: Mutex::default(),
+            breakpoints_dirty: AtomicBool::new(false),
+            pause_requested: AtomicBool::new(false),
+            terminate: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            break_on_throw: AtomicBool::new(false),
+            break_on_error: AtomicBool::new(false),
+            snapshot: Mutex::new(None),
+        }
+    }
+    fn send(&self, mut message: Json) {
+        message["seq"] = self.seq.fetch_add(1, Ordering::Relaxed).into();
+        let body = message.to_string();
+        let mut out = self.out.lock();
+        _ = write!(out, "Content-Length: {}\r\n\r\n{body}", body.len());
+        _ = out.flush();
+    }
+    fn respond(&self, request: &Json, body: Json) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }));
+    }
+    fn respond_error(&self, request: &Json, message: impl Into<String>) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message.into(),
+        }));
+    }
+    fn event(&self, event: &str, body: Json) {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }));
+    }
+    fn output(&self, category: &str, output: impl Into<String>) {
+        self.event(
+            "output",
+            json!({ "category": category, "output": output.into() }),
+        );
+    }
+}
+
+fn read_message(input: &mut impl BufRead) -> Option<Json> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+    let mut body = vec![0; length?];
+    input.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+/// Handle requests from the client until it disconnects
+fn dispatch(
+    session: &Session,
+    mut input: impl BufRead,
+    launch_send: Sender<LaunchConfig>,
+    command_send: Sender<DebugCommand>,
+) {
+    let mut config = None;
+    while let Some(request) = read_message(&mut input) {
+        let args = &request["arguments"];
+        let command = |cmd: DebugCommand| {
+            if session.paused.swap(false, Ordering::Relaxed) {
+                _ = command_send.send(cmd);
+            }
+        };
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                session.respond(
+                    &request,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsTerminateRequest": true,
+                        "exceptionBreakpointFilters": [
+                            { "filter": "throw", "label": "Assertion failures", "default": false },
+                            { "filter": "error", "label": "Runtime errors", "default": false },
+                        ],
+                    }),
+                );
+                session.event("initialized", json!({}));
+            }
+            "launch" | "attach" => {
+                let Some(program) = args["program"].as_str() else {
+                    session.respond_error(&request, "Missing `program` argument");
+                    continue;
+                };
+                config = Some(LaunchConfig {
+                    program: program.into(),
+                    args: (args["args"].as_array().into_iter().flatten())
+                        .filter_map(|arg| arg.as_str().map(Into::into))
+                        .collect(),
+                    cwd: args["cwd"].as_str().map(Into::into),
+                    stop_on_entry: args["stopOnEntry"].as_bool().unwrap_or(false),
+                });
+                session.respond(&request, json!({}));
+            }
+            "setBreakpoints" => {
+                let path = PathBuf::from(args["source"]["path"].as_str().unwrap_or_default());
+                let lines: Vec<usize> = (args["breakpoints"].as_array().into_iter().flatten())
+                    .filter_map(|bp| bp["line"].as_u64().map(|line| line as usize))
+                    .collect();
+                let breakpoints: Vec<Json> = (lines.iter())
+                    .map(|line| json!({ "verified": true, "line": line }))
+                    .collect();
+                session.breakpoints.lock().insert(path, lines);
+                session.breakpoints_dirty.store(true, Ordering::Relaxed);
+                session.respond(&request, json!({ "breakpoints": breakpoints }));
+            }
+            "setExceptionBreakpoints" => {
+                let filters = args["filters"].as_array().cloned().unwrap_or_default();
+                let has = |name: &str| filters.iter().any(|f| f == name);
+                session
+                    .break_on_throw
+                    .store(has("throw"), Ordering::Relaxed);
+                session
+                    .break_on_error
+                    .store(has("error"), Ordering::Relaxed);
+                session.respond(&request, json!({}));
+            }
+            "configurationDone" => {
+                session.respond(&request, json!({}));
+                if let Some(config) = config.take() {
+                    _ = launch_send.send(config);
+                }
+            }
+            "threads" => session.respond(
+                &request,
+                json!({ "threads": [{ "id": 1, "name": "main" }] }),
+            ),
+            "stackTrace" => {
+                let frames = (session.snapshot.lock().as_ref())
+                    .map(|snapshot| snapshot.frames.clone())
+                    .unwrap_or_default();
+                session.respond(
+                    &request,
+                    json!({ "stackFrames": frames, "totalFrames": frames.len() }),
+                );
+            }
+            "scopes" => session.respond(
+                &request,
+                json!({ "scopes": [
+                    { "name": "Stack", "variablesReference": STACK_REF, "expensive": false },
+                    { "name": "Under", "variablesReference": UNDER_REF, "expensive": false },
+                    { "name": "Inline", "variablesReference": INLINE_REF, "expensive": false },
+                ] }),
+            ),
+            "variables" => {
+                let reference = args["variablesReference"].as_i64().unwrap_or_default();
+                let variables = (session.snapshot.lock().as_ref())
+                    .and_then(|snapshot| snapshot.variables.get(&reference).cloned())
+                    .unwrap_or_default();
+                session.respond(&request, json!({ "variables": variables }));
+            }
+            "continue" => {
+                command(DebugCommand::Continue);
+                session.respond(&request, json!({ "allThreadsContinued": true }));
+            }
+            "next" => {
+                command(DebugCommand::StepOver);
+                session.respond(&request, json!({}));
+            }
+            "stepIn" => {
+                command(DebugCommand::StepInstr);
+                session.respond(&request, json!({}));
+            }
+            "stepOut" => {
+                command(DebugCommand::StepOut);
+                session.respond(&request, json!({}));
+            }
+            "pause" => {
+                session.pause_requested.store(true, Ordering::Relaxed);
+                session.respond(&request, json!({}));
+            }
+            "terminate" | "disconnect" => {
+                session.terminate.store(true, Ordering::Relaxed);
+                command(DebugCommand::Abort);
+                session.respond(&request, json!({}));
+                if request["command"] == "disconnect" {
+                    break;
+                }
+            }
+            command => session.respond_error(&request, format!("Unsupported request {command}")),
+        }
+    }
+    session.terminate.store(true, Ordering::Relaxed);
+    if session.paused.swap(false, Ordering::Relaxed) {
+        _ = command_send.send(DebugCommand::Abort);
+    }
+}
+
+fn run_program(session: &Arc<Session>, config: LaunchConfig, commands: Receiver<DebugCommand>) {
+    if let Some(cwd) = &config.cwd {
+        if let Err(e) = env::set_current_dir(cwd) {
+            session.output("stderr", format!("Failed to set working directory: {e}\n"));
+        }
+    }
+    let hook = DapHook {
+        session: session.clone(),
+        commands,
+        stepping: None,
+    };
+    let mut debugger = Debugger::new(hook).break_on_error(true);
+    if config.stop_on_entry {
+        debugger = debugger.pause_on_start();
+    }
+    let mut rt = Uiua::with_backend(DapSys(session.clone()))
+        .with_file_path(&config.program)
+        .with_args(config.args)
+        .with_debugger(debugger);
+    let res = rt.load_file(&config.program);
+    for diagnostic in rt.take_diagnostics() {
+        let report = diagnostic.report().color(false);
+        session.output("console", format!("{report}\n"));
+    }
+    let exit_code = match res {
+        Ok(()) => {
+            for value in rt.take_stack() {
+                session.output("stdout", format!("{}\n", value.show()));
+            }
+            0
+        }
+        Err(e) => {
+            if !session.terminate.load(Ordering::Relaxed) {
+                session.output("stderr", format!("{}\n", e.report().color(false)));
+            }
+            1
+        }
+    };
+    session.event("exited", json!({ "exitCode": exit_code }));
+    session.event("terminated", json!({}));
+}
+
+type Location = Option<(PathBuf, usize)>;
+
+struct DapHook {
+    session: Arc<Session>,
+    commands: Receiver<DebugCommand>,
+    /// The step command in progress, along with where and at what depth it started
+    stepping: Option<(DebugCommand, Location, usize)>,
+}
+
+fn frame_location(frame: &DebugFrame) -> Location {
+    Some((frame.path()?.to_path_buf(), frame.line_col()?.0))
+}
+
+impl DebugHook for DapHook {
+    fn poll(&mut self, breakpoints: &mut Vec<Breakpoint>) -> bool {
+        if self
+            .session
+            .breakpoints_dirty
+            .swap(false, Ordering::Relaxed)
+        {
+            *breakpoints = (self.session.breakpoints.lock().iter())
+                .flat_map(|(path, lines)| {
+                    lines.iter().map(|&line| Breakpoint::Line {
+                        path: Some(path.clone()),
+                        line,
+                    })
+                })
+                .collect();
+        }
+        self.session.terminate.load(Ordering::Relaxed)
+            || self.session.pause_requested.swap(false, Ordering::Relaxed)
+    }
+    fn pause(&mut self, pause: Pause) -> DebugCommand {
+        let session = &self.session;
+        if session.terminate.load(Ordering::Relaxed) {
+            return DebugCommand::Abort;
+        }
+        let frames = pause.state.call_stack();
+        let depth = frames.len();
+        let location = frames.last().and_then(frame_location);
+        let (reason, text) = match pause.reason {
+            PauseReason::Step => match self.stepping.take() {
+                None => ("entry", None),
+                Some((command, from, from_depth)) => {
+                    let same_line = from == location && from_depth == depth;
+                    let keep_going = match command {
+                        DebugCommand::StepOver => same_line || depth > from_depth,
+                        DebugCommand::StepInstr => same_line,
+                        _ => false,
+                    };
+                    if keep_going {
+                        self.stepping = Some((command, from, from_depth));
+                        return command;
+                    }
+                    ("step", None)
+                }
+            },
+            PauseReason::Breakpoint(_) => ("breakpoint", None),
+            PauseReason::Requested => ("pause", None),
+            PauseReason::Throw(message) => {
+                if !session.break_on_throw.load(Ordering::Relaxed) {
+                    return DebugCommand::Continue;
+                }
+                ("exception", Some(message))
+            }
+            PauseReason::Error(message) => {
+                if !session.break_on_error.load(Ordering::Relaxed) {
+                    return DebugCommand::Continue;
+                }
+                ("exception", Some(message))
+            }
+        };
+        *session.snapshot.lock() = Some(Snapshot::new(&pause.state, &frames));
+        session.paused.store(true, Ordering::Relaxed);
+        session.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "text": text,
+                "description": text,
+                "threadId": 1,
+                "allThreadsStopped": true,
+            }),
+        );
+        let command = self.commands.recv().unwrap_or(DebugCommand::Abort);
+        *session.snapshot.lock() = None;
+        if matches!(
+            command,
+            DebugCommand::StepInstr | DebugCommand::StepOver | DebugCommand::StepOut
+        ) {
+            self.stepping = Some((command, location, depth));
+        }
+        command
+    }
+}
+
+impl Snapshot {
+    fn new(state: &DebugState, frames: &[DebugFrame]) -> Self {
+        let frames = (frames.iter().enumerate().rev())
+            .map(|(i, frame)| {
+                let (line, column) = frame.line_col().unwrap_or((0, 0));
+                let source = frame.path().map(|path| {
+                    json!({
+                        "name": path.file_name().map(|name| name.to_string_lossy()),
+                        "path": path,
+                    })
+                });
+                json!({
+                    "id": i,
+                    "name": frame.id.to_string(),
+                    "source": source,
+                    "line": line,
+                    "column": column,
+                })
+            })
+            .collect();
+        let variables = |values: &[Value]| -> Vec<Json> {
+            (values.iter().rev().enumerate())
+                .map(|(i, value)| {
+                    json!({
+                        "name": format!("[{i}]"),
+                        "value": value.show(),
+                        "type": value.type_name(),
+                        "variablesReference": 0,
+                    })
+                })
+                .collect()
+        };
+        let variables = [
+            (STACK_REF, variables(state.stack())),
+            (UNDER_REF, variables(state.under_stack())),
+            (INLINE_REF, variables(state.inline_stack())),
+        ]
+        .into();
+        Snapshot { frames, variables }
+    }
+}
+
+/// A backend that sends output to the client and otherwise behaves like [`NativeSys`]
+struct DapSys(Arc<Session>);
+
+impl SysBackend for DapSys {
+    fn any(&self) -> &dyn Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn save_error_color(&self, error: &UiuaError) {
+        NativeSys.save_error_color(error)
+    }
+    fn print_str_stdout(&self, s: &str) -> Result<(), String> {
+        self.0.output("stdout", s);
+        Ok(())
+    }
+    fn print_str_stderr(&self, s: &str) -> Result<(), String> {
+        self.0.output("stderr", s);
+        Ok(())
+    }
+    fn print_str_trace(&self, s: &str) {
+        self.0.output("console", s);
+    }
+    fn scan_line_stdin(&self) -> Result<Option<String>, String> {
+        Err("Reading from stdin is not supported while debugging".into())
+    }
+    fn set_raw_mode(&self, _: bool) -> Result<(), String> {
+        Err("Setting raw mode is not supported while debugging".into())
+    }
+    fn var(&self, name: &str) -> Option<String> {
+        NativeSys.var(name)
+    }
+    fn term_size(&self) -> Result<(usize, usize), String> {
+        NativeSys.term_size()
+    }
+    fn file_exists(&self, path: &str) -> bool {
+        NativeSys.file_exists(path)
+    }
+    fn list_dir(&self, path: &str) -> Result<Vec<String>, String> {
+        NativeSys.list_dir(path)
+    }
+    fn is_file(&self, path: &str) -> Result<bool, String> {
+        NativeSys.is_file(path)
+    }
+    fn delete(&self, path: &str) -> Result<(), String> {
+        NativeSys.delete(path)
+    }
+    fn trash(&self, path: &str) -> Result<(), String> {
+        NativeSys.trash(path)
+    }
+    fn read(&self, handle: Handle, count: usize) -> Result<Vec<u8>, String> {
+        // Stdin carries the protocol, so it can't be read by the program
+        if handle == Handle::STDIN {
+            return Err("Reading from stdin is not supported while debugging".into());
+        }
+        NativeSys.read(handle, count)
+    }
+    fn read_until(&self, handle: Handle, delim: &[u8]) -> Result<Vec<u8>, String> {
+        if handle == Handle::STDIN {
+            return Err("Reading from stdin is not supported while debugging".into());
+        }
+        NativeSys.read_until(handle, delim)
+    }
+    fn write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        match handle {
+            Handle::STDOUT => self.print_str_stdout(&String::from_utf8_lossy(contents)),
+            Handle::STDERR => self.print_str_stderr(&String::from_utf8_lossy(contents)),
+            _ => NativeSys.write(handle, contents),
+        }
+    }
+    fn create_file(&self, path: &str) -> Result<Handle, String> {
+        NativeSys.create_file(path)
+    }
+    fn open_file(&self, path: &str) -> Result<Handle, String> {
+        NativeSys.open_file(path)
+    }
+    fn file_read_all(&self, path: &str) -> Result<Vec<u8>, String> {
+        NativeSys.file_read_all(path)
+    }
+    fn file_write_all(&self, path: &str, contents: &[u8]) -> Result<(), String> {
+        NativeSys.file_write_all(path, contents)
+    }
+    fn sleep(&self, seconds: f64) -> Result<(), String> {
+        NativeSys.sleep(seconds)
+    }
+    fn now(&self) -> f64 {
+        NativeSys.now()
+    }
+    fn random_seed(&self) -> u64 {
+        NativeSys.random_seed()
+    }
+    fn show_image(&self, image: DynamicImage) -> Result<(), String> {
+        NativeSys.show_image(image)
+    }
+    fn show_gif(&self, gif_bytes: Vec<u8>) -> Result<(), String> {
+        NativeSys.show_gif(gif_bytes)
+    }
+    fn play_audio(&self, wave_bytes: Vec<u8>) -> Result<(), String> {
+        NativeSys.play_audio(wave_bytes)
+    }
+    fn audio_sample_rate(&self) -> u32 {
+        NativeSys.audio_sample_rate()
+    }
+    fn stream_audio(&self, f: AudioStreamFn) -> Result<(), String> {
+        NativeSys.stream_audio(f)
+    }
+    fn tcp_listen(&self, addr: &str) -> Result<Handle, String> {
+        NativeSys.tcp_listen(addr)
+    }
+    fn tcp_accept(&self, handle: Handle) -> Result<Handle, String> {
+        NativeSys.tcp_accept(handle)
+    }
+    fn tcp_connect(&self, addr: &str) -> Result<Handle, String> {
+        NativeSys.tcp_connect(addr)
+    }
+    fn tcp_addr(&self, handle: Handle) -> Result<String, String> {
+        NativeSys.tcp_addr(handle)
+    }
+    fn tcp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        NativeSys.tcp_set_non_blocking(handle, non_blocking)
+    }
+    fn tcp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        NativeSys.tcp_set_read_timeout(handle, timeout)
+    }
+    fn tcp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        NativeSys.tcp_set_write_timeout(handle, timeout)
+    }
+    fn udp_bind(&self, addr: &str) -> Result<Handle, String> {
+        NativeSys.udp_bind(addr)
+    }
+    fn udp_send(&self, handle: Handle, addr: &str, bytes: &[u8]) -> Result<(), String> {
+        NativeSys.udp_send(handle, addr, bytes)
+    }
+    fn udp_receive(&self, handle: Handle, max: usize) -> Result<(Vec<u8>, String), String> {
+        NativeSys.udp_receive(handle, max)
+    }
+    fn udp_addr(&self, handle: Handle) -> Result<String, String> {
+        NativeSys.udp_addr(handle)
+    }
+    fn udp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        NativeSys.udp_set_non_blocking(handle, non_blocking)
+    }
+    fn udp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        NativeSys.udp_set_read_timeout(handle, timeout)
+    }
+    fn udp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        NativeSys.udp_set_write_timeout(handle, timeout)
+    }
+    fn ffi_load(&self, path: &str) -> Result<Handle, String> {
+        NativeSys.ffi_load(path)
+    }
+    fn ffi_call(
+        &self,
+        lib: Handle,
+        sig: &FfiSignature,
+        args: &mut [FfiValue],
+    ) -> Result<FfiValue, String> {
+        NativeSys.ffi_call(lib, sig, args)
+    }
+    fn close(&self, handle: Handle) -> Result<(), String> {
+        NativeSys.close(handle)
+    }
+    fn invoke(&self, path: &str) -> Result<(), String> {
+        NativeSys.invoke(path)
+    }
+    fn run_command_inherit(&self, command: &str, args: &[&str]) -> Result<i32, String> {
+        NativeSys.run_command_inherit(command, args)
+    }
+    fn run_command_capture(
+        &self,
+        command: &str,
+        args: &[&str],
+    ) -> Result<(i32, String, String), String> {
+        NativeSys.run_command_capture(command, args)
+    }
+    fn change_directory(&self, path: &str) -> Result<(), String> {
+        NativeSys.change_directory(path)
+    }
+    fn https_get(&self, request: &str, handle: Handle) -> Result<String, String> {
+        NativeSys.https_get(request, handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Read, path::Path, sync::mpsc::RecvTimeoutError};
+
+    use super::*;
+
+    /// A reader that receives its bytes from a channel
+    struct ChannelReader {
+        recv: Receiver<Vec<u8>>,
+        buffer: Vec<u8>,
+    }
+
+    impl Read for ChannelReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.buffer.is_empty() {
+                match self.recv.recv_timeout(Duration::from_secs(10)) {
+                    Ok(bytes) => self.buffer = bytes,
+                    Err(RecvTimeoutError::Disconnected) => return Ok(0),
+                    Err(RecvTimeoutError::Timeout) => {
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"))
+                    }
+                }
+            }
+            let n = buf.len().min(self.buffer.len());
+            buf[..n].copy_from_slice(&self.buffer[..n]);
+            self.buffer.drain(..n);
+            Ok(n)
+        }
+    }
+
+    /// A writer that sends its bytes to a channel
+    struct ChannelWriter(Sender<Vec<u8>>);
+
+    impl Write for ChannelWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            _ = self.0.send(buf.to_vec());
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn channel_pipe() -> (ChannelWriter, BufReader<ChannelReader>) {
+        let (send, recv) = channel();
+        let reader = ChannelReader {
+            recv,
+            buffer: Vec::new(),
+        };
+        (ChannelWriter(send), BufReader::new(reader))
+    }
+
+    struct Client {
+        seq: i64,
+        input: ChannelWriter,
+        output: BufReader<ChannelReader>,
+    }
+
+    impl Client {
+        fn request(&mut self, command: &str, arguments: Json) {
+            self.seq += 1;
+            let body = json!({
+                "seq": self.seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            })
+            .to_string();
+            write!(self.input, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+        }
+        /// Read messages until one matches the predicate, collecting the output events on the way
+        fn expect(&mut self, output: &mut String, f: impl Fn(&Json) -> bool) -> Json {
+            loop {
+                let message = read_message(&mut self.output).expect("adapter sent no message");
+                if message["event"] == "output" {
+                    output.push_str(message["body"]["output"].as_str().unwrap_or_default());
+                }
+                if f(&message) {
+                    return message;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn read_message_headers() {
+        let body = r#"{"command":"threads"}"#;
+        let text = format!(
+            "content-length: {}\r\nContent-Type: application/json\r\n\r\n{body}",
+            body.len()
+        );
+        let mut input = text.as_bytes();
+        let message = read_message(&mut input).unwrap();
+        assert_eq!(message["command"], "threads");
+        assert!(read_message(&mut input).is_none());
+    }
+
+    #[test]
+    fn stdin_is_not_readable() {
+        let sys = DapSys(Arc::new(Session::new(io::sink())));
+        assert!(sys.read(Handle::STDIN, 1).is_err());
+        assert!(sys.read_until(Handle::STDIN, b"\n").is_err());
+    }
+
+    /// Get the names of the methods in the block that starts with a line
+    fn block_methods(source: &str, start: &str) -> Vec<String> {
+        let block = &source[source.find(start).unwrap()..];
+        let block = &block[..block.find("\n}\n").unwrap()];
+        (block.lines())
+            .filter_map(|line| line.trim().strip_prefix("fn "))
+            .map(|line| line.split(['(', '<']).next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn every_method_is_implemented() {
+        // A method left to its default would behave differently under the debugger
+        let trait_methods = block_methods(
+            &fs::read_to_string("src/sys.rs").unwrap(),
+            "pub trait SysBackend",
+        );
+        let impl_methods = block_methods(
+            &fs::read_to_string("src/dap.rs").unwrap(),
+            "impl SysBackend for DapSys",
+        );
+        assert!(trait_methods.len() > 40);
+        for method in trait_methods {
+            assert!(
+                impl_methods.contains(&method),
+                "DapSys does not implement SysBackend::{method}"
+            );
+        }
+    }
+
+    #[test]
+    fn sockets_are_forwarded() {
+        let sys = DapSys(Arc::new(Session::new(io::sink())));
+        let handle = sys.udp_bind("127.0.0.1:0").unwrap();
+        assert!(sys.udp_addr(handle).unwrap().starts_with("127.0.0.1:"));
+        sys.close(handle).unwrap();
+    }
+
+    #[test]
+    fn breakpoint_session() {
+        let dir = Path::new("target/dap-test");
+        fs::create_dir_all(dir).unwrap();
+        let program = dir.join("main.ua");
+        fs::write(&program, "X ← 1\n&p + X 2\n").unwrap();
+        // Clients send absolute paths, while the program is loaded by a relative one
+        let absolute = program.canonicalize().unwrap();
+
+        let (input, server_input) = channel_pipe();
+        let (server_output, output) = channel_pipe();
+        let server = thread::spawn(move || serve(server_input, server_output));
+        let mut client = Client {
+            seq: 0,
+            input,
+            output,
+        };
+        let mut printed = String::new();
+        let is_event = |name: &'static str| move |message: &Json| message["event"] == name;
+
+        client.request("initialize", json!({}));
+        client.expect(&mut printed, is_event("initialized"));
+        client.request(
+            "setBreakpoints",
+            json!({ "source": { "path": absolute }, "breakpoints": [{ "line": 2 }] }),
+        );
+        client.request("launch", json!({ "program": program }));
+        client.request("configurationDone", json!({}));
+
+        let stopped = client.expect(&mut printed, is_event("stopped"));
+        assert_eq!(stopped["body"]["reason"], "breakpoint");
+        client.request("stackTrace", json!({ "threadId": 1 }));
+        let trace = client.expect(&mut printed, |message| message["command"] == "stackTrace");
+        assert_eq!(trace["body"]["stackFrames"][0]["line"], 2);
+
+        client.request("continue", json!({ "threadId": 1 }));
+        let exited = client.expect(&mut printed, is_event("exited"));
+        assert_eq!(exited["body"]["exitCode"], 0);
+        assert_eq!(printed, "3\n");
+
+        client.request("disconnect", json!({}));
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,420 @@
+//! Stack-level debugging of running Uiua code
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use crate::{lex::Span, Function, FunctionId, Ident, Instr, Uiua, UiuaError, UiuaResult, Value};
+
+/// A place where execution should pause
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Pause when execution reaches a line
+    Line {
+        /// The file the line is in
+        ///
+        /// If `None`, the line matches in any file.
+        /// Otherwise, it matches the same file, or any file whose path ends with this one.
+        path: Option<PathBuf>,
+        /// The 1-indexed line number
+        line: usize,
+    },
+    /// Pause when a named function is called
+    Binding(Ident),
+}
+
+impl Breakpoint {
+    /// Create a breakpoint on a line in any file
+    pub fn line(line: usize) -> Self {
+        Breakpoint::Line { path: None, line }
+    }
+    /// Create a breakpoint on a named function
+    pub fn binding(name: impl Into<Ident>) -> Self {
+        Breakpoint::Binding(name.into())
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+    /// Parse a breakpoint from `<line>`, `<path>:<line>`, or `<binding name>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(line) = s.parse() {
+            return Ok(Breakpoint::line(line));
+        }
+        if let Some((path, line)) = s.rsplit_once(':') {
+            if let Ok(line) = line.parse() {
+                return Ok(Breakpoint::Line {
+                    path: Some(path.into()),
+                    line,
+                });
+            }
+        }
+        if !s.is_empty() && s.chars().all(crate::is_ident_char) {
+            Ok(Breakpoint::binding(s))
+        } else {
+            Err(format!("Invalid breakpoint {s:?}"))
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Line {
+                path: Some(path),
+                line,
+            } => write!(f, "{}:{line}", path.display()),
+            Breakpoint::Line { path: None, line } => write!(f, "line {line}"),
+            Breakpoint::Binding(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Why execution paused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PauseReason {
+    /// A step command finished, or the debugger was set to pause on start
+    Step,
+    /// A breakpoint was hit
+    ///
+    /// Contains the index of the breakpoint in [`Debugger::breakpoints`].
+    Breakpoint(usize),
+    /// The hook requested a pause from [`DebugHook::poll`]
+    Requested,
+    /// A value was thrown by [`assert`](crate::Primitive::Assert)
+    ///
+    /// Execution cannot continue past the error, but its state can still be inspected.
+    Throw(String),
+    /// An error occurred
+    ///
+    /// Execution cannot continue past the error, but its state can still be inspected.
+    Error(String),
+}
+
+/// What to do after a pause
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugCommand {
+    /// Run until the next breakpoint
+    Continue,
+    /// Execute a single instruction, entering called functions
+    StepInstr,
+    /// Execute a single instruction, running called functions to completion
+    StepOver,
+    /// Run until the current function returns
+    StepOut,
+    /// Stop execution with an error
+    Abort,
+}
+
+/// A handler that is called whenever a [`Debugger`] pauses
+pub trait DebugHook: Send + 'static {
+    /// Handle a pause and decide how to continue
+    fn pause(&mut self, pause: Pause) -> DebugCommand;
+    /// Called before every instruction while running
+    ///
+    /// This can be used to update breakpoints from outside the interpreter.
+    /// Returning `true` pauses execution with [`PauseReason::Requested`].
+    #[allow(unused_variables)]
+    fn poll(&mut self, breakpoints: &mut Vec<Breakpoint>) -> bool {
+        false
+    }
+}
+
+impl<F> DebugHook for F
+where
+    F: FnMut(Pause) -> DebugCommand + Send + 'static,
+{
+    fn pause(&mut self, pause: Pause) -> DebugCommand {
+        self(pause)
+    }
+}
+
+/// Information passed to a [`DebugHook`] when execution pauses
+pub struct Pause<'a> {
+    /// Why execution paused
+    pub reason: PauseReason,
+    /// The state of the interpreter
+    pub state: DebugState<'a>,
+    /// The debugger's breakpoints, which may be modified
+    pub breakpoints: &'a mut Vec<Breakpoint>,
+}
+
+/// A view of the interpreter's state while paused
+#[derive(Clone, Copy)]
+pub struct DebugState<'a> {
+    env: &'a Uiua,
+}
+
+/// A function call in progress
+#[derive(Debug, Clone)]
+pub struct DebugFrame {
+    /// The id of the function being executed
+    pub id: FunctionId,
+    /// The function being executed
+    pub function: Arc<Function>,
+    /// The span at which the function was called
+    pub call_span: Span,
+    /// The span currently being executed in the function
+    pub span: Span,
+    /// The index of the current instruction in the function
+    pub pc: usize,
+}
+
+impl DebugFrame {
+    /// Get the instruction currently being executed in this frame
+    pub fn instr(&self) -> Option<&Instr> {
+        self.function.instrs.get(self.pc)
+    }
+    /// Get the path of the file currently being executed in this frame
+    pub fn path(&self) -> Option<&Path> {
+        match &self.span {
+            Span::Code(span) => span.path.as_deref(),
+            Span::Builtin => None,
+        }
+    }
+    /// Get the 1-indexed line and column currently being executed in this frame
+    pub fn line_col(&self) -> Option<(usize, usize)> {
+        match &self.span {
+            Span::Code(span) => Some((span.start.line, span.start.col)),
+            Span::Builtin => None,
+        }
+    }
+    /// Get the source code currently being executed in this frame
+    pub fn source(&self) -> Option<&str> {
+        match &self.span {
+            Span::Code(span) => Some(span.as_str()),
+            Span::Builtin => None,
+        }
+    }
+}
+
+impl<'a> DebugState<'a> {
+    /// Get the value stack
+    pub fn stack(&self) -> &'a [Value] {
+        &self.env.stack
+    }
+    /// Get the temporary stack used by [`under`](crate::Primitive::Under)
+    pub fn under_stack(&self) -> &'a [Value] {
+        &self.env.under_stack
+    }
+    /// Get the temporary stack used by inlined modifiers
+    pub fn inline_stack(&self) -> &'a [Value] {
+        &self.env.inline_stack
+    }
+    /// Get the function stack
+    pub fn function_stack(&self) -> &'a [Arc<Function>] {
+        &self.env.function_stack
+    }
+    /// Get the temporary function stack used by modifier placeholders
+    pub fn temp_function_stack(&self) -> &'a [Arc<Function>] {
+        &self.env.temp_function_stack
+    }
+    /// Get the call stack, outermost frame first
+    pub fn call_stack(&self) -> Vec<DebugFrame> {
+        let env = self.env;
+        (env.higher_scopes.iter())
+            .chain([&env.scope])
+            .flat_map(|scope| &scope.call)
+            .filter(|frame| !frame.function.instrs.is_empty())
+            .map(|frame| {
+                // Instructions like pushes have no span, so use the next one that does
+                let span = (frame.spans.last().map(|(span, _)| *span))
+                    .or_else(|| (frame.function.instrs.iter().skip(frame.pc)).find_map(Instr::span))
+                    .unwrap_or(frame.call_span);
+                DebugFrame {
+                    id: frame.function.id.clone(),
+                    function: frame.function.clone(),
+                    call_span: env.get_span(frame.call_span),
+                    span: env.get_span(span),
+                    pc: frame.pc,
+                }
+            })
+            .collect()
+    }
+    /// Get the innermost call frame
+    pub fn current_frame(&self) -> Option<DebugFrame> {
+        self.call_stack().pop()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Run,
+    Instr,
+    Over(usize),
+    Out(usize),
+}
+
+/// A debugger that can be attached to a [`Uiua`] runtime with [`Uiua::with_debugger`]
+///
+/// The debugger pauses execution at [`Breakpoint`]s and after step commands,
+/// calling its [`DebugHook`] to inspect the state and decide how to continue.
+///
+/// ```
+/// use uiua::{debug::*, *};
+///
+/// let mut seen = Vec::new();
+/// let (send, recv) = std::sync::mpsc::channel();
+/// let debugger = Debugger::new(move |pause: Pause| {
+///     send.send(pause.state.stack().len()).unwrap();
+///     DebugCommand::Continue
+/// })
+/// .with_breakpoint(Breakpoint::binding("F"));
+/// let mut uiua = Uiua::with_native_sys().with_debugger(debugger);
+/// uiua.load_str("F ← +1\nF F 5").unwrap();
+/// seen.extend(recv.try_iter());
+/// assert_eq!(seen, [1, 1]);
+/// ```
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    break_on_error: bool,
+    step: Step,
+    last_line: Option<(Option<Arc<Path>>, usize)>,
+    /// The canonical forms of paths that have been compared to breakpoints
+    canonical_paths: HashMap<PathBuf, PathBuf>,
+    hook: Box<dyn DebugHook>,
+}
+
+/// Check if a breakpoint's file is the file a span is in
+///
+/// Both paths are compared in canonical form, so absolute and relative paths to
+/// the same file match. A breakpoint path that is a suffix of the span's path
+/// also matches.
+fn same_file(
+    canonical_paths: &mut HashMap<PathBuf, PathBuf>,
+    bp_path: &Option<PathBuf>,
+    span_path: &Option<Arc<Path>>,
+) -> bool {
+    let (Some(bp_path), Some(span_path)) = (bp_path, span_path) else {
+        return bp_path.is_none();
+    };
+    if span_path.ends_with(bp_path) {
+        return true;
+    }
+    let mut canonical = |path: &Path| {
+        (canonical_paths.entry(path.into()))
+            .or_insert_with(|| path.canonicalize().unwrap_or_else(|_| path.into()))
+            .clone()
+    };
+    canonical(bp_path) == canonical(span_path)
+}
+
+impl Debugger {
+    /// Create a new debugger that calls a hook when it pauses
+    pub fn new(hook: impl DebugHook) -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            break_on_error: false,
+            step: Step::Run,
+            last_line: None,
+            canonical_paths: HashMap::new(),
+            hook: Box::new(hook),
+        }
+    }
+    /// Add a breakpoint
+    pub fn with_breakpoint(mut self, breakpoint: Breakpoint) -> Self {
+        self.breakpoints.push(breakpoint);
+        self
+    }
+    /// Pause before the first instruction is executed
+    pub fn pause_on_start(mut self) -> Self {
+        self.step = Step::Instr;
+        self
+    }
+    /// Set whether to pause when an error is thrown
+    pub fn break_on_error(mut self, break_on_error: bool) -> Self {
+        self.break_on_error = break_on_error;
+        self
+    }
+    /// Get the breakpoints
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+    /// Get the breakpoints mutably
+    pub fn breakpoints_mut(&mut self) -> &mut Vec<Breakpoint> {
+        &mut self.breakpoints
+    }
+    pub(crate) fn before_instr(&mut self, env: &Uiua) -> UiuaResult {
+        let frame = env.scope.call.last().unwrap();
+        let Some(instr) = frame.function.instrs.get(frame.pc) else {
+            return Ok(());
+        };
+        let depth = env.call_depth();
+        let line = instr.span().and_then(|span| match env.get_span(span) {
+            Span::Code(span) => Some((span.path, span.start.line)),
+            Span::Builtin => None,
+        });
+        let new_line = line.is_some() && line != self.last_line;
+        if let Some(line) = line {
+            self.last_line = Some(line);
+        }
+        let requested = self.hook.poll(&mut self.breakpoints);
+        let mut hit = None;
+        for (i, bp) in self.breakpoints.iter().enumerate() {
+            let matches = match bp {
+                Breakpoint::Line {
+                    path,
+                    line: bp_line,
+                } => {
+                    new_line
+                        && self.last_line.as_ref().is_some_and(|(span_path, line)| {
+                            line == bp_line && same_file(&mut self.canonical_paths, path, span_path)
+                        })
+                }
+                Breakpoint::Binding(name) => {
+                    frame.pc == 0
+                        && matches!(&frame.function.id, FunctionId::Named(id) if **id == **name)
+                }
+            };
+            if matches {
+                hit = Some(i);
+                break;
+            }
+        }
+        let reason = if let Some(i) = hit {
+            PauseReason::Breakpoint(i)
+        } else if requested {
+            PauseReason::Requested
+        } else {
+            match self.step {
+                Step::Instr => PauseReason::Step,
+                Step::Over(d) if depth <= d => PauseReason::Step,
+                Step::Out(d) if depth < d => PauseReason::Step,
+                _ => return Ok(()),
+            }
+        };
+        self.pause(env, reason)
+    }
+    pub(crate) fn on_error(&mut self, env: &Uiua, error: &UiuaError) {
+        if self.break_on_error {
+            let reason = match error {
+                UiuaError::Throw(value, _) => PauseReason::Throw(value.to_string()),
+                error => PauseReason::Error(error.message()),
+            };
+            _ = self.pause(env, reason);
+        }
+    }
+    fn pause(&mut self, env: &Uiua, reason: PauseReason) -> UiuaResult {
+        let command = self.hook.pause(Pause {
+            reason,
+            state: DebugState { env },
+            breakpoints: &mut self.breakpoints,
+        });
+        let depth = env.call_depth();
+        self.step = match command {
+            DebugCommand::Continue => Step::Run,
+            DebugCommand::StepInstr => Step::Instr,
+            DebugCommand::StepOver => Step::Over(depth),
+            DebugCommand::StepOut => Step::Out(depth),
+            DebugCommand::Abort => {
+                self.step = Step::Run;
+                return Err(env.error("Execution aborted by the debugger"));
+            }
+        };
+        Ok(())
+    }
+}
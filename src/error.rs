@@ -5,6 +5,7 @@ use colored::*;
 use crate::{
     function::FunctionId,
     lex::{Sp, Span},
+    memory::format_bytes,
     parse::ParseError,
     value::Value,
 };
@@ -33,6 +34,10 @@ pub enum UiuaError {
     Break(usize, Span),
     /// Maximum execution time exceeded
     Timeout(Span),
+    /// The memory limit in bytes was exceeded
+    MemoryLimit(usize, Span),
+    /// Execution was stopped with an [`InterruptHandle`](crate::InterruptHandle)
+    Interrupted(Span),
     /// A wrapper marking this error as being fill-related
     Fill(Box<Self>),
 }
@@ -87,6 +92,10 @@ impl fmt::Display for UiuaError {
             UiuaError::Throw(value, span) => write!(f, "{span}: {value}"),
             UiuaError::Break(_, span) => write!(f, "{span}: Break amount exceeded loop depth"),
             UiuaError::Timeout(_) => write!(f, "Maximum execution time exceeded"),
+            UiuaError::MemoryLimit(limit, _) => {
+                write!(f, "Memory limit of {} exceeded", format_bytes(*limit))
+            }
+            UiuaError::Interrupted(_) => write!(f, "Program interrupted"),
             UiuaError::Fill(error) => error.fmt(f),
         }
     }
@@ -120,6 +129,16 @@ impl UiuaError {
             error => Err(error),
         }
     }
+    /// Check if the error is from the program being interrupted
+    ///
+    /// Interruptions cannot be caught with `try`.
+    pub fn is_interrupted(&self) -> bool {
+        match self {
+            UiuaError::Traced { error, .. } | UiuaError::Fill(error) => error.is_interrupted(),
+            UiuaError::Interrupted(_) => true,
+            _ => false,
+        }
+    }
     /// Check if the error is fill-related
     pub(crate) fn is_fill(&self) -> bool {
         match self {
@@ -200,6 +219,12 @@ impl UiuaError {
             UiuaError::Timeout(span) => {
                 Report::new_multi(kind, [("Maximum execution time exceeded", span.clone())])
             }
+            UiuaError::MemoryLimit(_, span) => {
+                Report::new_multi(kind, [(self.to_string(), span.clone())])
+            }
+            UiuaError::Interrupted(span) => {
+                Report::new_multi(kind, [("Program interrupted", span.clone())])
+            }
             UiuaError::Fill(error) => error.report(),
             UiuaError::Load(..) | UiuaError::Format(..) => Report::new(kind, self.to_string()),
         }
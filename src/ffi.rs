@@ -0,0 +1,368 @@
+//! Types for calling C functions in shared libraries with `&ffi`
+
+use std::{fmt, mem::size_of, os::raw::c_long};
+
+use crate::{array::Array, value::Value};
+
+/// A C type that can be passed to or returned from a foreign function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FfiType {
+    /// `void`, only valid as a return type
+    Void,
+    /// A 32-bit integer
+    Int,
+    /// A 64-bit integer
+    Long,
+    /// A 32-bit float
+    Float,
+    /// A 64-bit float
+    Double,
+    /// An opaque pointer, passed as a number
+    Ptr,
+    /// A null-terminated string
+    CStr,
+    /// A pointer to a buffer of numbers
+    Array(FfiElem),
+}
+
+/// The element type of an [`FfiType::Array`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FfiElem {
+    /// `unsigned char` or `uint8_t`
+    Byte,
+    /// A 32-bit integer
+    Int,
+    /// A 64-bit integer
+    Long,
+    /// A 32-bit float
+    Float,
+    /// A 64-bit float
+    Double,
+}
+
+impl fmt::Display for FfiType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FfiType::Void => write!(f, "void"),
+            FfiType::Int => write!(f, "int"),
+            FfiType::Long => write!(f, "int64_t"),
+            FfiType::Float => write!(f, "float"),
+            FfiType::Double => write!(f, "double"),
+            FfiType::Ptr => write!(f, "void*"),
+            FfiType::CStr => write!(f, "char*"),
+            FfiType::Array(FfiElem::Byte) => write!(f, "uint8_t*"),
+            FfiType::Array(FfiElem::Int) => write!(f, "int*"),
+            FfiType::Array(FfiElem::Long) => write!(f, "int64_t*"),
+            FfiType::Array(FfiElem::Float) => write!(f, "float*"),
+            FfiType::Array(FfiElem::Double) => write!(f, "double*"),
+        }
+    }
+}
+
+/// A parsed C function declaration
+///
+/// ```
+/// use uiua::*;
+///
+/// let sig = FfiSignature::parse("double pow(double x, double y)").unwrap();
+/// assert_eq!(sig.name, "pow");
+/// assert_eq!(sig.ret, FfiType::Double);
+/// assert_eq!(sig.args, [FfiType::Double, FfiType::Double]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FfiSignature {
+    /// The name of the function
+    pub name: String,
+    /// The return type
+    pub ret: FfiType,
+    /// The argument types
+    pub args: Vec<FfiType>,
+}
+
+impl FfiSignature {
+    /// Parse a C function declaration like `double pow(double, double)`
+    ///
+    /// Supported types are `void`, `int`, `long`, `long long`, `int32_t`, `int64_t`,
+    /// `float`, `double`, `char*` (a string), `void*` (an opaque pointer), and pointers
+    /// to `unsigned char`, `uint8_t`, `int`, `long`, `float`, or `double` (a buffer of numbers).
+    /// Parameter names and `const` are ignored.
+    pub fn parse(decl: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid C function declaration `{decl}`");
+        let trimmed = decl.trim().trim_end_matches(';').trim_end();
+        let (head, params) = trimmed.split_once('(').ok_or_else(invalid)?;
+        let params = params.strip_suffix(')').ok_or_else(invalid)?;
+        let head = head.trim_end();
+        let name_start = head
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map_or(0, |i| i + 1);
+        let (ret, name) = head.split_at(name_start);
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let ret = parse_type(ret)?;
+        let args = if matches!(params.trim(), "" | "void") {
+            Vec::new()
+        } else {
+            params
+                .split(',')
+                .map(parse_type)
+                .collect::<Result<_, _>>()?
+        };
+        if args.contains(&FfiType::Void) {
+            return Err(format!("Parameter of `{name}` cannot be void"));
+        }
+        Ok(FfiSignature {
+            name: name.into(),
+            ret,
+            args,
+        })
+    }
+}
+
+const TYPE_WORDS: &[&str] = &[
+    "void", "char", "unsigned", "int", "long", "float", "double", "int32_t", "int64_t", "uint8_t",
+];
+
+fn parse_type(s: &str) -> Result<FfiType, String> {
+    let unsupported = || format!("Unsupported C type `{}`", s.trim());
+    let normalized = s.replace("[]", "*");
+    let pointers = normalized.matches('*').count();
+    let mut words: Vec<&str> = normalized
+        .split(|c: char| c == '*' || c.is_whitespace())
+        .filter(|word| !word.is_empty() && *word != "const")
+        .collect();
+    // Ignore a parameter name
+    if words.len() > 1 && !TYPE_WORDS.contains(words.last().unwrap()) {
+        words.pop();
+    }
+    let long = if size_of::<c_long>() == 8 {
+        FfiElem::Long
+    } else {
+        FfiElem::Int
+    };
+    let elem = match words.join(" ").as_str() {
+        "void" => {
+            return match pointers {
+                0 => Ok(FfiType::Void),
+                1 => Ok(FfiType::Ptr),
+                _ => Err(unsupported()),
+            }
+        }
+        "char" if pointers == 1 => return Ok(FfiType::CStr),
+        "unsigned char" | "uint8_t" if pointers == 1 => FfiElem::Byte,
+        "int" | "int32_t" => FfiElem::Int,
+        "long" => long,
+        "long long" | "int64_t" => FfiElem::Long,
+        "float" => FfiElem::Float,
+        "double" => FfiElem::Double,
+        _ => return Err(unsupported()),
+    };
+    Ok(match (pointers, elem) {
+        (0, FfiElem::Int) => FfiType::Int,
+        (0, FfiElem::Long) => FfiType::Long,
+        (0, FfiElem::Float) => FfiType::Float,
+        (0, FfiElem::Double) => FfiType::Double,
+        (1, elem) => FfiType::Array(elem),
+        _ => return Err(unsupported()),
+    })
+}
+
+/// A value passed to or returned from a foreign function
+#[derive(Debug, Clone, PartialEq)]
+#[allow(missing_docs)]
+pub enum FfiValue {
+    Void,
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Ptr(usize),
+    CStr(String),
+    Bytes(Vec<u8>),
+    Ints(Vec<i32>),
+    Longs(Vec<i64>),
+    Floats(Vec<f32>),
+    Doubles(Vec<f64>),
+}
+
+impl FfiValue {
+    /// Check if the value is a buffer that the function may have modified
+    pub fn is_array(&self) -> bool {
+        matches!(
+            self,
+            FfiValue::Bytes(_)
+                | FfiValue::Ints(_)
+                | FfiValue::Longs(_)
+                | FfiValue::Floats(_)
+                | FfiValue::Doubles(_)
+        )
+    }
+}
+
+impl From<FfiValue> for Value {
+    fn from(value: FfiValue) -> Self {
+        match value {
+            FfiValue::Void => Array::<f64>::default().into(),
+            FfiValue::Int(n) => (n as f64).into(),
+            FfiValue::Long(n) => (n as f64).into(),
+            FfiValue::Float(n) => (n as f64).into(),
+            FfiValue::Double(n) => n.into(),
+            FfiValue::Ptr(p) => (p as f64).into(),
+            FfiValue::CStr(s) => s.into(),
+            FfiValue::Bytes(bytes) => bytes.into_iter().collect::<Array<u8>>().into(),
+            FfiValue::Ints(ns) => ns.into_iter().map(f64::from).collect::<Array<f64>>().into(),
+            FfiValue::Longs(ns) => ns
+                .into_iter()
+                .map(|n| n as f64)
+                .collect::<Array<f64>>()
+                .into(),
+            FfiValue::Floats(ns) => ns.into_iter().map(f64::from).collect::<Array<f64>>().into(),
+            FfiValue::Doubles(ns) => ns.into_iter().collect::<Array<f64>>().into(),
+        }
+    }
+}
+
+/// Call a function in a loaded library
+///
+/// Buffer arguments are updated with whatever the function wrote to them.
+#[cfg(feature = "ffi")]
+pub(crate) fn call(
+    lib: &libloading::Library,
+    sig: &FfiSignature,
+    args: &mut [FfiValue],
+) -> Result<FfiValue, String> {
+    use std::{
+        ffi::{c_char, c_void, CStr, CString},
+        os::raw::c_int,
+    };
+
+    use libffi::middle::{arg, Cif, CodePtr, Type};
+
+    fn ffi_type(ty: FfiType) -> Type {
+        match ty {
+            FfiType::Void => Type::void(),
+            FfiType::Int => Type::c_int(),
+            FfiType::Long => Type::i64(),
+            FfiType::Float => Type::f32(),
+            FfiType::Double => Type::f64(),
+            FfiType::Ptr | FfiType::CStr | FfiType::Array(_) => Type::pointer(),
+        }
+    }
+
+    enum Slot {
+        Int(c_int),
+        Long(i64),
+        Float(f32),
+        Double(f64),
+        Ptr(*mut c_void),
+    }
+
+    if args.len() != sig.args.len() {
+        return Err(format!(
+            "`{}` takes {} arguments, but {} were given",
+            sig.name,
+            sig.args.len(),
+            args.len()
+        ));
+    }
+    let symbol = CString::new(sig.name.as_str()).map_err(|e| e.to_string())?;
+    let func =
+        unsafe { lib.get::<*mut c_void>(symbol.as_bytes_with_nul()) }.map_err(|e| e.to_string())?;
+    let code = CodePtr::from_ptr(*func);
+    // The strings must outlive the call
+    let mut strings = Vec::new();
+    let mut slots = Vec::with_capacity(args.len());
+    for (i, (ty, value)) in sig.args.iter().zip(args.iter_mut()).enumerate() {
+        slots.push(match (ty, value) {
+            (FfiType::Int, FfiValue::Int(n)) => Slot::Int(*n),
+            (FfiType::Long, FfiValue::Long(n)) => Slot::Long(*n),
+            (FfiType::Float, FfiValue::Float(n)) => Slot::Float(*n),
+            (FfiType::Double, FfiValue::Double(n)) => Slot::Double(*n),
+            (FfiType::Ptr, FfiValue::Ptr(p)) => Slot::Ptr(*p as *mut c_void),
+            (FfiType::CStr, FfiValue::CStr(s)) => {
+                let s = CString::new(s.as_str()).map_err(|e| e.to_string())?;
+                strings.push(s);
+                Slot::Ptr(strings.last().unwrap().as_ptr() as *mut c_void)
+            }
+            (FfiType::Array(FfiElem::Byte), FfiValue::Bytes(v)) => Slot::Ptr(v.as_mut_ptr().cast()),
+            (FfiType::Array(FfiElem::Int), FfiValue::Ints(v)) => Slot::Ptr(v.as_mut_ptr().cast()),
+            (FfiType::Array(FfiElem::Long), FfiValue::Longs(v)) => Slot::Ptr(v.as_mut_ptr().cast()),
+            (FfiType::Array(FfiElem::Float), FfiValue::Floats(v)) => {
+                Slot::Ptr(v.as_mut_ptr().cast())
+            }
+            (FfiType::Array(FfiElem::Double), FfiValue::Doubles(v)) => {
+                Slot::Ptr(v.as_mut_ptr().cast())
+            }
+            (ty, value) => {
+                return Err(format!(
+                    "Argument {} of `{}` should be {ty}, but it is {value:?}",
+                    i + 1,
+                    sig.name
+                ))
+            }
+        });
+    }
+    let ffi_args: Vec<_> = slots
+        .iter()
+        .map(|slot| match slot {
+            Slot::Int(n) => arg(n),
+            Slot::Long(n) => arg(n),
+            Slot::Float(n) => arg(n),
+            Slot::Double(n) => arg(n),
+            Slot::Ptr(p) => arg(p),
+        })
+        .collect();
+    let cif = Cif::new(sig.args.iter().copied().map(ffi_type), ffi_type(sig.ret));
+    // Safety: the caller of `&ffi` is responsible for the declaration matching the function
+    Ok(unsafe {
+        match sig.ret {
+            FfiType::Void => {
+                cif.call::<()>(code, &ffi_args);
+                FfiValue::Void
+            }
+            FfiType::Int => FfiValue::Int(cif.call::<c_int>(code, &ffi_args)),
+            FfiType::Long => FfiValue::Long(cif.call::<i64>(code, &ffi_args)),
+            FfiType::Float => FfiValue::Float(cif.call::<f32>(code, &ffi_args)),
+            FfiType::Double => FfiValue::Double(cif.call::<f64>(code, &ffi_args)),
+            FfiType::CStr => {
+                let ptr = cif.call::<*const c_char>(code, &ffi_args);
+                if ptr.is_null() {
+                    return Err(format!("`{}` returned a null string", sig.name));
+                }
+                FfiValue::CStr(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+            }
+            FfiType::Ptr | FfiType::Array(_) => {
+                FfiValue::Ptr(cif.call::<*mut c_void>(code, &ffi_args) as usize)
+            }
+        }
+    })
+}
+
+#[cfg(all(test, feature = "ffi", target_os = "linux"))]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn call_libc() {
+        let mut env = Uiua::with_native_sys();
+        env.load_str(
+            r#"
+Libc ← &ffil "libc.so.6"
+⍤"abs"≍ 5 &ffi "int abs(int)" Libc ¯5
+⍤"atoi"≍ 42 &ffi "int atoi(const char* s)" Libc "42"
+⍤"memset"≍ [7 7 3] ⊔⊢↘1 &ffi "void* memset(unsigned char s[], int, long)" Libc {[1 2 3] 7 2}
+&cl Libc
+⍤"under"≍ 3 ⍜&ffil(&ffi "double fabs(double)") "libm.so.6" ¯3
+"#,
+        )
+        .unwrap_or_else(|e| panic!("{}", e.report()));
+    }
+
+    #[test]
+    fn sandbox_denies_ffi() {
+        let mut env = Uiua::with_backend(SandboxSys::new(SandboxPolicy::new()));
+        assert!(env.load_str(r#"&ffil "libc.so.6""#).is_err());
+        let denials = env.downcast_backend::<SandboxSys>().unwrap().take_denials();
+        assert_eq!(denials[0].capability, Capability::Ffi);
+    }
+}
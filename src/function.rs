@@ -43,6 +43,11 @@ pub enum Instr {
     },
     /// Call a dynamic function
     Dynamic(DynamicFunction),
+    /// Format values into a string
+    Format {
+        parts: Arc<[String]>,
+        span: usize,
+    },
     PushTempFunctions(usize),
     PopTempFunctions(usize),
     GetTempFunction {
@@ -85,6 +90,7 @@ impl PartialEq for Instr {
             (Self::EndArray { .. }, Self::EndArray { .. }) => true,
             (Self::Prim(a, s_span), Self::Prim(b, b_span)) => a == b && s_span == b_span,
             (Self::Call(a), Self::Call(b)) => a == b,
+            (Self::Format { parts: a, .. }, Self::Format { parts: b, .. }) => a == b,
             (Self::PushTempUnder { count: a, .. }, Self::PushTempUnder { count: b, .. }) => a == b,
             (Self::PopTempUnder { count: a, .. }, Self::PopTempUnder { count: b, .. }) => a == b,
             (Self::PushTempInline { count: a, .. }, Self::PushTempInline { count: b, .. }) => {
@@ -153,6 +159,7 @@ impl Hash for Instr {
             Instr::PopTempFunctions(count) => count.hash(state),
             Instr::GetTempFunction { offset, .. } => offset.hash(state),
             Instr::Dynamic(f) => f.id.hash(state),
+            Instr::Format { parts, .. } => parts.hash(state),
             Instr::PushTempUnder { count, .. } => count.hash(state),
             Instr::PopTempUnder { count, .. } => count.hash(state),
             Instr::PushTempInline { count, .. } => count.hash(state),
@@ -175,6 +182,54 @@ impl Instr {
     pub fn push_func(f: impl Into<Arc<Function>>) -> Self {
         Self::PushFunc(f.into())
     }
+    /// Get the index of the span of this instruction, if it has one
+    pub(crate) fn span(&self) -> Option<usize> {
+        match self {
+            Self::Push(_)
+            | Self::BeginArray
+            | Self::PushFunc(_)
+            | Self::Dynamic(_)
+            | Self::PushTempFunctions(_)
+            | Self::PopTempFunctions(_) => None,
+            Self::EndArray { span, .. }
+            | Self::Prim(_, span)
+            | Self::ImplPrim(_, span)
+            | Self::Call(span)
+            | Self::Switch { span, .. }
+            | Self::Format { span, .. }
+            | Self::GetTempFunction { span, .. }
+            | Self::PushTempUnder { span, .. }
+            | Self::PopTempUnder { span, .. }
+            | Self::PushTempInline { span, .. }
+            | Self::PopTempInline { span, .. }
+            | Self::CopyTempInline { span, .. }
+            | Self::DropTempInline { span, .. } => Some(*span),
+        }
+    }
+    /// Get a mutable reference to the index of the span of this instruction, if it has one
+    pub(crate) fn span_mut(&mut self) -> Option<&mut usize> {
+        match self {
+            Self::Push(_)
+            | Self::BeginArray
+            | Self::PushFunc(_)
+            | Self::Dynamic(_)
+            | Self::PushTempFunctions(_)
+            | Self::PopTempFunctions(_) => None,
+            Self::EndArray { span, .. }
+            | Self::Prim(_, span)
+            | Self::ImplPrim(_, span)
+            | Self::Call(span)
+            | Self::Switch { span, .. }
+            | Self::Format { span, .. }
+            | Self::GetTempFunction { span, .. }
+            | Self::PushTempUnder { span, .. }
+            | Self::PopTempUnder { span, .. }
+            | Self::PushTempInline { span, .. }
+            | Self::PopTempInline { span, .. }
+            | Self::CopyTempInline { span, .. }
+            | Self::DropTempInline { span, .. } => Some(span),
+        }
+    }
     pub(crate) fn is_temp(&self) -> bool {
         matches!(
             self,
@@ -219,6 +274,16 @@ impl fmt::Display for Instr {
             Instr::PopTempFunctions(count) => write!(f, "<pop {count} functions>"),
             Instr::GetTempFunction { offset, .. } => write!(f, "<get function at {offset}>"),
             Instr::Dynamic(df) => write!(f, "{df:?}"),
+            Instr::Format { parts, .. } => {
+                write!(f, "$\"")?;
+                for (i, part) in parts.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "_")?;
+                    }
+                    write!(f, "{part}")?;
+                }
+                write!(f, "\"")
+            }
             Instr::PushTempUnder { count, .. } => write!(f, "<push under {count}>"),
             Instr::PopTempUnder { count, .. } => write!(f, "<pop under {count}>"),
             Instr::PushTempInline { count, .. } => write!(f, "<push inline {count}>"),
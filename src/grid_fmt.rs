@@ -95,14 +95,20 @@ impl GridFmt for char {
     }
 }
 
+impl GridFmt for Value {
+    fn fmt_grid(&self, boxed: bool) -> Grid {
+        match self {
+            Value::Num(array) => array.fmt_grid(boxed),
+            Value::Byte(array) => array.fmt_grid(boxed),
+            Value::Char(array) => array.fmt_grid(boxed),
+            Value::Box(array) => array.fmt_grid(boxed),
+        }
+    }
+}
+
 impl GridFmt for Boxed {
     fn fmt_grid(&self, boxed: bool) -> Grid {
-        let mut grid = match self.as_value() {
-            Value::Num(array) => array.fmt_grid(true),
-            Value::Byte(array) => array.fmt_grid(true),
-            Value::Char(array) => array.fmt_grid(true),
-            Value::Box(array) => array.fmt_grid(true),
-        };
+        let mut grid = self.as_value().fmt_grid(true);
         if boxed && grid.len() == 1 {
             grid = vec![boxed_scalar(true)
                 .chain(grid.into_iter().flatten())
@@ -137,10 +143,25 @@ impl<T: GridFmt + ArrayValue> GridFmt for Array<T> {
         }
         // Fill the metagrid
         let mut metagrid = Metagrid::new();
-
+        let rank = if let Some(keys) =
+            (self.map_keys.as_ref()).filter(|keys| keys.keys().row_count() == self.row_count())
+        {
+            // Show each key next to its value
+            for (i, key) in keys.keys().rows().enumerate() {
+                let arrow = vec![" → ".chars().collect()];
+                metagrid.push(vec![
+                    key.fmt_grid(false),
+                    arrow,
+                    self.row(i).fmt_grid(false),
+                ]);
+            }
+            2
+        } else {
+            fmt_array(&self.shape, &self.data, stringy, boxed, &mut metagrid);
+            self.rank()
+        };
         let mut grid: Grid = Grid::new();
 
-        fmt_array(&self.shape, &self.data, stringy, boxed, &mut metagrid);
         // Determine max row heights and column widths
         let metagrid_width = metagrid.iter().map(|row| row.len()).max().unwrap();
         let metagrid_height = metagrid.len();
@@ -176,7 +197,7 @@ impl<T: GridFmt + ArrayValue> GridFmt for Array<T> {
         }
         // Outline the grid
         let row_count = grid.len();
-        if row_count == 1 && self.rank() == 1 {
+        if row_count == 1 && rank == 1 {
             // Add brackets to vectors
             if !stringy {
                 let (left, right) = if boxed { ('⟦', '⟧') } else { ('[', ']') };
@@ -187,15 +208,10 @@ impl<T: GridFmt + ArrayValue> GridFmt for Array<T> {
             // Add corners to non-vectors
             let width = grid[0].len();
             let height = grid.len();
-            pad_grid_center(
-                width + 4,
-                (height + 2).max(self.rank() + 1),
-                false,
-                &mut grid,
-            );
+            pad_grid_center(width + 4, (height + 2).max(rank + 1), false, &mut grid);
             grid[0][0] = if boxed { '╓' } else { '╭' };
             grid[0][1] = '─';
-            for i in 0..self.rank().saturating_sub(1) {
+            for i in 0..rank.saturating_sub(1) {
                 grid[i + 1][0] = if boxed { '║' } else { '╷' };
             }
             *grid.last_mut().unwrap().last_mut().unwrap() = if boxed { '╜' } else { '╯' };
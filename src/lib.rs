@@ -5,7 +5,7 @@ This is the crate so you can use Uiua as a Rust library. If you just want to wri
 
 # Usage
 
-The main entry point is the [`Uiua`] struct, which is the Uiua runtime. It must be created with a [`SysBackend`]. [`Uiua::with_native_sys`] is a convenient way to create a Uiua runtime that uses the same backend as the Uiua CLI, though keep in mind it gives full access to the filesystem and TCP sockets and so probably shouldn't be used in a sandboxed environment.
+The main entry point is the [`Uiua`] struct, which is the Uiua runtime. It must be created with a [`SysBackend`]. [`Uiua::with_native_sys`] is a convenient way to create a Uiua runtime that uses the same backend as the Uiua CLI, though keep in mind it gives full access to the filesystem and TCP sockets and so probably shouldn't be used in a sandboxed environment. [`SandboxSys`] wraps the native backend with a [`SandboxPolicy`] that only grants the capabilities you allow. [`RecordingSys`] logs a program's interactions with any backend so that [`ReplaySys`] can replay them later.
 
 [`Value`] is the generic value type. It wraps one of four [`Array`] types.
 
@@ -44,6 +44,16 @@ uiua.load_str("MyAdd 2 3").unwrap();
 let res = uiua.pop_num().unwrap();
 assert_eq!(res, 5.0);
 ```
+You can call bindings from Rust with [`Uiua::call_binding`] or [`Uiua::get_binding`]. Arguments and results are converted with the [`IntoValues`] and [`FromValues`] traits.
+```rust
+use uiua::*;
+
+let mut uiua = Uiua::with_native_sys();
+uiua.load_str("Dist ← √/+ⁿ2").unwrap();
+
+let res: f64 = uiua.call_binding("Dist", vec![3.0, 4.0]).unwrap();
+assert_eq!(res, 5.0);
+```
 You can format Uiua code with the [`mod@format`] module.
 ```rust
 use uiua::format::*;
@@ -60,46 +70,76 @@ assert_eq!(formatted, "↯3_4⇡12");
 mod algorithm;
 mod array;
 mod ast;
+mod binding;
 mod boxed;
+mod bytecode;
 mod check;
 mod compile;
+mod coverage;
 mod cowslice;
+#[cfg(feature = "dap")]
+mod dap;
+pub mod debug;
 mod error;
+mod ffi;
 pub mod format;
 mod function;
 mod grid_fmt;
 mod lex;
 mod lsp;
+mod memory;
 mod parse;
 mod primitive;
 #[doc(hidden)]
 pub mod profile;
+mod profiler;
+mod program;
 mod run;
+mod snapshot;
 mod sys;
+mod sys_memory;
 mod sys_native;
+mod sys_record;
+mod sys_sandbox;
+mod test_report;
 mod value;
 
 use std::sync::Arc;
 
 pub use {
     array::*,
+    binding::*,
     boxed::*,
+    bytecode::CompiledModule,
+    coverage::*,
     error::*,
+    ffi::*,
     function::*,
     lex::is_ident_char,
     lsp::*,
     lsp::{spans, SpanKind},
     parse::parse,
     primitive::*,
+    profiler::*,
+    program::Program,
     run::*,
+    snapshot::*,
     sys::*,
+    sys_memory::*,
     sys_native::*,
+    sys_record::*,
+    sys_sandbox::*,
+    test_report::*,
     value::*,
 };
 
 /// A Uiua identifier
 pub type Ident = Arc<str>;
 
+#[cfg(feature = "dap")]
+#[doc(hidden)]
+pub use dap::run_debug_adapter;
+
 #[test]
 fn suite() {
     for entry in std::fs::read_dir("tests").unwrap() {
@@ -116,6 +156,53 @@ fn suite() {
     }
 }
 
+#[test]
+fn unoptimized_suite() {
+    // The optimizer must not change what a program leaves on the stack or prints
+    fn run(path: &std::path::Path, optimize: bool) -> Result<(Vec<String>, String), String> {
+        let mut env = Uiua::with_backend(MemoryFsSys::new())
+            .with_optimization(optimize)
+            .with_seed(0)
+            .with_virtual_clock(0.0);
+        let res = env.load_file(path);
+        let stack = env.take_stack().iter().map(Value::show).collect();
+        let stdout = env.downcast_backend::<MemoryFsSys>().unwrap().take_stdout();
+        res.map(|_| (stack, stdout)).map_err(|e| e.message())
+    }
+    for entry in std::fs::read_dir("tests").unwrap() {
+        let entry = entry.unwrap();
+        let path = entry.path();
+        if path.is_file() && path.extension().is_some_and(|s| s == "ua") {
+            assert_eq!(
+                run(&path, true),
+                run(&path, false),
+                "Optimization changed the behavior of {}",
+                path.display()
+            );
+        }
+    }
+}
+
+#[test]
+fn compiled_suite() {
+    for entry in std::fs::read_dir("tests").unwrap() {
+        let entry = entry.unwrap();
+        let path = entry.path();
+        if path.is_file() && path.extension().is_some_and(|s| s == "ua") {
+            let module = Uiua::with_native_sys().compile_file(&path).unwrap();
+            let bytes = module.to_bytes().unwrap();
+            let module = CompiledModule::from_bytes(&bytes).unwrap();
+            if let Err(e) = Uiua::with_native_sys().load_module(&module) {
+                panic!(
+                    "Compiled test failed in {}:\n{}",
+                    path.display(),
+                    e.report()
+                );
+            }
+        }
+    }
+}
+
 #[test]
 fn no_dbgs() {
     fn recurse_dirs(dir: &std::path::Path, f: &impl Fn(&std::path::Path)) {
@@ -11,21 +11,32 @@ use std::{
     time::Duration,
 };
 
-use clap::{error::ErrorKind, Parser};
+use clap::{error::ErrorKind, Parser, ValueEnum};
 use colored::*;
 use instant::Instant;
 use notify::{EventKind, RecursiveMode, Watcher};
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
 use uiua::{
+    debug::{Breakpoint, DebugCommand, DebugHook, Debugger, Pause, PauseReason},
     format::{format_file, format_str, FormatConfig, FormatConfigSource},
-    spans, PrimClass, RunMode, SpanKind, Uiua, UiuaError, UiuaResult,
+    spans, InterruptHandle, NativeSys, PrimClass, Profile, RecordingSys, ReplaySys, Report,
+    ReportKind, RunMode, Snapshot, SnapshotFile, SpanKind, TestReport, TestResult, Uiua, UiuaError,
+    UiuaResult,
 };
 
 fn main() {
     color_backtrace::install();
 
     let _ = ctrlc::set_handler(|| {
+        // Stop a running program at the next instruction the first time,
+        // and exit immediately if it is still running
+        if let Some(handle) = &*INTERRUPT.lock() {
+            if !handle.is_interrupted() {
+                handle.interrupt();
+                return;
+            }
+        }
         let mut child = WATCH_CHILD.lock();
         if let Some(ch) = &mut *child {
             _ = ch.kill();
@@ -47,6 +58,7 @@ fn main() {
 }
 
 static WATCH_CHILD: Lazy<Mutex<Option<Child>>> = Lazy::new(Default::default);
+static INTERRUPT: Lazy<Mutex<Option<InterruptHandle>>> = Lazy::new(Default::default);
 
 fn run() -> UiuaResult {
     if cfg!(feature = "profile") {
@@ -85,6 +97,15 @@ fn run() -> UiuaResult {
                 no_update,
                 time_instrs,
                 mode,
+                module_cache,
+                no_optimize,
+                record,
+                replay,
+                seed,
+                profile,
+                profile_prefix,
+                profile_top,
+                memory_limit,
                 #[cfg(feature = "audio")]
                 audio_options,
                 args,
@@ -103,7 +124,8 @@ fn run() -> UiuaResult {
                         }
                     }
                 };
-                if !no_format {
+                let compiled = path.extension().is_some_and(|ext| ext == "uac");
+                if !no_format && !compiled {
                     let config = FormatConfig::from_source(
                         formatter_options.format_config_source,
                         Some(&path),
@@ -113,15 +135,100 @@ fn run() -> UiuaResult {
                 let mode = mode.unwrap_or(RunMode::Normal);
                 #[cfg(feature = "audio")]
                 setup_audio(audio_options);
-                let mut rt = Uiua::with_native_sys()
+                let rt = if let Some(log_path) = &record {
+                    let log = fs::File::create(log_path).unwrap_or_else(|e| {
+                        eprintln!("Failed to create {}: {e}", log_path.display());
+                        exit(1)
+                    });
+                    Uiua::with_backend(RecordingSys::new(NativeSys).with_writer(log))
+                } else if let Some(log_path) = &replay {
+                    let replay = fs::read_to_string(log_path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|log| ReplaySys::new(&log));
+                    match replay {
+                        Ok(replay) => Uiua::with_backend(replay),
+                        Err(e) => {
+                            eprintln!("Failed to load {}: {e}", log_path.display());
+                            exit(1)
+                        }
+                    }
+                } else {
+                    Uiua::with_native_sys()
+                };
+                let mut rt = rt
                     .with_mode(mode)
                     .with_file_path(&path)
                     .with_args(args)
                     .print_diagnostics(true)
-                    .time_instrs(time_instrs);
-                rt.load_file(path)?;
-                for value in rt.take_stack() {
-                    println!("{}", value.show());
+                    .time_instrs(time_instrs)
+                    .with_optimization(!no_optimize)
+                    .with_profiling(profile);
+                if let Some(bytes) = memory_limit {
+                    rt = rt.with_memory_limit(bytes);
+                }
+                *INTERRUPT.lock() = Some(rt.interrupt_handle());
+                if let Some(dir) = module_cache {
+                    rt = rt.with_module_cache(dir);
+                }
+                if let Some(seed) = seed {
+                    rt = rt.with_seed(seed).with_virtual_clock(0.0);
+                }
+                let res = if compiled {
+                    rt.load_module_file(path)
+                } else {
+                    rt.load_file(path)
+                };
+                // An interrupted program's stack is still shown
+                if res
+                    .as_ref()
+                    .map_or_else(UiuaError::is_interrupted, |_| true)
+                {
+                    for value in rt.take_stack() {
+                        println!("{}", value.show());
+                    }
+                }
+                if let Some(profile) = rt.profile() {
+                    write_profile(&profile_prefix, &profile, profile_top);
+                }
+                if let Some(report) = rt.memory_report() {
+                    eprintln!("{report}");
+                }
+                res?;
+                if let Some(recording) = rt.downcast_backend::<RecordingSys>() {
+                    if let Err(e) = recording.finish() {
+                        eprintln!("Failed to write replay log: {e}");
+                        exit(1);
+                    }
+                }
+                if let Some(replay) = rt.downcast_backend::<ReplaySys>() {
+                    if let Err(e) = replay.finish() {
+                        eprintln!("{e}");
+                        exit(1);
+                    }
+                }
+            }
+            App::Build { path, output } => {
+                let path = if let Some(path) = path {
+                    path
+                } else {
+                    match working_file_path() {
+                        Ok(path) => path,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return Ok(());
+                        }
+                    }
+                };
+                let output = output.unwrap_or_else(|| path.with_extension("uac"));
+                let module = Uiua::with_native_sys()
+                    .with_file_path(&path)
+                    .print_diagnostics(true)
+                    .compile_file(&path)?;
+                let res = (module.to_bytes())
+                    .and_then(|bytes| fs::write(&output, bytes).map_err(|e| e.to_string()));
+                if let Err(e) = res {
+                    eprintln!("Failed to write {}: {e}", output.display());
+                    exit(1);
                 }
             }
             App::Eval {
@@ -144,6 +251,13 @@ fn run() -> UiuaResult {
             App::Test {
                 path,
                 formatter_options,
+                seed,
+                bless,
+                filter,
+                report,
+                report_file,
+                coverage,
+                lcov,
             } => {
                 let path = if let Some(path) = path {
                     path
@@ -156,14 +270,84 @@ fn run() -> UiuaResult {
                         }
                     }
                 };
+                if coverage && lcov.extension().is_some_and(|ext| ext == "ua") {
+                    eprintln!("Refusing to write coverage to {}", lcov.display());
+                    exit(1);
+                }
                 let config =
                     FormatConfig::from_source(formatter_options.format_config_source, Some(&path))?;
                 format_file(&path, &config)?;
-                Uiua::with_native_sys()
+                let mut rt = Uiua::with_native_sys()
                     .with_mode(RunMode::Test)
-                    .print_diagnostics(true)
-                    .load_file(path)?;
-                println!("No failures!");
+                    .print_diagnostics(true);
+                if let Some(seed) = seed {
+                    rt = rt.with_seed(seed).with_virtual_clock(0.0);
+                }
+                if let Some(filter) = &filter {
+                    rt = rt.with_test_filter(filter);
+                }
+                if coverage {
+                    rt = rt.with_coverage(true);
+                }
+                rt.load_file(&path)?;
+                let mut results = rt.take_test_results();
+                let snapshots = rt.take_snapshots();
+                let all_passed = results.iter().all(TestResult::passed);
+                if !bless {
+                    check_snapshots(&path, &snapshots, filter.is_some(), &mut results);
+                } else if all_passed {
+                    bless_snapshots(&path, &snapshots);
+                }
+                let test_report = TestReport::new(path.display().to_string(), results);
+                let report_to_stdout = report.is_some() && report_file.is_none();
+                if let Some(format) = report {
+                    let text = match format {
+                        ReportFormat::Junit => test_report.to_junit(),
+                        ReportFormat::Json => test_report.to_json(),
+                    };
+                    if let Some(report_file) = &report_file {
+                        if let Err(e) = fs::write(report_file, text) {
+                            eprintln!("Failed to write {}: {e}", report_file.display());
+                            exit(1);
+                        }
+                    } else {
+                        print!("{text}");
+                    }
+                }
+                for res in &test_report.results {
+                    if let Some(failure) = &res.failure {
+                        eprintln!("{} {} (line {})", "FAIL".red(), res.name, res.line);
+                        eprintln!("{failure}\n");
+                    }
+                }
+                let summary = format!(
+                    "{} passed, {} failed",
+                    test_report.passed(),
+                    test_report.failed()
+                );
+                if report_to_stdout {
+                    eprintln!("{summary}");
+                } else {
+                    println!("{summary}");
+                }
+                if bless && !all_passed {
+                    eprintln!("Snapshots were not blessed because some tests failed");
+                }
+                if let Some(coverage) = rt.coverage() {
+                    if let Err(e) = fs::write(&lcov, coverage.to_lcov()) {
+                        eprintln!("Failed to write {}: {e}", lcov.display());
+                        exit(1);
+                    }
+                    let summary = coverage.summary();
+                    if report_to_stdout {
+                        eprint!("{summary}");
+                    } else {
+                        print!("{summary}");
+                    }
+                }
+                if test_report.failed() > 0 {
+                    exit(1);
+                }
             }
             App::Watch {
                 no_format,
@@ -187,8 +371,43 @@ fn run() -> UiuaResult {
                     eprintln!("Error watching file: {e}");
                 }
             }
+            App::Debug {
+                path,
+                breakpoints,
+                break_on_error,
+                args,
+            } => {
+                let path = if let Some(path) = path {
+                    path
+                } else {
+                    match working_file_path() {
+                        Ok(path) => path,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return Ok(());
+                        }
+                    }
+                };
+                let mut debugger = Debugger::new(CliDebugger::default())
+                    .break_on_error(break_on_error)
+                    .pause_on_start();
+                for breakpoint in breakpoints {
+                    debugger = debugger.with_breakpoint(breakpoint);
+                }
+                let mut rt = Uiua::with_native_sys()
+                    .with_file_path(&path)
+                    .with_args(args)
+                    .print_diagnostics(true)
+                    .with_debugger(debugger);
+                rt.load_file(path)?;
+                for value in rt.take_stack() {
+                    println!("{}", value.show());
+                }
+            }
             #[cfg(feature = "lsp")]
             App::Lsp => uiua::run_language_server(),
+            #[cfg(feature = "dap")]
+            App::Dap => uiua::run_debug_adapter(),
             App::Repl {
                 formatter_options,
                 #[cfg(feature = "audio")]
@@ -264,6 +483,103 @@ impl fmt::Display for NoWorkingFile {
     }
 }
 
+fn parse_memory_size(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let (number, scale) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1e3),
+        Some((i, 'm' | 'M')) => (&s[..i], 1e6),
+        Some((i, 'g' | 'G')) => (&s[..i], 1e9),
+        _ => (s, 1.0),
+    };
+    match number.trim().parse::<f64>() {
+        Ok(n) if n >= 0.0 => Ok((n * scale) as usize),
+        _ => Err(format!("invalid memory size `{s}`")),
+    }
+}
+
+fn write_profile(prefix: &str, profile: &Profile, top: usize) {
+    let files = [
+        (format!("{prefix}.folded"), profile.to_collapsed()),
+        (format!("{prefix}.trace.json"), profile.to_chrome_trace()),
+    ];
+    for (path, contents) in files {
+        if let Err(e) = fs::write(&path, contents) {
+            eprintln!("Failed to write {path}: {e}");
+            exit(1);
+        }
+    }
+    eprint!("{}", profile.top(top));
+}
+
+fn bless_snapshots(path: &Path, snapshots: &[Snapshot]) {
+    let snap_path = SnapshotFile::path_for(path);
+    let res = if !snapshots.is_empty() {
+        fs::write(
+            &snap_path,
+            SnapshotFile::from_snapshots(snapshots).to_string(),
+        )
+    } else if snap_path.exists() {
+        fs::remove_file(&snap_path)
+    } else {
+        return;
+    };
+    if let Err(e) = res {
+        eprintln!("Failed to write {}: {e}", snap_path.display());
+        exit(1);
+    }
+    if !snapshots.is_empty() {
+        println!("Blessed {} snapshot(s)", snapshots.len());
+    }
+}
+
+/// Check snapshots against the stored ones, failing the tests of those that do not match
+fn check_snapshots(
+    path: &Path,
+    snapshots: &[Snapshot],
+    filtered: bool,
+    results: &mut Vec<TestResult>,
+) {
+    let snap_path = SnapshotFile::path_for(path);
+    let mut stored = if snap_path.exists() {
+        let stored = fs::read_to_string(&snap_path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| SnapshotFile::parse(&s));
+        match stored {
+            Ok(stored) => stored,
+            Err(e) => {
+                eprintln!("Failed to load {}: {e}", snap_path.display());
+                exit(1);
+            }
+        }
+    } else {
+        SnapshotFile::default()
+    };
+    // Snapshots whose tests were filtered out were not taken
+    if filtered {
+        (stored.snapshots).retain(|(name, _)| snapshots.iter().any(|snap| &snap.name == name));
+    }
+    let failures = stored.check(snapshots);
+    for failure in &failures {
+        let message = format!(
+            "{}\nRun `uiua test --bless` to update {}",
+            failure.message,
+            snap_path.display()
+        );
+        let report = Report::new(ReportKind::Error, message);
+        let test = (results.iter_mut()).find(|res| Some(res.line) == failure.line);
+        if let Some(test) = test {
+            test.failure = Some(report);
+        } else {
+            results.push(TestResult {
+                name: format!("snapshot {}", failure.name),
+                line: failure.line.unwrap_or(0),
+                duration: 0.0,
+                failure: Some(report),
+            });
+        }
+    }
+}
+
 fn working_file_path() -> Result<PathBuf, NoWorkingFile> {
     let main_in_src = PathBuf::from("src/main.ua");
     let main = if main_in_src.exists() {
@@ -428,6 +744,15 @@ fn watch(
     }
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Junit,
+    Json,
+}
+
+const SEED_HELP: &str = "Run deterministically, seeding random numbers and \
+                         using a virtual clock that sleeping advances instantly";
+
 #[derive(Parser)]
 #[clap(version)]
 enum App {
@@ -446,12 +771,66 @@ enum App {
         time_instrs: bool,
         #[clap(long, help = "Run the file in a specific mode")]
         mode: Option<RunMode>,
+        #[clap(long, help = "Cache compiled imports in a directory")]
+        module_cache: Option<PathBuf>,
+        #[clap(long, help = "Don't optimize the compiled code")]
+        no_optimize: bool,
+        #[clap(
+            long,
+            value_name = "LOG",
+            conflicts_with = "replay",
+            help = "Record every interaction with the system to a log file"
+        )]
+        record: Option<PathBuf>,
+        #[clap(
+            long,
+            value_name = "LOG",
+            help = "Replay a log made with --record instead of interacting with the system"
+        )]
+        replay: Option<PathBuf>,
+        #[clap(long, help = SEED_HELP)]
+        seed: Option<u64>,
+        #[clap(
+            long,
+            help = "Profile the run by timing every instruction, which slows it down"
+        )]
+        profile: bool,
+        #[clap(
+            long,
+            value_name = "PREFIX",
+            default_value = "profile",
+            requires = "profile",
+            help = "Write the profile to PREFIX.folded for flamegraph tools \
+                    and PREFIX.trace.json for Chrome's trace viewer"
+        )]
+        profile_prefix: String,
+        #[clap(
+            long,
+            value_name = "N",
+            default_value_t = 10,
+            requires = "profile",
+            help = "The number of entries in each table of the profile summary"
+        )]
+        profile_top: usize,
+        #[clap(
+            long,
+            value_name = "SIZE",
+            value_parser = parse_memory_size,
+            help = "Limit the memory used by arrays, in bytes or with a K, M, or G suffix"
+        )]
+        memory_limit: Option<usize>,
         #[cfg(feature = "audio")]
         #[clap(flatten)]
         audio_options: AudioOptions,
         #[clap(trailing_var_arg = true)]
         args: Vec<String>,
     },
+    #[clap(about = "Compile a file to bytecode that can be run without its source")]
+    Build {
+        path: Option<PathBuf>,
+        #[clap(short, long, help = "The path of the compiled file")]
+        output: Option<PathBuf>,
+    },
     #[clap(about = "Evaluate an expression and print its output")]
     Eval {
         code: String,
@@ -466,6 +845,39 @@ enum App {
         path: Option<PathBuf>,
         #[clap(flatten)]
         formatter_options: FormatterOptions,
+        #[clap(long, help = SEED_HELP)]
+        seed: Option<u64>,
+        #[clap(
+            long,
+            conflicts_with = "filter",
+            help = "Update the stored snapshots instead of checking them"
+        )]
+        bless: bool,
+        #[clap(long, help = "Only run tests whose names contain this text")]
+        filter: Option<String>,
+        #[clap(
+            long,
+            value_name = "FORMAT",
+            help = "Emit a report of the test results"
+        )]
+        report: Option<ReportFormat>,
+        #[clap(
+            long,
+            value_name = "PATH",
+            requires = "report",
+            help = "Write the report to a file instead of stdout"
+        )]
+        report_file: Option<PathBuf>,
+        #[clap(long, help = "Record code coverage and write it to an lcov file")]
+        coverage: bool,
+        #[clap(
+            long,
+            value_name = "PATH",
+            default_value = "lcov.info",
+            requires = "coverage",
+            help = "The lcov file to write coverage to"
+        )]
+        lcov: PathBuf,
     },
     #[clap(about = "Run .ua files in the current directory when they change")]
     Watch {
@@ -482,6 +894,20 @@ enum App {
         #[clap(trailing_var_arg = true)]
         args: Vec<String>,
     },
+    #[clap(about = "Run a file in the interactive debugger")]
+    Debug {
+        path: Option<PathBuf>,
+        #[clap(
+            short = 'b',
+            long = "break",
+            help = "Set a breakpoint at a line, path:line, or binding name"
+        )]
+        breakpoints: Vec<Breakpoint>,
+        #[clap(long, help = "Pause when an error is thrown")]
+        break_on_error: bool,
+        #[clap(trailing_var_arg = true)]
+        args: Vec<String>,
+    },
     #[clap(about = "Format a uiua file or all files in the current directory")]
     Fmt {
         path: Option<PathBuf>,
@@ -491,6 +917,9 @@ enum App {
     #[cfg(feature = "lsp")]
     #[clap(about = "Run the Language Server")]
     Lsp,
+    #[cfg(feature = "dap")]
+    #[clap(about = "Run the Debug Adapter Protocol server")]
+    Dap,
     #[clap(about = "Run very simple REPL")]
     Repl {
         #[clap(flatten)]
@@ -633,6 +1062,154 @@ fn format_multi_files(config: &FormatConfig, stdout: bool) -> Result<(), UiuaErr
     Ok(())
 }
 
+#[derive(Default)]
+struct CliDebugger {
+    last_command: Option<DebugCommand>,
+}
+
+const DEBUG_HELP: &str = "\
+Commands:
+  s, step          Execute one instruction, entering functions
+  n, next          Execute one instruction, stepping over functions
+  o, out           Run until the current function returns
+  c, continue      Run until the next breakpoint
+  q, quit          Stop execution
+  st, stack        Show the value stack
+  t, temps         Show the temporary stacks
+  f, functions     Show the function stack
+  bt, backtrace    Show the call stack
+  b <breakpoint>   Add a breakpoint at a line, path:line, or binding name
+  bl               List breakpoints
+  d <n>            Delete a breakpoint
+  h, help          Show this message
+An empty line repeats the last step command.";
+
+impl DebugHook for CliDebugger {
+    fn pause(&mut self, pause: Pause) -> DebugCommand {
+        let Pause {
+            reason,
+            state,
+            breakpoints,
+        } = pause;
+        match &reason {
+            PauseReason::Step | PauseReason::Requested => {}
+            PauseReason::Breakpoint(i) => println!("{} {}", "Breakpoint".bold(), breakpoints[*i]),
+            PauseReason::Throw(message) | PauseReason::Error(message) => {
+                println!("{} {message}", "Error:".bright_red().bold())
+            }
+        }
+        if let Some(frame) = state.current_frame() {
+            let location = match (frame.path(), frame.line_col()) {
+                (Some(path), Some((line, col))) => format!("{}:{line}:{col}", path.display()),
+                (None, Some((line, col))) => format!("{line}:{col}"),
+                _ => "<builtin>".into(),
+            };
+            let instr = frame.instr().map(|instr| format!("{instr:?}"));
+            println!(
+                "{} {} {}",
+                location.bright_black(),
+                frame.source().unwrap_or_default().bright_white().bold(),
+                instr.unwrap_or_default().bright_black()
+            );
+        }
+        let show_values = |name: &str, values: &[uiua::Value]| {
+            println!("{}", name.bold());
+            if values.is_empty() {
+                println!("  (empty)");
+            }
+            for (i, value) in values.iter().enumerate().rev() {
+                let shown = value.show();
+                let mut lines = shown.lines();
+                println!("  {i:>3}: {}", lines.next().unwrap_or_default());
+                for line in lines {
+                    println!("       {line}");
+                }
+            }
+        };
+        loop {
+            print!("{} ", "(debug)".bright_white().bold());
+            _ = io::stdout().flush();
+            let mut line = String::new();
+            match io::stdin().read_line(&mut line) {
+                Ok(0) | Err(_) => exit(0),
+                Ok(_) => {}
+            }
+            let (command, arg) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+            let arg = arg.trim();
+            let step = match command {
+                "" => self.last_command,
+                "s" | "step" => Some(DebugCommand::StepInstr),
+                "n" | "next" => Some(DebugCommand::StepOver),
+                "o" | "out" => Some(DebugCommand::StepOut),
+                "c" | "continue" => Some(DebugCommand::Continue),
+                "q" | "quit" => exit(0),
+                "st" | "stack" => {
+                    show_values("stack", state.stack());
+                    None
+                }
+                "t" | "temps" => {
+                    show_values("under", state.under_stack());
+                    show_values("inline", state.inline_stack());
+                    None
+                }
+                "f" | "functions" => {
+                    println!("{}", "functions".bold());
+                    for (i, f) in state.function_stack().iter().enumerate().rev() {
+                        println!("  {i:>3}: {f} {}", f.signature());
+                    }
+                    for (i, f) in state.temp_function_stack().iter().enumerate().rev() {
+                        println!("  {:>3}: {f} {} (temp)", i, f.signature());
+                    }
+                    None
+                }
+                "bt" | "backtrace" => {
+                    for frame in state.call_stack().iter().rev() {
+                        println!("  {} at {}", frame.id, frame.span);
+                    }
+                    None
+                }
+                "b" | "break" => {
+                    match arg.parse::<Breakpoint>() {
+                        Ok(bp) => {
+                            println!("Breakpoint {}: {bp}", breakpoints.len());
+                            breakpoints.push(bp);
+                        }
+                        Err(e) => println!("{e}"),
+                    }
+                    None
+                }
+                "bl" => {
+                    for (i, bp) in breakpoints.iter().enumerate() {
+                        println!("  {i}: {bp}");
+                    }
+                    None
+                }
+                "d" | "delete" => {
+                    match arg.parse::<usize>() {
+                        Ok(i) if i < breakpoints.len() => {
+                            println!("Deleted breakpoint {}", breakpoints.remove(i));
+                        }
+                        _ => println!("No breakpoint {arg:?}"),
+                    }
+                    None
+                }
+                "h" | "help" => {
+                    println!("{DEBUG_HELP}");
+                    None
+                }
+                _ => {
+                    println!("Unknown command {command:?}. Type `h` for help.");
+                    None
+                }
+            };
+            if let Some(step) = step {
+                self.last_command = Some(step);
+                return step;
+            }
+        }
+    }
+}
+
 fn repl(mut rt: Uiua, config: FormatConfig) {
     let repl = |rt: &mut Uiua| -> Result<(), UiuaError> {
         print!("{} ", "»".bright_white().bold());
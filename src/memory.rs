@@ -0,0 +1,328 @@
+//! Memory limits
+//!
+//! With [`Uiua::with_memory_limit`], the bytes held by the values on the stacks are
+//! updated after every instruction. Only the values that changed since the last
+//! instruction are measured, so the cost does not grow with the size of values
+//! that are just sitting on the stack. While an instruction runs, the array data it
+//! allocates counts against what is left of the limit. Primitives that can make
+//! very large arrays check their result's size before allocating it. An instruction
+//! that goes over the limit fails with [`UiuaError::MemoryLimit`].
+
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    mem::size_of,
+    sync::{
+        atomic::{self, AtomicU64, AtomicUsize},
+        Arc,
+    },
+};
+
+use crate::{Array, Uiua, UiuaError, UiuaResult, Value};
+
+/// A runtime's memory limit
+#[derive(Debug, Clone)]
+pub(crate) struct MemoryLimit {
+    /// Identifies the runtime, and the threads it spawns, that the limit belongs to
+    id: u64,
+    /// The limit in bytes
+    pub bytes: usize,
+    /// The most bytes that have been held at once, shared between threads
+    pub peak: Arc<AtomicUsize>,
+}
+
+impl MemoryLimit {
+    pub fn new(bytes: usize) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        MemoryLimit {
+            id: NEXT_ID.fetch_add(1, atomic::Ordering::Relaxed),
+            bytes,
+            peak: Default::default(),
+        }
+    }
+}
+
+/// The memory accounting of the instruction a thread is running
+#[derive(Debug, Clone, Copy)]
+struct Allowance {
+    /// The id of the [`MemoryLimit`] being accounted for
+    limit: u64,
+    /// The bytes held by the stacks after the last instruction
+    held: usize,
+    /// The bytes allocated by the current instruction
+    allocated: usize,
+    /// The most bytes that have been in use at once
+    peak: usize,
+}
+
+impl Allowance {
+    fn in_use(&self) -> usize {
+        self.held.saturating_add(self.allocated)
+    }
+}
+
+thread_local! {
+    static ALLOWANCE: Cell<Option<Allowance>> = const { Cell::new(None) };
+}
+
+/// Count an allocation of `count` elements against the memory limit
+///
+/// This is called wherever array data is allocated. It does nothing
+/// if the current thread is not running with a memory limit.
+pub(crate) fn allocate<T>(count: usize) {
+    ALLOWANCE.with(|allowance| {
+        if let Some(mut a) = allowance.get() {
+            a.allocated = (a.allocated).saturating_add(count.saturating_mul(size_of::<T>()));
+            a.peak = a.peak.max(a.in_use());
+            allowance.set(Some(a));
+        }
+    })
+}
+
+/// Format a number of bytes for display
+pub(crate) fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+fn element_size(value: &Value) -> usize {
+    value.generic_ref_shallow(
+        |_| size_of::<f64>(),
+        |_| size_of::<u8>(),
+        |_| size_of::<char>(),
+        |_| size_of::<crate::Boxed>(),
+    )
+}
+
+/// Count the bytes of array data held by a value
+///
+/// Data shared within the value is only counted once.
+fn held_bytes(value: &Value) -> usize {
+    fn array_bytes<T>(arr: &Array<T>, seen: &mut HashSet<(usize, usize)>) -> usize {
+        let key = (arr.data.as_ptr() as usize, arr.data.len());
+        if arr.data.is_empty() || !seen.insert(key) {
+            return 0;
+        }
+        arr.data.len() * size_of::<T>()
+    }
+    fn value_bytes(value: &Value, seen: &mut HashSet<(usize, usize)>) -> usize {
+        match value {
+            Value::Num(arr) => array_bytes(arr, seen),
+            Value::Byte(arr) => array_bytes(arr, seen),
+            Value::Char(arr) => array_bytes(arr, seen),
+            Value::Box(arr) => {
+                let inner: usize = (arr.data.iter()).map(|bx| value_bytes(&bx.0, seen)).sum();
+                array_bytes(arr, seen) + inner
+            }
+        }
+    }
+    value_bytes(value, &mut HashSet::new())
+}
+
+/// The bytes held by the values on a runtime's stacks
+///
+/// This is updated incrementally, measuring only the values that are not
+/// the same as they were at the last update.
+#[derive(Clone, Default)]
+pub(crate) struct HeldValues {
+    /// The values on the stack, under stack, and inline stack
+    stacks: [Vec<HeldValue>; 3],
+    /// The number of values that share each piece of top-level array data
+    shared: HashMap<DataKey, usize>,
+    /// The total bytes held, with shared top-level data counted once
+    total: usize,
+}
+
+/// Identifies a value's top-level array data by its type, address, and length
+type DataKey = (u8, usize, usize);
+
+#[derive(Clone)]
+struct HeldValue {
+    key: DataKey,
+    bytes: usize,
+    /// Box arrays are kept alive so that their contents can't change in place
+    /// without their data being copied, which changes their key
+    _boxes: Option<Value>,
+}
+
+fn data_key(value: &Value) -> DataKey {
+    match value {
+        Value::Num(arr) => (0, arr.data.as_ptr() as usize, arr.data.len()),
+        Value::Byte(arr) => (1, arr.data.as_ptr() as usize, arr.data.len()),
+        Value::Char(arr) => (2, arr.data.as_ptr() as usize, arr.data.len()),
+        Value::Box(arr) => (3, arr.data.as_ptr() as usize, arr.data.len()),
+    }
+}
+
+impl HeldValues {
+    /// Update to match the current stacks and get the total bytes held
+    fn update(&mut self, stacks: [&[Value]; 3]) -> usize {
+        for (held, stack) in self.stacks.iter_mut().zip(stacks) {
+            let unchanged = (held.iter().zip(stack))
+                .take_while(|(held, value)| held.key == data_key(value))
+                .count();
+            for removed in held.drain(unchanged..) {
+                let count = self.shared.get_mut(&removed.key).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    self.shared.remove(&removed.key);
+                    self.total -= removed.bytes;
+                }
+            }
+            for value in &stack[unchanged..] {
+                let key = data_key(value);
+                let bytes = held_bytes(value);
+                let count = self.shared.entry(key).or_default();
+                if *count == 0 {
+                    self.total += bytes;
+                }
+                *count += 1;
+                let boxes = matches!(value, Value::Box(_)).then(|| value.clone());
+                held.push(HeldValue {
+                    key,
+                    bytes,
+                    _boxes: boxes,
+                });
+            }
+        }
+        self.total
+    }
+}
+
+impl Uiua {
+    /// Start accounting for an instruction
+    ///
+    /// Returns the bytes allocated so far by the instruction that
+    /// is already running, if there is one, so that they can be
+    /// restored with [`Uiua::end_instr_memory`].
+    pub(crate) fn begin_instr_memory(&self) -> usize {
+        let Some(limit) = &self.memory_limit else {
+            return 0;
+        };
+        ALLOWANCE.with(|allowance| {
+            // Another runtime may have used this thread before
+            let mut a = (allowance.get())
+                .filter(|a| a.limit == limit.id)
+                .unwrap_or(Allowance {
+                    limit: limit.id,
+                    held: 0,
+                    allocated: 0,
+                    peak: 0,
+                });
+            let outer_allocated = a.allocated;
+            a.allocated = 0;
+            allowance.set(Some(a));
+            outer_allocated
+        })
+    }
+    /// Finish accounting for an instruction
+    ///
+    /// Returns an error if the instruction went over the memory limit.
+    pub(crate) fn end_instr_memory(
+        &mut self,
+        outer_allocated: usize,
+        span: Option<usize>,
+    ) -> UiuaResult {
+        let Some(limit) = &self.memory_limit else {
+            return Ok(());
+        };
+        let held = self
+            .held_values
+            .update([&self.stack, &self.under_stack, &self.inline_stack]);
+        let in_use = ALLOWANCE.with(|allowance| {
+            let mut a = allowance.get()?;
+            let in_use = a.in_use();
+            a.held = held;
+            a.allocated = outer_allocated;
+            a.peak = a.peak.max(held);
+            limit.peak.fetch_max(a.peak, atomic::Ordering::Relaxed);
+            allowance.set(Some(a));
+            Some(in_use.max(held))
+        });
+        if in_use.is_some_and(|in_use| in_use > limit.bytes) {
+            let span = span.map_or_else(|| self.span(), |span| self.get_span(span));
+            return Err(UiuaError::MemoryLimit(limit.bytes, span));
+        }
+        Ok(())
+    }
+    /// Check that allocating `count` elements of type `T` would not go over the memory limit
+    pub(crate) fn check_alloc<T>(&self, count: usize) -> UiuaResult {
+        self.check_memory(count.saturating_mul(size_of::<T>()))
+    }
+    /// Check that allocating `count` elements of the same type as a value would not go over the memory limit
+    pub(crate) fn check_value_alloc(&self, value: &Value, count: usize) -> UiuaResult {
+        self.check_memory(count.saturating_mul(element_size(value)))
+    }
+    fn check_memory(&self, bytes: usize) -> UiuaResult {
+        let Some(limit) = &self.memory_limit else {
+            return Ok(());
+        };
+        let in_use = ALLOWANCE.with(|allowance| allowance.get().map_or(0, |a| a.in_use()));
+        if in_use.saturating_add(bytes) > limit.bytes {
+            return Err(UiuaError::MemoryLimit(limit.bytes, self.span()));
+        }
+        Ok(())
+    }
+    /// Get the most memory that array data on the stacks has used at once, in bytes
+    ///
+    /// Returns `None` if there is no limit set with [`Uiua::with_memory_limit`].
+    pub fn peak_memory(&self) -> Option<usize> {
+        (self.memory_limit.as_ref()).map(|limit| limit.peak.load(atomic::Ordering::Relaxed))
+    }
+    /// Get a report of the peak memory use and the memory limit
+    ///
+    /// Returns `None` if there is no limit set with [`Uiua::with_memory_limit`].
+    pub fn memory_report(&self) -> Option<String> {
+        let limit = self.memory_limit.as_ref()?;
+        let peak = limit.peak.load(atomic::Ordering::Relaxed);
+        Some(format!(
+            "Peak memory: {} of {} ({:.1}%)",
+            format_bytes(peak),
+            format_bytes(limit.bytes),
+            peak as f64 / limit.bytes.max(1) as f64 * 100.0
+        ))
+    }
+}
+
+#[test]
+fn memory_limit() {
+    let mut env = Uiua::with_native_sys().with_memory_limit(1_000_000);
+    let err = env.load_str("↯1e9 0").unwrap_err();
+    assert!(err.message().contains("Memory limit of 1.0 MB exceeded"));
+
+    let mut env = Uiua::with_native_sys().with_memory_limit(1_000_000);
+    env.load_str("⍣(⇡1e8)(\"caught\";)").unwrap();
+    assert_eq!(env.pop_string().unwrap(), "caught");
+
+    let mut env = Uiua::with_native_sys().with_memory_limit(1_000_000);
+    env.load_str("⧻ ⇡1000").unwrap();
+    let peak = env.peak_memory().unwrap();
+    assert!((8000..16000).contains(&peak), "peak was {peak}");
+    assert!(env.memory_report().unwrap().starts_with("Peak memory: 8."));
+
+    let mut env = Uiua::with_native_sys().with_memory_limit(1_000_000);
+    assert!(env.load_str("⍥(⊂.)20 [1]").is_err());
+
+    // Popped values are credited and shared data is counted once
+    let mut env = Uiua::with_native_sys().with_memory_limit(1_000_000);
+    env.load_str("⍥(;⇡1e4)50 0\n..⇡1e4").unwrap();
+    let peak = env.peak_memory().unwrap();
+    assert!((80_000..200_000).contains(&peak), "peak was {peak}");
+
+    // Runtimes on the same thread don't share their accounting
+    let mut big = Uiua::with_native_sys().with_memory_limit(10_000_000);
+    big.load_str("↯5e5 0").unwrap();
+    let mut small = Uiua::with_native_sys().with_memory_limit(10_000_000);
+    small.load_str("⇡10").unwrap();
+    assert!(big.peak_memory().unwrap() >= 4_000_000);
+    assert!(small.peak_memory().unwrap() < 1000);
+}
@@ -1085,9 +1085,33 @@ primitive!(
     /// [invert][sine] gives the arcsine.
     /// ex: ⍘○ 1
     ///
+    /// [invert][join] splits off the first row of an array.
+    /// ex: ⍘⊂ [1 2 3 4]
+    ///
+    /// [invert][parse] formats a number as a string.
+    /// ex: ⍘parse 17
+    ///
     /// While more inverses exists, most of them are not useful on their own.
     /// They are usually used within [under].
     ([1], Invert, OtherModifier, ("invert", '⍘')),
+    /// Get the derivative of a function
+    ///
+    /// The derivative is taken with respect to the function's first argument.
+    /// Any other arguments are treated as constants.
+    ///
+    /// ex: ∂(×.) 5
+    /// ex: ∂○ 0
+    /// ex: ∂(ⁿ2) [1 2 3]
+    /// ex: ∂(×) 2 3
+    ///
+    /// For an array argument, every element is differentiated at once.
+    /// With a pervasive function, this gives the derivative at each element.
+    /// ex: ∂(ₙe) [1 2 4]
+    ///
+    /// [derivative] works with arithmetic, [sine], [reduce][add], [table], [rows], and stack manipulation.
+    /// Other functions cannot be differentiated.
+    /// ex! ∂⇌ [1 2 3]
+    ([1], Derivative, OtherModifier, ("derivative", '∂')),
     /// Discard the top stack value then call a function
     ///
     /// See the [Advanced Stack Manipulation Tutorial](/docs/advancedstack) for a more complete understanding of why [gap] is useful.
@@ -1207,8 +1231,13 @@ primitive!(
     /// ex: ⍜⊏'×10 1_3 1_2_3_4_5
     /// Although, [under][select] only works if the indices are unique.
     /// ex! ⍜⊏'×10 1_3_3 1_2_3_4_5
-    /// [under][keep] works as long as the counts list is boolean.
+    /// [under][keep] works as long as copies of the same row are still the same.
     /// ex: ⍜▽'×10 =0◿3.⇡10
+    /// ex: ⍜▽'×10 [2 0 1] [1 2 3]
+    /// [under][join] splits the joined array back into the parts that were joined.
+    /// ex: ⍜(⊂1_2)⇌ 3_4_5
+    /// [under][reshape] reshapes the array back to its original shape.
+    /// ex: ⍜(↯2_3)≡⇌ ⇡6
     ///
     /// If `g` takes more than 1 argument, keep in mind that `f` will be called on the stack as it is when the full under expression begins.
     /// This means you may have to flip the arguments to `g`.
@@ -1471,6 +1500,22 @@ primitive!(
     ///
     /// Errors thrown by [assert] can be caught with [try].
     (2(0), Assert, Control, ("assert", '⍤')),
+    /// Throw an error describing how two arrays differ if they do not [match]
+    ///
+    /// Expects the expected value and then the actual value.
+    /// The error shows both arrays along with where they first differ, or how their shapes or types differ.
+    ///
+    /// ex: expect [2 3 4] +1 [1 2 3]
+    /// ex! expect [2 3 4] +1 [1 2 4]
+    /// ex! expect [2 3 4] +1 [1 2]
+    /// ex! expect "abc" [1 2 3]
+    ///
+    /// Use [fill] to allow numbers to differ by up to the fill value.
+    /// ex! expect 0.3 +0.1 0.2
+    /// ex: ⬚0.000001expect 0.3 +0.1 0.2
+    ///
+    /// Errors thrown by [expect] can be caught with [try].
+    (2(0), Expect, Control, "expect"),
     /// Spawn a thread
     ///
     /// Expects a function.
@@ -1490,6 +1535,17 @@ primitive!(
     /// ex: ↯3_3⇡9
     ///   : wait≡spawn/+.
     ([1], Spawn, OtherModifier, "spawn"),
+    /// Cache a function's outputs for the arguments it is called with
+    ///
+    /// When a [memo]ized function is called with the same arguments as before, it pushes the same outputs without being run again.
+    /// ex: F ← memo(/+⇡)
+    ///   : [F 1e6 F 1e6]
+    /// The cache lasts for the whole program and is shared with [spawn]ed threads.
+    ///
+    /// Because the function is not run again, impure functions are only run once for each set of arguments.
+    /// ex: F ← memo(+⚂)
+    ///   : =F 1 F 1
+    ([1], Memo, OtherModifier, "memo"),
     /// Wait for a thread to finish and push its results to the stack
     ///
     /// The argument must be a thread id returned by [spawn].
@@ -1620,6 +1676,57 @@ primitive!(
     /// [under][now] can be used to time a function.
     /// ex: ⍜now(5&sl1)
     (0, Now, Misc, "now"),
+    /// Create a map from a table of keys and values
+    ///
+    /// A map is an array of values with a key for each row. Looking up a key does not search the array.
+    /// The table must be a box array with 2 columns. The first column holds the keys and the second holds the values.
+    /// ex: map [{1 "one"} {2 "two"} {3 "three"}]
+    /// Keys or values that do not fit together in one array are boxed.
+    /// ex: map [{"a" 1} {"bc" 2}]
+    ///
+    /// Other functions treat a map as an array of its values.
+    /// ex: ×10 map [{"a" 1} {"bc" 2}]
+    /// An empty array is an empty map.
+    /// ex: insert 1 2 map []
+    ///
+    /// See also: [insert], [get], [has], [remove], [keys], [values]
+    (1, Map, Misc, "map"),
+    /// Insert a key and value into a map
+    ///
+    /// ex: insert 3 "c" map [{1 "a"} {2 "b"}]
+    /// If the key is already in the map, its value is replaced.
+    /// ex: insert 1 "z" map [{1 "a"} {2 "b"}]
+    /// An empty array is an empty map.
+    /// ex: insert "y" 2 insert "x" 1 []
+    (3, Insert, Misc, "insert"),
+    /// Get the value of a key in a map
+    ///
+    /// ex: get 2 map [{1 "a"} {2 "b"}]
+    /// If the key is not in the map, an error is thrown.
+    /// ex! get 5 map [{1 "a"} {2 "b"}]
+    /// [fill] can be used to get a default value instead.
+    /// ex: ⬚@- get 5 map [{1 "a"} {2 "b"}]
+    (2, Get, Misc, "get"),
+    /// Check if a map has a key
+    ///
+    /// ex: has 2 map [{1 "a"} {2 "b"}]
+    /// ex: has 5 map [{1 "a"} {2 "b"}]
+    (2, Has, Misc, "has"),
+    /// Remove a key and its value from a map
+    ///
+    /// ex: remove 1 map [{1 "a"} {2 "b"} {3 "c"}]
+    /// If the key is not in the map, the map is unchanged.
+    /// ex: remove 5 map [{1 "a"} {2 "b"}]
+    (2, Remove, Misc, "remove"),
+    /// Get the keys of a map
+    ///
+    /// ex: keys map [{1 "a"} {2 "b"}]
+    (1, Keys, Misc, "keys"),
+    /// Get the values of a map
+    ///
+    /// The result is an array without keys.
+    /// ex: values map [{1 "a"} {2 "b"}]
+    (1, Values, Misc, "values"),
     /// The number of radians in a quarter circle
     ///
     /// Equivalent to `divide``2``pi` or `divide``4``tau`
@@ -1731,10 +1838,13 @@ impl_primitive!(
     (3, Untake),
     (3, Undrop),
     (3, Unkeep),
+    (3, Unreshape),
     (3[1], Unpartition),
     (3[1], Ungroup),
     (1, InvUtf),
     (1, InvTrace),
+    (1, InvParse),
+    (3(2), Unjoin),
     // Optimizations
     (1, Cos),
     (1, Last),
@@ -1743,4 +1853,27 @@ impl_primitive!(
     (1, LastMinIndex),
     (1, LastMaxIndex),
     (1, FirstWhere),
+    (1, SortUp),
+    (1, SortDown),
+    (1, RangeSum),
+    (2[1], RowsWindows),
+    (2[1], ReduceWindows),
+    // Derivatives
+    (1, DualSeed),
+    (1, DualConst),
+    (1, DualTangent),
+    (2, DualAdd),
+    (2, DualSub),
+    (2, DualMul),
+    (2, DualDiv),
+    (2, DualPow),
+    (2, DualLog),
+    (1, DualNeg),
+    (1, DualAbs),
+    (1, DualSqrt),
+    (1, DualSin),
+    (1, DualCos),
+    (2[1], DualTable),
+    (1[1], DualReduce),
+    (0[1], DualRows),
 );
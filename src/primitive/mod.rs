@@ -27,7 +27,7 @@ use rand::prelude::*;
 use regex::Regex;
 
 use crate::{
-    algorithm::{fork, loops, reduce, table, zip},
+    algorithm::{derivative, expect, fork, loops, memo, reduce, table, windows, zip},
     array::Array,
     boxed::Boxed,
     lex::AsciiToken,
@@ -134,16 +134,39 @@ impl fmt::Display for ImplPrimitive {
             InverseBits => write!(f, "⍘{Bits}"),
             InvTrace => write!(f, "⍘{Trace}"),
             InvWhere => write!(f, "⍘{Where}"),
+            InvParse => write!(f, "⍘{Parse}"),
+            Unjoin => write!(f, "⍘{Join}"),
             Uncouple => write!(f, "⍘{Couple}"),
             Untake => write!(f, "⍘{Take}"),
             Undrop => write!(f, "⍘{Drop}"),
             Unselect => write!(f, "⍘{Select}"),
+            Unreshape => write!(f, "⍘{Reshape}"),
             Unpick => write!(f, "⍘{Pick}"),
             Unpartition => write!(f, "⍘{Partition}"),
             Cos => write!(f, "{Sin}{Add}{Eta}"),
             Asin => write!(f, "{Invert}{Sin}"),
             Acos => write!(f, "{Invert}{Cos}"),
             Last => write!(f, "{First}{Reverse}"),
+            SortUp => write!(f, "{Select}{Rise}{Dup}"),
+            SortDown => write!(f, "{Select}{Fall}{Dup}"),
+            RangeSum => write!(f, "{Reduce}{Add}{Range}"),
+            RowsWindows => write!(f, "{Rows}{Windows}"),
+            ReduceWindows => write!(f, "{Reduce}{Windows}"),
+            DualSeed | DualConst | DualTangent => write!(f, "{Derivative}"),
+            DualAdd => write!(f, "{Derivative}{Add}"),
+            DualSub => write!(f, "{Derivative}{Sub}"),
+            DualMul => write!(f, "{Derivative}{Mul}"),
+            DualDiv => write!(f, "{Derivative}{Div}"),
+            DualPow => write!(f, "{Derivative}{Pow}"),
+            DualLog => write!(f, "{Derivative}{Log}"),
+            DualNeg => write!(f, "{Derivative}{Neg}"),
+            DualAbs => write!(f, "{Derivative}{Abs}"),
+            DualSqrt => write!(f, "{Derivative}{Sqrt}"),
+            DualSin => write!(f, "{Derivative}{Sin}"),
+            DualCos => write!(f, "{Derivative}{Cos}"),
+            DualTable => write!(f, "{Derivative}{Table}"),
+            DualReduce => write!(f, "{Derivative}{Reduce}"),
+            DualRows => write!(f, "{Derivative}{Rows}"),
             _ => write!(f, "{self:?}"),
         }
     }
@@ -477,6 +500,11 @@ impl Primitive {
                 let inv_f = f.invert("", env)?;
                 env.call(inv_f)?;
             }
+            Primitive::Derivative => {
+                let f = env.pop_function()?;
+                let derivative = f.derivative(env)?;
+                env.call(derivative)?;
+            }
             Primitive::Under => {
                 let f = env.pop_function()?;
                 let g = env.pop_function()?;
@@ -513,6 +541,9 @@ impl Primitive {
                 let backup = env.clone_stack_top(f_args);
                 let bottom = env.stack_size().saturating_sub(f_args);
                 if let Err(e) = env.call(f) {
+                    if e.is_interrupted() {
+                        return Err(e);
+                    }
                     env.truncate_stack(bottom);
                     env.backend.save_error_color(&e);
                     env.push(e.value());
@@ -529,11 +560,10 @@ impl Primitive {
                     return Err(UiuaError::Throw(msg.into(), env.span().clone()));
                 }
             }
+            Primitive::Expect => expect::expect(env)?,
             Primitive::Rand => {
-                thread_local! {
-                    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::seed_from_u64(instant::now().to_bits()));
-                }
-                env.push(RNG.with(|rng| rng.borrow_mut().gen::<f64>()));
+                let n = env.rand();
+                env.push(n);
             }
             Primitive::Gen => {
                 let seed = env.pop(1)?;
@@ -564,6 +594,7 @@ impl Primitive {
                     Value::Box(_) => 2,
                 });
             }
+            Primitive::Memo => memo::memo(env)?,
             Primitive::Spawn => {
                 let f = env.pop_function()?;
                 env.spawn(f.signature().args, |env| env.call(f))?;
@@ -585,7 +616,25 @@ impl Primitive {
                 let id = env.pop(1)?;
                 env.try_recv(id)?;
             }
-            Primitive::Now => env.push(instant::now() / 1000.0),
+            Primitive::Now => env.push(env.now()),
+            Primitive::Map => env.monadic_env(Value::map)?,
+            Primitive::Insert => {
+                let key = env.pop(1)?;
+                let value = env.pop(2)?;
+                let mut map = env.pop(3)?;
+                map.insert(key, value, env)?;
+                env.push(map);
+            }
+            Primitive::Get => env.dyadic_oo_env(|key, map, env| map.get(key, env))?,
+            Primitive::Has => env.dyadic_oo_env(|key, map, env| map.has_key(key, env))?,
+            Primitive::Remove => {
+                let key = env.pop(1)?;
+                let mut map = env.pop(2)?;
+                map.remove(key, env)?;
+                env.push(map);
+            }
+            Primitive::Keys => env.monadic_ref_env(Value::keys)?,
+            Primitive::Values => env.monadic_env(Value::values)?,
             Primitive::Trace => trace(env, false)?,
             Primitive::Dump => dump(env)?,
             Primitive::Sys(io) => io.run(env)?,
@@ -655,6 +704,12 @@ impl ImplPrimitive {
                 let from = env.pop(3)?;
                 env.push(from.undrop(index, into, env)?);
             }
+            ImplPrimitive::Unreshape => {
+                let into = env.pop(1)?;
+                let shape = env.pop(2)?;
+                let from = env.pop(3)?;
+                env.push(from.unreshape(&shape, into, env)?);
+            }
             ImplPrimitive::Uncouple => {
                 let coupled = env.pop(1)?;
                 let (a, b) = coupled.uncouple(env)?;
@@ -675,6 +730,15 @@ impl ImplPrimitive {
             }
             ImplPrimitive::InvWhere => env.monadic_ref_env(Value::inverse_where)?,
             ImplPrimitive::InvUtf => env.monadic_ref_env(Value::inv_utf8)?,
+            ImplPrimitive::InvParse => env.monadic_ref_env(Value::inv_parse)?,
+            ImplPrimitive::Unjoin => {
+                let a_shape = env.pop(1)?;
+                let b_shape = env.pop(2)?;
+                let joined = env.pop(3)?;
+                let (a, b) = joined.unjoin(&a_shape, &b_shape, env)?;
+                env.push(b);
+                env.push(a);
+            }
             ImplPrimitive::InverseBits => env.monadic_ref_env(Value::inverse_bits)?,
             ImplPrimitive::Unpartition => loops::unpartition(env)?,
             ImplPrimitive::Ungroup => loops::ungroup(env)?,
@@ -687,6 +751,29 @@ impl ImplPrimitive {
             ImplPrimitive::LastMinIndex => env.monadic_ref_env(Value::last_min_index)?,
             ImplPrimitive::LastMaxIndex => env.monadic_ref_env(Value::last_max_index)?,
             ImplPrimitive::FirstWhere => env.monadic_ref_env(Value::first_where)?,
+            ImplPrimitive::SortUp => env.monadic_env(Value::sort_up)?,
+            ImplPrimitive::SortDown => env.monadic_env(Value::sort_down)?,
+            ImplPrimitive::RangeSum => reduce::range_sum(env)?,
+            ImplPrimitive::RowsWindows => windows::rows_windows(env)?,
+            ImplPrimitive::ReduceWindows => windows::reduce_windows(env)?,
+            // Derivatives
+            ImplPrimitive::DualSeed => env.monadic_env(derivative::seed)?,
+            ImplPrimitive::DualConst => env.monadic_env(derivative::constant)?,
+            ImplPrimitive::DualTangent => env.monadic_env(derivative::tangent)?,
+            ImplPrimitive::DualAdd => env.dyadic_oo_env(derivative::add)?,
+            ImplPrimitive::DualSub => env.dyadic_oo_env(derivative::sub)?,
+            ImplPrimitive::DualMul => env.dyadic_oo_env(derivative::mul)?,
+            ImplPrimitive::DualDiv => env.dyadic_oo_env(derivative::div)?,
+            ImplPrimitive::DualPow => env.dyadic_oo_env(derivative::pow)?,
+            ImplPrimitive::DualLog => env.dyadic_oo_env(derivative::log)?,
+            ImplPrimitive::DualNeg => env.monadic_env(derivative::neg)?,
+            ImplPrimitive::DualAbs => env.monadic_env(derivative::abs)?,
+            ImplPrimitive::DualSqrt => env.monadic_env(derivative::sqrt)?,
+            ImplPrimitive::DualSin => env.monadic_env(derivative::sin)?,
+            ImplPrimitive::DualCos => env.monadic_env(derivative::cos)?,
+            ImplPrimitive::DualTable => derivative::dual_table(env)?,
+            ImplPrimitive::DualReduce => derivative::dual_reduce(env)?,
+            ImplPrimitive::DualRows => derivative::dual_rows(env)?,
         }
         Ok(())
     }
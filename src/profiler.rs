@@ -0,0 +1,532 @@
+//! Execution profiling
+//!
+//! With [`Uiua::with_profiling`], the time spent on every instruction is attributed
+//! to the stack of functions that were being called, to the primitive being run,
+//! and to the instruction's source line. [`Uiua::profile`] collects these times.
+//! Calls to small functions are not inlined while profiling so that they keep
+//! their place in the call stack.
+//!
+//! The profiler instruments execution rather than sampling it. The clock is read
+//! and the shared profile is locked before every instruction, so profiled programs
+//! run much slower, and this overhead inflates the times of cheap instructions
+//! relative to expensive ones.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use parking_lot::Mutex;
+use serde_json::json;
+
+use crate::{function::FunctionId, lex::Span, Primitive, Uiua};
+
+/// The maximum number of function calls that are kept for the trace
+const MAX_TRACE_EVENTS: usize = 1_000_000;
+
+/// Times collected by all of the threads of a profiled program
+#[derive(Default)]
+pub(crate) struct ProfileData {
+    /// The tree of call stacks
+    ///
+    /// Node 0 is the root, which is not a function.
+    nodes: Vec<StackNode>,
+    /// The time spent and number of calls of each primitive
+    primitives: HashMap<Primitive, (u64, f64)>,
+    /// The time spent on the instructions with each span index
+    spans: HashMap<usize, f64>,
+    /// Function calls, in the order they finished
+    events: Vec<Event>,
+    /// The time at which profiling started
+    start: f64,
+    /// The number of threads that have been profiled
+    threads: usize,
+}
+
+struct StackNode {
+    id: FunctionId,
+    parent: usize,
+    children: HashMap<FunctionId, usize>,
+    calls: u64,
+    /// Time spent in this function itself, not including the functions it called
+    time: f64,
+}
+
+struct Event {
+    node: usize,
+    thread: usize,
+    start: f64,
+    end: f64,
+}
+
+/// What a thread is currently spending time on
+#[derive(Clone, Copy)]
+struct Sample {
+    node: usize,
+    prim: Option<Primitive>,
+    span: Option<usize>,
+}
+
+struct Frame {
+    node: usize,
+    start: f64,
+    /// What the calling function was doing when this one was called
+    caller: Option<Sample>,
+}
+
+/// A thread's view of the profile
+pub(crate) struct Profiler {
+    data: Arc<Mutex<ProfileData>>,
+    thread: usize,
+    frames: Vec<Frame>,
+    current: Option<Sample>,
+    last: f64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        let data = ProfileData {
+            nodes: vec![StackNode {
+                id: FunctionId::Unnamed,
+                parent: 0,
+                children: HashMap::new(),
+                calls: 0,
+                time: 0.0,
+            }],
+            start: instant::now(),
+            threads: 1,
+            ..ProfileData::default()
+        };
+        Profiler {
+            data: Arc::new(Mutex::new(data)),
+            thread: 0,
+            frames: Vec::new(),
+            current: None,
+            last: 0.0,
+        }
+    }
+    /// Make a profiler for a spawned thread
+    ///
+    /// The thread's stacks start at the function that spawned it.
+    pub fn spawn(&self) -> Self {
+        let thread = {
+            let mut data = self.data.lock();
+            data.threads += 1;
+            data.threads - 1
+        };
+        let node = self.frames.last().map_or(0, |frame| frame.node);
+        Profiler {
+            data: self.data.clone(),
+            thread,
+            frames: vec![Frame {
+                node,
+                start: instant::now(),
+                caller: None,
+            }],
+            current: None,
+            last: 0.0,
+        }
+    }
+    /// Attribute the time since the last event to what is currently being done
+    fn tick(current: Option<Sample>, last: &mut f64, data: &mut ProfileData) -> f64 {
+        let now = instant::now();
+        if let Some(sample) = current {
+            let elapsed = now - *last;
+            data.nodes[sample.node].time += elapsed;
+            if let Some(prim) = sample.prim {
+                data.primitives.entry(prim).or_default().1 += elapsed;
+            }
+            if let Some(span) = sample.span {
+                *data.spans.entry(span).or_default() += elapsed;
+            }
+        }
+        *last = now;
+        now
+    }
+    /// Start a function call
+    pub fn enter(&mut self, id: &FunctionId) {
+        let mut data = self.data.lock();
+        let now = Self::tick(self.current, &mut self.last, &mut data);
+        let parent = self.frames.last().map_or(0, |frame| frame.node);
+        let node = if let FunctionId::Unnamed = id {
+            parent
+        } else if let Some(&node) = data.nodes[parent].children.get(id) {
+            node
+        } else {
+            let node = data.nodes.len();
+            data.nodes.push(StackNode {
+                id: id.clone(),
+                parent,
+                children: HashMap::new(),
+                calls: 0,
+                time: 0.0,
+            });
+            data.nodes[parent].children.insert(id.clone(), node);
+            node
+        };
+        if node != parent {
+            data.nodes[node].calls += 1;
+        }
+        self.frames.push(Frame {
+            node,
+            start: now,
+            caller: self.current,
+        });
+        self.current = Some(Sample {
+            node,
+            prim: None,
+            span: None,
+        });
+    }
+    /// Finish the current function call
+    pub fn exit(&mut self) {
+        let mut data = self.data.lock();
+        let now = Self::tick(self.current, &mut self.last, &mut data);
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        let parent = self.frames.last().map_or(0, |frame| frame.node);
+        if frame.node != parent && data.events.len() < MAX_TRACE_EVENTS {
+            let (start, end) = (frame.start - data.start, now - data.start);
+            data.events.push(Event {
+                node: frame.node,
+                thread: self.thread,
+                start,
+                end,
+            });
+        }
+        self.current = frame.caller;
+    }
+    /// Start an instruction
+    pub fn instr(&mut self, prim: Option<Primitive>, span: Option<usize>) {
+        let mut data = self.data.lock();
+        Self::tick(self.current, &mut self.last, &mut data);
+        if let Some(prim) = prim {
+            data.primitives.entry(prim).or_default().0 += 1;
+        }
+        let node = self.frames.last().map_or(0, |frame| frame.node);
+        self.current = Some(Sample { node, prim, span });
+    }
+}
+
+impl Clone for Profiler {
+    fn clone(&self) -> Self {
+        Profiler {
+            data: self.data.clone(),
+            thread: self.thread,
+            frames: Vec::new(),
+            current: None,
+            last: 0.0,
+        }
+    }
+}
+
+/// The times collected while profiling
+///
+/// All times are in seconds.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    /// The time spent in each call stack, not including the functions called from it
+    pub stacks: Vec<StackTime>,
+    /// The time spent in each function, from most to least total time
+    pub functions: Vec<FunctionTime>,
+    /// The time spent running each primitive, from most to least time
+    pub primitives: Vec<PrimitiveTime>,
+    /// The time spent on each source line, from most to least time
+    pub lines: Vec<LineTime>,
+    /// The function calls that were made, in the order they finished
+    ///
+    /// Only the first million calls are kept.
+    pub events: Vec<TraceEvent>,
+}
+
+/// The time spent in a call stack
+#[derive(Debug, Clone)]
+pub struct StackTime {
+    /// The names of the functions in the stack, from outermost to innermost
+    pub frames: Vec<String>,
+    /// The time spent in the innermost function
+    pub time: f64,
+}
+
+/// The time spent in a function
+#[derive(Debug, Clone)]
+pub struct FunctionTime {
+    /// The function's name
+    pub name: String,
+    /// How many times the function was called
+    pub calls: u64,
+    /// The time spent in the function itself
+    pub self_time: f64,
+    /// The time spent in the function and the functions it called
+    pub total_time: f64,
+}
+
+/// The time spent running a primitive
+#[derive(Debug, Clone)]
+pub struct PrimitiveTime {
+    /// The primitive
+    pub prim: Primitive,
+    /// How many times the primitive was run
+    pub calls: u64,
+    /// The time spent running the primitive, not including the functions it called
+    pub time: f64,
+}
+
+/// The time spent on a source line
+#[derive(Debug, Clone)]
+pub struct LineTime {
+    /// The file's path, or `None` for code that was not loaded from a file
+    pub path: Option<PathBuf>,
+    /// The line number
+    pub line: usize,
+    /// The time spent on the line's instructions, not including the functions they called
+    pub time: f64,
+}
+
+/// A function call
+#[derive(Debug, Clone)]
+pub struct TraceEvent {
+    /// The function's name
+    pub name: String,
+    /// The thread the function was called on
+    pub thread: usize,
+    /// The time the call started, relative to the start of profiling
+    pub start: f64,
+    /// How long the call took
+    pub duration: f64,
+}
+
+fn function_name(id: &FunctionId) -> String {
+    match id {
+        FunctionId::Named(name) => name.to_string(),
+        FunctionId::Anonymous(span) => format!("({span})"),
+        id => id.to_string(),
+    }
+}
+
+impl Uiua {
+    /// Get the times collected while profiling
+    ///
+    /// Returns `None` if profiling was not enabled with [`Uiua::with_profiling`].
+    pub fn profile(&self) -> Option<Profile> {
+        let data = self.profiler.as_ref()?.data.lock();
+        let spans = self.spans.lock();
+        let names: Vec<String> = (data.nodes.iter())
+            .map(|node| function_name(&node.id))
+            .collect();
+        let path_of = |mut node: usize| {
+            let mut path = Vec::new();
+            while node != 0 {
+                path.push(node);
+                node = data.nodes[node].parent;
+            }
+            path.reverse();
+            path
+        };
+        // Stacks and functions
+        let mut stacks = Vec::new();
+        let mut functions: BTreeMap<&str, FunctionTime> = BTreeMap::new();
+        for (i, node) in data.nodes.iter().enumerate().skip(1) {
+            let path = path_of(i);
+            let entry = functions.entry(&names[i]).or_insert_with(|| FunctionTime {
+                name: names[i].clone(),
+                calls: 0,
+                self_time: 0.0,
+                total_time: 0.0,
+            });
+            entry.calls += node.calls;
+            entry.self_time += node.time / 1000.0;
+            // Recursive functions only count toward their total time once
+            let mut counted: Vec<&str> = Vec::new();
+            for &n in &path {
+                if !counted.contains(&names[n].as_str()) {
+                    counted.push(&names[n]);
+                    functions.get_mut(names[n].as_str()).unwrap().total_time += node.time / 1000.0;
+                }
+            }
+            if node.time > 0.0 {
+                stacks.push(StackTime {
+                    frames: path.iter().map(|&n| names[n].clone()).collect(),
+                    time: node.time / 1000.0,
+                });
+            }
+        }
+        let mut functions: Vec<FunctionTime> = functions.into_values().collect();
+        functions.sort_by(|a, b| b.total_time.total_cmp(&a.total_time));
+        // Primitives
+        let mut primitives: Vec<PrimitiveTime> = (data.primitives.iter())
+            .map(|(&prim, &(calls, time))| PrimitiveTime {
+                prim,
+                calls,
+                time: time / 1000.0,
+            })
+            .collect();
+        primitives.sort_by(|a, b| b.time.total_cmp(&a.time));
+        // Lines
+        let mut lines: HashMap<(Option<PathBuf>, usize), f64> = HashMap::new();
+        for (&span, &time) in &data.spans {
+            if let Some(Span::Code(span)) = spans.get(span) {
+                let path = span.path.as_ref().map(|path| path.to_path_buf());
+                *lines.entry((path, span.start.line)).or_default() += time / 1000.0;
+            }
+        }
+        let mut lines: Vec<LineTime> = (lines.into_iter())
+            .map(|((path, line), time)| LineTime { path, line, time })
+            .collect();
+        lines.sort_by(|a, b| (b.time.total_cmp(&a.time)).then(a.line.cmp(&b.line)));
+        // Events
+        let events = (data.events.iter())
+            .map(|event| TraceEvent {
+                name: names[event.node].clone(),
+                thread: event.thread,
+                start: event.start / 1000.0,
+                duration: (event.end - event.start) / 1000.0,
+            })
+            .collect();
+        Some(Profile {
+            stacks,
+            functions,
+            primitives,
+            lines,
+            events,
+        })
+    }
+}
+
+impl Profile {
+    /// Format the call stacks in the collapsed stack format used by flamegraph tools
+    ///
+    /// Each line is a stack of function names separated by `;`, followed by
+    /// the time spent in it in microseconds.
+    pub fn to_collapsed(&self) -> String {
+        let mut collapsed = String::new();
+        for stack in &self.stacks {
+            let micros = (stack.time * 1e6).round() as u64;
+            if micros > 0 {
+                _ = writeln!(collapsed, "{} {micros}", stack.frames.join(";"));
+            }
+        }
+        collapsed
+    }
+    /// Format the function calls in the Chrome trace event format
+    ///
+    /// This can be opened in `chrome://tracing` or [Perfetto](https://ui.perfetto.dev).
+    pub fn to_chrome_trace(&self) -> String {
+        let events: Vec<_> = (self.events.iter())
+            .map(|event| {
+                json!({
+                    "name": event.name,
+                    "cat": "function",
+                    "ph": "X",
+                    "ts": event.start * 1e6,
+                    "dur": event.duration * 1e6,
+                    "pid": 1,
+                    "tid": event.thread,
+                })
+            })
+            .collect();
+        let trace = json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms",
+        });
+        serde_json::to_string(&trace).unwrap()
+    }
+    /// Make a table of the functions, primitives, and lines that took the most time
+    pub fn top(&self, n: usize) -> String {
+        fn ms(time: f64) -> String {
+            format!("{:.3}ms", time * 1000.0)
+        }
+        let mut rows: Vec<[String; 4]> = Vec::new();
+        let mut sections = Vec::new();
+        sections.push(rows.len());
+        rows.push([
+            "Function".into(),
+            "calls".into(),
+            "self".into(),
+            "total".into(),
+        ]);
+        for f in self.functions.iter().take(n) {
+            rows.push([
+                f.name.clone(),
+                f.calls.to_string(),
+                ms(f.self_time),
+                ms(f.total_time),
+            ]);
+        }
+        sections.push(rows.len());
+        rows.push(["Primitive".into(), "calls".into(), "time".into(), "".into()]);
+        for p in self.primitives.iter().take(n) {
+            let name = match p.prim.glyph() {
+                Some(glyph) => format!("{glyph} {}", p.prim.name()),
+                None => p.prim.to_string(),
+            };
+            rows.push([name, p.calls.to_string(), ms(p.time), String::new()]);
+        }
+        sections.push(rows.len());
+        rows.push(["Line".into(), "".into(), "time".into(), "".into()]);
+        for line in self.lines.iter().take(n) {
+            let location = match &line.path {
+                Some(path) => format!("{}:{}", path.display(), line.line),
+                None => format!("line {}", line.line),
+            };
+            rows.push([location, String::new(), ms(line.time), String::new()]);
+        }
+        let mut widths = [0; 4];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let mut table = String::new();
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 && sections.contains(&i) {
+                table.push('\n');
+            }
+            let indent = if sections.contains(&i) { "" } else { "  " };
+            let name_width = widths[0] + 2 - indent.len();
+            let mut line = format!("{indent}{:name_width$}", row[0]);
+            for (cell, width) in row.iter().zip(widths).skip(1) {
+                _ = write!(line, "  {cell:>width$}");
+            }
+            table.push_str(line.trim_end());
+            table.push('\n');
+        }
+        table
+    }
+}
+
+#[test]
+fn profile() {
+    let code = "\
+Sq ← ×.
+Sum ← /+ ≡Sq
+Sum ⇡10
+Sq 3";
+    let mut env = Uiua::with_native_sys().with_profiling(true);
+    env.load_str(code).unwrap();
+    let profile = env.profile().unwrap();
+    let calls = |name: &str| {
+        (profile.functions.iter())
+            .find(|f| f.name == name)
+            .map(|f| f.calls)
+    };
+    assert_eq!(calls("Sum"), Some(1));
+    assert_eq!(calls("Sq"), Some(11));
+    assert!(profile.primitives.iter().any(|p| p.prim == Primitive::Rows));
+    assert!((profile.events.iter()).any(|event| event.name == "Sq" && event.thread == 0));
+    let stacks: Vec<_> = (profile.stacks.iter())
+        .map(|stack| stack.frames.join(";"))
+        .collect();
+    assert!(stacks.contains(&"main;Sum;Sq".into()));
+    assert!(stacks.contains(&"main;Sq".into()));
+    let lines: Vec<_> = profile.lines.iter().map(|line| line.line).collect();
+    assert!(lines.contains(&1) && lines.contains(&3));
+    let trace: serde_json::Value = serde_json::from_str(&profile.to_chrome_trace()).unwrap();
+    assert_eq!(trace["traceEvents"][0]["ph"], "X");
+    let top = profile.top(3);
+    assert!(top.starts_with("Function"));
+    assert!(top.contains("\nPrimitive"));
+}
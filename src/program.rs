@@ -0,0 +1,211 @@
+//! Sharing compiled programs between runtimes
+//!
+//! A [`Program`] is a frozen copy of a runtime's compiled state. Any number of runtimes
+//! can be created from it with [`Uiua::from_program`] without parsing or compiling
+//! anything again. They share the program's globals and spans, and only store what
+//! they add themselves.
+
+use std::{collections::HashMap, mem::replace, ops::Index, path::Path, sync::Arc};
+
+use crate::{
+    bytecode::{Recording, Step},
+    lex::Span,
+    run::Global,
+    Ident, SysBackend, Uiua, UiuaError, UiuaResult,
+};
+
+/// A list whose first items may be shared with other runtimes
+#[derive(Clone)]
+pub(crate) struct LayeredVec<T> {
+    /// The shared items
+    base: Arc<[T]>,
+    /// The items added after the shared ones
+    added: Vec<T>,
+}
+
+impl<T> LayeredVec<T> {
+    pub fn new(base: Arc<[T]>) -> Self {
+        Self {
+            base,
+            added: Vec::new(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.base.len() + self.added.len()
+    }
+    pub fn get(&self, index: usize) -> Option<&T> {
+        match index.checked_sub(self.base.len()) {
+            None => self.base.get(index),
+            Some(index) => self.added.get(index),
+        }
+    }
+    pub fn push(&mut self, item: T) {
+        self.added.push(item);
+    }
+}
+
+impl<T: Clone> LayeredVec<T> {
+    /// Move all items into the shared part and get it
+    pub fn freeze(&mut self) -> Arc<[T]> {
+        if !self.added.is_empty() {
+            let items = self.base.iter().cloned().chain(self.added.drain(..));
+            self.base = items.collect();
+        }
+        self.base.clone()
+    }
+}
+
+impl<T> From<Vec<T>> for LayeredVec<T> {
+    fn from(items: Vec<T>) -> Self {
+        Self::new(items.into())
+    }
+}
+
+impl<T> Index<usize> for LayeredVec<T> {
+    type Output = T;
+    #[track_caller]
+    fn index(&self, index: usize) -> &T {
+        let len = self.len();
+        self.get(index)
+            .unwrap_or_else(|| panic!("index {index} is out of bounds for length {len}"))
+    }
+}
+
+impl<T> Extend<T> for LayeredVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.added.extend(iter);
+    }
+}
+
+/// A compiled program that can be shared between runtimes
+///
+/// A program is created with [`Uiua::compile_program_str`] or [`Uiua::compile_program_file`].
+/// Compiling a program evaluates its bindings but not its other top-level code.
+///
+/// [`Uiua::from_program`] creates a runtime with the program's bindings, which can be called
+/// with [`Uiua::call_binding`]. [`Uiua::run_program`] runs the program's top-level code.
+///
+/// Cloning a program is cheap, and it can be sent between threads.
+///
+/// ```
+/// use uiua::*;
+///
+/// let program = Uiua::with_native_sys()
+///     .compile_program_str("Double ← ×2\nDouble 21")
+///     .unwrap();
+/// let handles: Vec<_> = (0..4)
+///     .map(|i| {
+///         let program = program.clone();
+///         std::thread::spawn(move || {
+///             let mut env = Uiua::from_program(&program, NativeSys);
+///             env.run_program(&program).unwrap();
+///             let res: f64 = env.call_binding("Double", i as f64).unwrap();
+///             (env.pop_num().unwrap(), res)
+///         })
+///     })
+///     .collect();
+/// for (i, handle) in handles.into_iter().enumerate() {
+///     assert_eq!(handle.join().unwrap(), (42.0, i as f64 * 2.0));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Program {
+    globals: Arc<[Global]>,
+    spans: Arc<[Span]>,
+    names: Arc<HashMap<Ident, usize>>,
+    /// The top-level code that was not run when compiling
+    steps: Arc<[Step]>,
+}
+
+impl Program {
+    /// Get the names of the program's bindings
+    pub fn binding_names(&self) -> impl Iterator<Item = &str> {
+        self.names.keys().map(AsRef::as_ref)
+    }
+}
+
+impl Uiua {
+    /// Compile a Uiua file into a [`Program`]
+    ///
+    /// Bindings are evaluated, but other top-level code is not run.
+    pub fn compile_program_file<P: AsRef<Path>>(&mut self, path: P) -> UiuaResult<Program> {
+        let path = path.as_ref();
+        let input =
+            std::fs::read_to_string(path).map_err(|e| UiuaError::Load(path.into(), e.into()))?;
+        self.compile_program_impl(&input, Some(path))
+    }
+    /// Compile a string into a [`Program`]
+    ///
+    /// Bindings are evaluated, but other top-level code is not run.
+    pub fn compile_program_str(&mut self, input: &str) -> UiuaResult<Program> {
+        self.compile_program_impl(input, None)
+    }
+    fn compile_program_impl(&mut self, input: &str, path: Option<&Path>) -> UiuaResult<Program> {
+        let compile_only = replace(&mut self.compile_only, true);
+        let depth = self.higher_scopes.len();
+        let outer = self.recording.replace(Recording::new(depth));
+        let res = match path {
+            Some(path) => self.load_str_path(input, path),
+            None => self.load_str(input),
+        };
+        self.compile_only = compile_only;
+        let recording = replace(&mut self.recording, outer).unwrap();
+        res?;
+        // Bindings are already in the globals
+        let steps = (recording.steps.into_iter())
+            .filter(|step| !matches!(step, Step::Bind(..)))
+            .collect();
+        Ok(Program {
+            globals: self.globals.lock().freeze(),
+            spans: self.spans.lock().freeze(),
+            names: Arc::new(self.scope.names.clone()),
+            steps,
+        })
+    }
+    /// Create a new Uiua runtime with the bindings of a [`Program`]
+    ///
+    /// The program's compiled code is shared, not copied.
+    pub fn from_program(program: &Program, backend: impl SysBackend) -> Self {
+        let mut env = Uiua::with_backend(backend);
+        *env.globals.lock() = LayeredVec::new(program.globals.clone());
+        *env.spans.lock() = LayeredVec::new(program.spans.clone());
+        env.scope.names = (*program.names).clone();
+        env
+    }
+    /// Run the top-level code of a [`Program`]
+    ///
+    /// # Errors
+    /// Returns an error if the code fails, or if this runtime was not created from
+    /// the program with [`Uiua::from_program`].
+    pub fn run_program(&mut self, program: &Program) -> UiuaResult {
+        if !Arc::ptr_eq(&self.spans.lock().base, &program.spans) {
+            return Err(self.error(
+                "A program can only be run in a runtime created from it with Uiua::from_program",
+            ));
+        }
+        self.execution_start = instant::now();
+        self.run_steps(program.steps.to_vec())
+    }
+}
+
+#[test]
+fn shared_program() {
+    let code = "\
+Counter ← 5
+Add ← +Counter
+Add 1";
+    let program = Uiua::with_native_sys().compile_program_str(code).unwrap();
+    assert!(program.binding_names().any(|name| name == "Add"));
+
+    let mut a = Uiua::from_program(&program, crate::NativeSys);
+    let mut b = Uiua::from_program(&program, crate::NativeSys);
+    a.load_str("Add ← ×10\nAdd 2").unwrap();
+    assert_eq!(a.pop_num().unwrap(), 20.0);
+    b.run_program(&program).unwrap();
+    assert_eq!(b.pop_num().unwrap(), 6.0);
+    let res: f64 = b.call_binding("Add", 2.0).unwrap();
+    assert_eq!(res, 7.0);
+
+    let mut other = Uiua::with_native_sys();
+    assert!(other.run_program(&program).is_err());
+}
@@ -6,7 +6,10 @@ use std::{
     panic::{catch_unwind, AssertUnwindSafe},
     path::{Path, PathBuf},
     str::FromStr,
-    sync::Arc,
+    sync::{
+        atomic::{self, AtomicBool},
+        Arc,
+    },
 };
 
 use crossbeam_channel::{Receiver, Sender, TryRecvError};
@@ -15,9 +18,22 @@ use parking_lot::Mutex;
 use rand::prelude::*;
 
 use crate::{
-    array::Array, boxed::Boxed, constants, function::*, lex::Span, parse::parse,
-    primitive::Primitive, value::Value, Diagnostic, DiagnosticKind, Ident, NativeSys, SysBackend,
-    SysOp, TraceFrame, UiuaError, UiuaResult,
+    algorithm::memo::MemoCache,
+    array::Array,
+    boxed::Boxed,
+    bytecode::{module_key, Recording},
+    constants,
+    debug::Debugger,
+    function::*,
+    lex::Span,
+    memory::{HeldValues, MemoryLimit},
+    parse::parse,
+    primitive::Primitive,
+    profiler::Profiler,
+    program::LayeredVec,
+    value::Value,
+    CoverageData, Diagnostic, DiagnosticKind, Ident, NativeSys, Snapshot, SysBackend, SysOp,
+    TestResult, TraceFrame, UiuaError, UiuaResult,
 };
 
 /// The Uiua runtime
@@ -26,19 +42,19 @@ pub struct Uiua {
     /// Functions which are under construction
     pub(crate) new_functions: Vec<Vec<Instr>>,
     /// Global values
-    pub(crate) globals: Arc<Mutex<Vec<Global>>>,
+    pub(crate) globals: Arc<Mutex<LayeredVec<Global>>>,
     /// Indexable spans
-    spans: Arc<Mutex<Vec<Span>>>,
+    pub(crate) spans: Arc<Mutex<LayeredVec<Span>>>,
     /// The thread's stack
     pub(crate) stack: Vec<Value>,
     /// The thread's function stack
     pub(crate) function_stack: Vec<Arc<Function>>,
     /// The thread's temp stack for inlining
-    inline_stack: Vec<Value>,
+    pub(crate) inline_stack: Vec<Value>,
     /// The thread's temp stack for unders
-    under_stack: Vec<Value>,
+    pub(crate) under_stack: Vec<Value>,
     /// The thread's temp stack for functions
-    temp_function_stack: Vec<Arc<Function>>,
+    pub(crate) temp_function_stack: Vec<Arc<Function>>,
     /// The current scope
     pub(crate) scope: Scope,
     /// Ancestor scopes of the current one
@@ -48,11 +64,19 @@ pub struct Uiua {
     /// A limit on the execution duration in milliseconds
     execution_limit: Option<f64>,
     /// The time at which execution started
-    execution_start: f64,
+    pub(crate) execution_start: f64,
     /// The paths of files currently being imported (used to detect import cycles)
-    current_imports: Arc<Mutex<HashSet<PathBuf>>>,
+    pub(crate) current_imports: Arc<Mutex<HashSet<PathBuf>>>,
     /// The stacks of imported files
-    imports: Arc<Mutex<HashMap<PathBuf, HashMap<Ident, usize>>>>,
+    imports: Arc<Mutex<HashMap<PathBuf, Import>>>,
+    /// The directory in which compiled imports are cached
+    pub(crate) module_cache: Option<PathBuf>,
+    /// The steps of the module currently being loaded, if they are being recorded
+    pub(crate) recording: Option<Recording>,
+    /// Whether to compile top-level code without running it
+    pub(crate) compile_only: bool,
+    /// Whether to optimize compiled code
+    pub(crate) optimize: bool,
     /// Accumulated diagnostics
     pub(crate) diagnostics: BTreeSet<Diagnostic>,
     /// Print diagnostics as they are encountered
@@ -69,6 +93,36 @@ pub struct Uiua {
     pub(crate) backend: Arc<dyn SysBackend>,
     /// The thread interface
     thread: ThisThread,
+    /// The attached debugger
+    pub(crate) debugger: Option<Arc<Mutex<Debugger>>>,
+    /// The random number generator, seeded from the backend on first use
+    rng: Option<SmallRng>,
+    /// The virtual clock's current time in seconds, if there is one
+    clock: Option<Arc<Mutex<f64>>>,
+    /// Output printed by the snapshot test block currently being run
+    pub(crate) snapshot_output: Option<String>,
+    /// Snapshots taken by snapshot test blocks
+    pub(crate) snapshots: Vec<Snapshot>,
+    /// The number of snapshot test blocks encountered
+    pub(crate) snapshot_blocks: usize,
+    /// Only run tests whose names contain this
+    test_filter: Option<String>,
+    /// The results of the tests that have been run
+    test_results: Vec<TestResult>,
+    /// Whether a test case is currently running
+    in_test_case: bool,
+    /// Execution counts for code coverage, if it is enabled
+    pub(crate) coverage: Option<Arc<Mutex<CoverageData>>>,
+    /// The profiler, if profiling is enabled
+    pub(crate) profiler: Option<Profiler>,
+    /// The memory limit, if there is one
+    pub(crate) memory_limit: Option<MemoryLimit>,
+    /// The bytes held by the values on the stacks, if there is a memory limit
+    pub(crate) held_values: HeldValues,
+    /// The handle for interrupting execution
+    interrupt: InterruptHandle,
+    /// The results cached by `memo`
+    pub(crate) memo: Arc<Mutex<MemoCache>>,
 }
 
 #[derive(Clone)]
@@ -77,12 +131,21 @@ pub(crate) enum Global {
     Func(Arc<Function>),
 }
 
+/// A module that has been imported
+#[derive(Clone)]
+pub(crate) struct Import {
+    /// The names the module binds
+    pub names: HashMap<Ident, usize>,
+    /// The imports the module's compiled code depends on
+    pub deps: Vec<(PathBuf, u64)>,
+}
+
 #[derive(Clone)]
 pub(crate) struct Scope {
     /// The stack height at the start of each array currently being built
     pub array: Vec<usize>,
     /// The call stack
-    call: Vec<StackFrame>,
+    pub(crate) call: Vec<StackFrame>,
     /// Map local names to global indices
     pub names: HashMap<Ident, usize>,
     /// The current fill values
@@ -120,15 +183,15 @@ struct Fills {
 }
 
 #[derive(Clone)]
-struct StackFrame {
+pub(crate) struct StackFrame {
     /// The function being executed
-    function: Arc<Function>,
+    pub function: Arc<Function>,
     /// The span at which the function was called
-    call_span: usize,
+    pub call_span: usize,
     /// The program counter for the function
-    pc: usize,
+    pub pc: usize,
     /// Additional spans for error reporting
-    spans: Vec<(usize, Option<Primitive>)>,
+    pub spans: Vec<(usize, Option<Primitive>)>,
 }
 
 #[derive(Debug, Clone)]
@@ -178,6 +241,9 @@ pub enum RunMode {
     #[default]
     Normal,
     /// Only run non-binding lines inside of test blocks
+    ///
+    /// Failing tests do not stop the run. Their results
+    /// can be taken with [`Uiua::take_test_results`].
     Test,
     /// Run everything
     All,
@@ -195,6 +261,31 @@ impl FromStr for RunMode {
     }
 }
 
+/// A handle for interrupting a running program from another thread
+///
+/// Execution stops at the next instruction, or at the next iteration of a
+/// looping primitive, with a [`UiuaError::Interrupted`]. Interrupting stops the
+/// threads the program has spawned as well.
+///
+/// The handle stays interrupted until it is [`reset`](InterruptHandle::reset).
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    /// Interrupt the program
+    pub fn interrupt(&self) {
+        self.0.store(true, atomic::Ordering::Relaxed);
+    }
+    /// Check if the program has been interrupted
+    pub fn is_interrupted(&self) -> bool {
+        self.0.load(atomic::Ordering::Relaxed)
+    }
+    /// Allow the program to run again
+    pub fn reset(&self) {
+        self.0.store(false, atomic::Ordering::Relaxed);
+    }
+}
+
 impl Uiua {
     /// Create a new Uiua runtime with the standard IO backend
     pub fn with_native_sys() -> Self {
@@ -205,7 +296,7 @@ impl Uiua {
             globals.push(Global::Val(def.value.clone()));
         }
         Uiua {
-            spans: Arc::new(Mutex::new(vec![Span::Builtin])),
+            spans: Arc::new(Mutex::new(vec![Span::Builtin].into())),
             stack: Vec::new(),
             function_stack: Vec::new(),
             inline_stack: Vec::new(),
@@ -213,10 +304,14 @@ impl Uiua {
             temp_function_stack: Vec::new(),
             scope,
             higher_scopes: Vec::new(),
-            globals: Arc::new(Mutex::new(globals)),
+            globals: Arc::new(Mutex::new(globals.into())),
             new_functions: Vec::new(),
             current_imports: Arc::new(Mutex::new(HashSet::new())),
             imports: Arc::new(Mutex::new(HashMap::new())),
+            module_cache: None,
+            recording: None,
+            compile_only: false,
+            optimize: true,
             mode: RunMode::Normal,
             diagnostics: BTreeSet::new(),
             backend: Arc::new(NativeSys),
@@ -228,6 +323,21 @@ impl Uiua {
             execution_limit: None,
             execution_start: 0.0,
             thread: ThisThread::default(),
+            debugger: None,
+            rng: None,
+            clock: None,
+            snapshot_output: None,
+            snapshots: Vec::new(),
+            snapshot_blocks: 0,
+            test_filter: None,
+            test_results: Vec::new(),
+            in_test_case: false,
+            coverage: None,
+            profiler: None,
+            memory_limit: None,
+            held_values: HeldValues::default(),
+            interrupt: InterruptHandle::default(),
+            memo: Arc::default(),
         }
     }
     /// Create a new Uiua runtime with a custom IO backend
@@ -266,11 +376,138 @@ impl Uiua {
         self.time_instrs = time_instrs;
         self
     }
+    /// Set whether to optimize compiled code
+    ///
+    /// This is enabled by default. Disabling it can be useful for checking
+    /// that an optimization does not change a program's behavior.
+    pub fn with_optimization(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
     /// Limit the execution duration
     pub fn with_execution_limit(mut self, limit: Duration) -> Self {
         self.execution_limit = Some(limit.as_millis() as f64);
         self
     }
+    /// Get a handle that can interrupt execution from another thread
+    ///
+    /// ```
+    /// # use uiua::*;
+    /// let mut env = Uiua::with_native_sys();
+    /// let handle = env.interrupt_handle();
+    /// std::thread::spawn(move || {
+    ///     std::thread::sleep(std::time::Duration::from_millis(100));
+    ///     handle.interrupt();
+    /// });
+    /// let err = env.load_str("⍥(+1)∞ 0").unwrap_err();
+    /// assert!(err.is_interrupted());
+    /// // The stack is left as it was when execution stopped
+    /// assert!(env.pop_num().unwrap() > 0.0);
+    /// ```
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+    /// Return an error if execution has been interrupted
+    pub(crate) fn check_interrupt(&self) -> UiuaResult {
+        if self.interrupt.is_interrupted() {
+            Err(UiuaError::Interrupted(self.span()))
+        } else {
+            Ok(())
+        }
+    }
+    /// Limit the memory used by array data on the stacks, in bytes
+    ///
+    /// An instruction that would go over the limit fails with [`UiuaError::MemoryLimit`],
+    /// which can be caught with `try`. The most memory used at once can be
+    /// retrieved with [`Uiua::peak_memory`].
+    ///
+    /// ```
+    /// # use uiua::*;
+    /// let mut env = Uiua::with_native_sys().with_memory_limit(1_000_000);
+    /// assert!(env.load_str("↯1e9 0").is_err());
+    /// env.load_str("⇡100").unwrap();
+    /// assert!(env.peak_memory().unwrap() >= 800);
+    /// ```
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = Some(MemoryLimit::new(bytes));
+        self
+    }
+    /// Seed the random number generator used by `random`
+    ///
+    /// Without a seed, the generator is seeded by [`SysBackend::random_seed`].
+    /// ```rust
+    /// use uiua::*;
+    ///
+    /// let run = || {
+    ///     let mut uiua = Uiua::with_native_sys().with_seed(42);
+    ///     uiua.load_str("[⍥⚂5]").unwrap();
+    ///     uiua.pop("numbers").unwrap()
+    /// };
+    /// assert_eq!(run(), run());
+    /// ```
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Some(SmallRng::seed_from_u64(seed));
+        self
+    }
+    /// Use a virtual clock that starts at `start` seconds
+    ///
+    /// `now` reads the virtual clock instead of [`SysBackend::now`],
+    /// and `&sl` advances it instantly instead of sleeping.
+    /// ```rust
+    /// use uiua::*;
+    ///
+    /// let mut uiua = Uiua::with_native_sys().with_virtual_clock(10.0);
+    /// uiua.load_str("now &sl 3600 now").unwrap();
+    /// assert_eq!(uiua.pop_num().unwrap(), 3610.0);
+    /// assert_eq!(uiua.pop_num().unwrap(), 10.0);
+    /// ```
+    pub fn with_virtual_clock(mut self, start: f64) -> Self {
+        self.clock = Some(Arc::new(Mutex::new(start)));
+        self
+    }
+    /// Set whether to collect code coverage
+    ///
+    /// Code compiled while coverage is enabled is not optimized,
+    /// so that every instruction maps back to its source.
+    /// The coverage can be retrieved with [`Uiua::coverage`].
+    pub fn with_coverage(mut self, coverage: bool) -> Self {
+        self.coverage = coverage.then(Default::default);
+        self
+    }
+    /// Set whether to profile execution
+    ///
+    /// Every instruction is timed, which slows execution down considerably.
+    /// Calls to small functions are not inlined while profiling.
+    /// The times can be retrieved with [`Uiua::profile`].
+    pub fn with_profiling(mut self, profiling: bool) -> Self {
+        self.profiler = profiling.then(Profiler::new);
+        self
+    }
+    /// Only run tests whose names contain `filter`
+    ///
+    /// This only applies in [`RunMode::Test`].
+    pub fn with_test_filter(mut self, filter: impl Into<String>) -> Self {
+        self.test_filter = Some(filter.into());
+        self
+    }
+    /// Attach a [`Debugger`]
+    pub fn with_debugger(mut self, debugger: Debugger) -> Self {
+        self.debugger = Some(Arc::new(Mutex::new(debugger)));
+        self
+    }
+    /// Detach the [`Debugger`], if there is one
+    pub fn take_debugger(&mut self) -> Option<Debugger> {
+        let debugger = self.debugger.take()?;
+        Arc::try_unwrap(debugger).ok().map(Mutex::into_inner)
+    }
+    /// Cache compiled imports in a directory
+    ///
+    /// Files imported with `&i` are stored as [`CompiledModule`]s keyed by a hash of their source,
+    /// so later imports of an unchanged file do not need to recompile it.
+    pub fn with_module_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.module_cache = Some(dir.into());
+        self
+    }
     /// Set the [`RunMode`]
     ///
     /// Default is [`RunMode::Normal`]
@@ -404,13 +641,18 @@ code:
                 path.to_string_lossy()
             )));
         }
+        let key = module_key(input, self.mode, self.optimize);
         if !self.imports.lock().contains_key(path) {
-            let import = self.in_scope(|env| env.load_str_path(input, path).map(drop))?;
+            let import = self.load_import(input, path, key)?;
             self.imports.lock().insert(path.into(), import);
         }
         let imports_gaurd = self.imports.lock();
-        let imports = &imports_gaurd[path];
-        let idx = imports.get(item).ok_or_else(|| {
+        let import = &imports_gaurd[path];
+        if let Some(recording) = &mut self.recording {
+            recording.deps.push((path.into(), key));
+            recording.deps.extend(import.deps.iter().cloned());
+        }
+        let idx = import.names.get(item).ok_or_else(|| {
             self.error(format!("Item `{}` not found in {}", item, path.display()))
         })?;
         let global = self.globals.lock()[*idx].clone();
@@ -432,9 +674,26 @@ code:
         Ok(())
     }
     fn exec(&mut self, frame: StackFrame) -> UiuaResult<Arc<Function>> {
+        let Some(profiler) = &mut self.profiler else {
+            return self.exec_frame(frame);
+        };
+        profiler.enter(&frame.function.id);
+        let res = self.exec_frame(frame);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.exit();
+        }
+        res
+    }
+    fn exec_frame(&mut self, frame: StackFrame) -> UiuaResult<Arc<Function>> {
         self.scope.call.push(frame);
         let mut formatted_instr = String::new();
         Ok(loop {
+            if let Some(debugger) = self.debugger.clone() {
+                if let Err(err) = debugger.lock().before_instr(self) {
+                    let frame = self.scope.call.pop().unwrap();
+                    return Err(self.trace_error(err, frame));
+                }
+            }
             let frame = self.scope.call.last().unwrap();
             let Some(instr) = frame.function.instrs.get(frame.pc) else {
                 break self.scope.call.pop().unwrap().function;
@@ -467,17 +726,35 @@ code:
                 formatted_instr = format!("{instr:?}");
                 self.last_time = instant::now();
             }
-            let res = match instr {
+            if let (Some(coverage), Some(span)) = (&self.coverage, instr.span()) {
+                coverage.lock().hit(span);
+            }
+            if let Some(profiler) = &mut self.profiler {
+                let prim = match instr {
+                    Instr::Prim(prim, _) => Some(*prim),
+                    _ => None,
+                };
+                profiler.instr(prim, instr.span());
+            }
+            let memory =
+                (self.memory_limit.is_some()).then(|| (self.begin_instr_memory(), instr.span()));
+            let mut res = match instr {
                 &Instr::Prim(prim, span) => {
                     self.push_span(span, Some(prim));
                     let res = prim.run(self);
                     self.pop_span();
+                    if res.is_ok() && !prim.keeps_map_keys() {
+                        self.strip_map_keys(prim.outputs().unwrap_or(0).into());
+                    }
                     res
                 }
                 &Instr::ImplPrim(prim, span) => {
                     self.push_span(span, None);
                     let res = prim.run(self);
                     self.pop_span();
+                    if res.is_ok() && prim.modifier_args().is_none() {
+                        self.strip_map_keys(prim.outputs().into());
+                    }
                     res
                 }
                 Instr::Push(val) => {
@@ -524,6 +801,7 @@ code:
                                 "Switch index {i} is out of bounds for switch of size {count}"
                             )));
                         }
+                        self.cover_branch(i, count);
                         let f = self
                             .function_stack
                             .drain(self.function_stack.len() - count..)
@@ -578,6 +856,24 @@ code:
                     res
                 }
                 Instr::Dynamic(df) => df.f.clone()(self),
+                Instr::Format { parts, span } => {
+                    let parts = parts.clone();
+                    self.push_span(*span, None);
+                    let res = (|| {
+                        let mut formatted = String::new();
+                        for (i, part) in parts.iter().enumerate() {
+                            if i > 0 {
+                                let val = self.pop(format!("format argument {i}"))?;
+                                formatted.push_str(&val.to_string());
+                            }
+                            formatted.push_str(part);
+                        }
+                        self.push(formatted);
+                        Ok(())
+                    })();
+                    self.pop_span();
+                    res
+                }
                 &Instr::PushTempUnder { count, span } => (|| {
                     self.push_span(span, None);
                     for _ in 0..count {
@@ -645,6 +941,9 @@ code:
                     Ok(())
                 })(),
             };
+            if let Some((outer_allocated, span)) = memory {
+                res = res.and(self.end_instr_memory(outer_allocated, span));
+            }
             if self.time_instrs {
                 let end_time = instant::now();
                 let padding = self.scope.call.len().saturating_sub(1) * 2;
@@ -657,12 +956,21 @@ code:
                 self.last_time = instant::now();
             }
             if let Err(err) = res {
+                if let Some(debugger) = &self.debugger {
+                    if !matches!(err, UiuaError::Traced { .. } | UiuaError::Break(..)) {
+                        debugger.lock().on_error(self, &err);
+                    }
+                }
                 // Trace errors
                 let frame = self.scope.call.pop().unwrap();
                 return Err(self.trace_error(err, frame));
             } else {
                 // Go to next instruction
                 self.scope.call.last_mut().unwrap().pc += 1;
+                if let Err(err) = self.check_interrupt() {
+                    let frame = self.scope.call.pop().unwrap();
+                    return Err(self.trace_error(err, frame));
+                }
                 if let Some(limit) = self.execution_limit {
                     if instant::now() - self.execution_start > limit {
                         return Err(UiuaError::Timeout(self.span()));
@@ -671,6 +979,15 @@ code:
             }
         })
     }
+    /// Get the number of frames on the call stack, including those of enclosing scopes
+    pub(crate) fn call_depth(&self) -> usize {
+        (self
+            .higher_scopes
+            .iter()
+            .map(|scope| scope.call.len())
+            .sum::<usize>())
+            + self.scope.call.len()
+    }
     pub(crate) fn push_span(&mut self, span: usize, prim: Option<Primitive>) {
         self.scope.call.last_mut().unwrap().spans.push((span, prim));
     }
@@ -948,6 +1265,18 @@ code:
     pub fn take_diagnostics(&mut self) -> BTreeSet<Diagnostic> {
         take(&mut self.diagnostics)
     }
+    /// Take the results of the tests that have been run
+    ///
+    /// Tests are only run in [`RunMode::Test`]
+    pub fn take_test_results(&mut self) -> Vec<TestResult> {
+        take(&mut self.test_results)
+    }
+    /// Take the snapshots taken by snapshot test blocks
+    ///
+    /// These are only taken when running in [`RunMode::Test`]
+    pub fn take_snapshots(&mut self) -> Vec<Snapshot> {
+        take(&mut self.snapshots)
+    }
     /// Clone `n` values from the top of the stack
     pub fn clone_stack_top(&self, n: usize) -> Vec<Value> {
         self.stack.iter().rev().take(n).rev().cloned().collect()
@@ -1093,6 +1422,85 @@ code:
     pub(crate) fn pack_boxes(&self) -> bool {
         self.scope.pack_depth > 0
     }
+    /// Generate a random number in the range `[0, 1)`
+    pub(crate) fn rand(&mut self) -> f64 {
+        let backend = &self.backend;
+        (self.rng)
+            .get_or_insert_with(|| SmallRng::seed_from_u64(backend.random_seed()))
+            .gen()
+    }
+    /// Get the current time in seconds
+    pub(crate) fn now(&self) -> f64 {
+        match &self.clock {
+            Some(clock) => *clock.lock(),
+            None => self.backend.now(),
+        }
+    }
+    /// Run a test case, recording its result instead of returning its error
+    ///
+    /// Test cases nested in another one are run as part of it.
+    pub(crate) fn test_case(
+        &mut self,
+        name: String,
+        line: usize,
+        f: impl FnOnce(&mut Self) -> UiuaResult,
+    ) -> UiuaResult {
+        if self.in_test_case {
+            return f(self);
+        }
+        if (self.test_filter.as_ref()).is_some_and(|filter| !name.contains(filter.as_str())) {
+            return Ok(());
+        }
+        let start_height = self.stack.len();
+        let scope_depth = self.higher_scopes.len();
+        let start = instant::now();
+        self.in_test_case = true;
+        let res = f(self);
+        self.in_test_case = false;
+        if res.as_ref().is_err_and(UiuaError::is_interrupted) {
+            return res;
+        }
+        let duration = (instant::now() - start) / 1000.0;
+        if res.is_err() {
+            self.truncate_stack(start_height);
+            while self.higher_scopes.len() > scope_depth {
+                self.scope = self.higher_scopes.pop().unwrap();
+            }
+        }
+        self.test_results.push(TestResult {
+            name,
+            line,
+            duration,
+            failure: res.err().map(|e| e.report()),
+        });
+        Ok(())
+    }
+    /// Count a taken branch of the current instruction for code coverage
+    pub(crate) fn cover_branch(&self, branch: usize, count: usize) {
+        if let Some(coverage) = &self.coverage {
+            coverage.lock().branch(self.span_index(), branch, count);
+        }
+    }
+    /// Print a string to stdout, or capture it for a snapshot
+    pub(crate) fn print_str_stdout(&mut self, s: &str) -> Result<(), String> {
+        match &mut self.snapshot_output {
+            Some(output) => {
+                output.push_str(s);
+                Ok(())
+            }
+            None => self.backend.print_str_stdout(s),
+        }
+    }
+    /// Sleep for `seconds` seconds, or advance the virtual clock
+    pub(crate) fn sleep(&self, seconds: f64) -> Result<(), String> {
+        match &self.clock {
+            Some(clock) => {
+                *clock.lock() += seconds;
+                Ok(())
+            }
+            None => self.backend.sleep(seconds),
+        }
+    }
     /// Spawn a thread
     pub(crate) fn spawn(
         &mut self,
@@ -1132,6 +1540,10 @@ code:
             mode: self.mode,
             current_imports: self.current_imports.clone(),
             imports: self.imports.clone(),
+            module_cache: self.module_cache.clone(),
+            recording: None,
+            compile_only: false,
+            optimize: self.optimize,
             diagnostics: BTreeSet::new(),
             print_diagnostics: self.print_diagnostics,
             time_instrs: self.time_instrs,
@@ -1142,6 +1554,24 @@ code:
             execution_limit: self.execution_limit,
             execution_start: self.execution_start,
             thread,
+            debugger: None,
+            rng: self
+                .rng
+                .as_mut()
+                .map(|rng| SmallRng::seed_from_u64(rng.gen())),
+            clock: self.clock.clone(),
+            snapshot_output: None,
+            snapshots: Vec::new(),
+            snapshot_blocks: 0,
+            test_filter: None,
+            test_results: Vec::new(),
+            in_test_case: false,
+            coverage: self.coverage.clone(),
+            profiler: self.profiler.as_ref().map(Profiler::spawn),
+            memory_limit: self.memory_limit.clone(),
+            held_values: HeldValues::default(),
+            interrupt: self.interrupt.clone(),
+            memo: self.memo.clone(),
         };
         #[cfg(not(target_arch = "wasm32"))]
         let handle = std::thread::Builder::new()
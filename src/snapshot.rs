@@ -0,0 +1,307 @@
+//! Snapshot testing
+//!
+//! A test block whose first line is a `# snapshot` comment has its printed
+//! output and final stack recorded when run in [`RunMode::Test`](crate::RunMode::Test).
+//! `uiua test` compares these against a `.snap` file next to the source.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// The recorded output of a snapshot test block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The snapshot's name
+    ///
+    /// This is the text after `# snapshot`, or the block's position
+    /// among the file's snapshot blocks if there is none.
+    pub name: String,
+    /// The line of the block's `# snapshot` comment
+    pub line: usize,
+    /// Everything the block printed to stdout
+    pub output: String,
+    /// The values the block left on the stack, formatted as they are shown
+    pub stack: Vec<String>,
+}
+
+impl Snapshot {
+    /// Get the snapshot's contents as they are stored
+    ///
+    /// Trailing whitespace is trimmed from every line so that
+    /// editors do not change stored snapshots.
+    pub fn contents(&self) -> String {
+        let mut contents = String::new();
+        let stack = (!self.stack.is_empty()).then_some("# stack");
+        for line in (self.output.lines())
+            .chain(stack)
+            .chain(self.stack.iter().flat_map(|v| v.lines()))
+        {
+            contents.push_str(line.trim_end());
+            contents.push('\n');
+        }
+        contents
+    }
+}
+
+/// Get the name of a snapshot block from its first comment
+pub(crate) fn snapshot_name(comment: &str) -> Option<&str> {
+    let rest = comment.trim().strip_prefix("snapshot")?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then(|| rest.trim())
+}
+
+/// The stored snapshots of a source file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotFile {
+    /// The names and contents of the snapshots, in order
+    pub snapshots: Vec<(String, String)>,
+}
+
+impl SnapshotFile {
+    /// Get the path of the snapshot file for a source file
+    pub fn path_for(source: &Path) -> PathBuf {
+        source.with_extension("snap")
+    }
+    /// Make a snapshot file from recorded snapshots
+    pub fn from_snapshots(snapshots: &[Snapshot]) -> Self {
+        SnapshotFile {
+            snapshots: (snapshots.iter())
+                .map(|snap| (snap.name.clone(), snap.contents()))
+                .collect(),
+        }
+    }
+    /// Parse a snapshot file
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut snapshots = Vec::new();
+        let mut lines = input.lines().enumerate();
+        while let Some((i, line)) = lines.next() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let header = (line.strip_prefix("--- "))
+                .and_then(|header| header.rsplit_once(" ("))
+                .and_then(|(name, count)| {
+                    let count =
+                        (count.strip_suffix(" lines)")).or_else(|| count.strip_suffix(" line)"))?;
+                    Some((name, count.parse::<usize>().ok()?))
+                });
+            let Some((name, count)) = header else {
+                return Err(format!(
+                    "Invalid snapshot header on line {}: {line:?}",
+                    i + 1
+                ));
+            };
+            let mut contents = String::new();
+            for _ in 0..count {
+                let Some((_, line)) = lines.next() else {
+                    return Err(format!("Snapshot `{name}` ends early"));
+                };
+                contents.push_str(line);
+                contents.push('\n');
+            }
+            snapshots.push((name.to_string(), contents));
+        }
+        Ok(SnapshotFile { snapshots })
+    }
+    /// Get the stored contents of a snapshot
+    pub fn get(&self, name: &str) -> Option<&str> {
+        (self.snapshots.iter())
+            .find(|(n, _)| n == name)
+            .map(|(_, contents)| contents.as_str())
+    }
+    /// Check recorded snapshots against the stored ones
+    ///
+    /// Changed snapshots' failure messages include a unified
+    /// diff of the stored and recorded contents.
+    pub fn check(&self, snapshots: &[Snapshot]) -> Vec<SnapshotFailure> {
+        let mut failures = Vec::new();
+        for (i, snap) in snapshots.iter().enumerate() {
+            let name = &snap.name;
+            let message = if snapshots[..i].iter().any(|other| &other.name == name) {
+                format!("Snapshot `{name}` has the same name as an earlier one")
+            } else {
+                let contents = snap.contents();
+                match self.get(name) {
+                    None => format!("Snapshot `{name}` has not been stored"),
+                    Some(stored) if stored != contents => format!(
+                        "Snapshot `{name}` does not match:\n{}",
+                        unified_diff(stored, &contents).trim_end()
+                    ),
+                    Some(_) => continue,
+                }
+            };
+            failures.push(SnapshotFailure {
+                name: name.clone(),
+                line: Some(snap.line),
+                message,
+            });
+        }
+        for (name, _) in &self.snapshots {
+            if !snapshots.iter().any(|snap| &snap.name == name) {
+                failures.push(SnapshotFailure {
+                    name: name.clone(),
+                    line: None,
+                    message: format!("Stored snapshot `{name}` was not taken"),
+                });
+            }
+        }
+        failures
+    }
+}
+
+/// A snapshot that does not match its stored version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotFailure {
+    /// The snapshot's name
+    pub name: String,
+    /// The line of the snapshot's block, if it was taken
+    pub line: Option<usize>,
+    /// A description of the failure
+    pub message: String,
+}
+
+impl fmt::Display for SnapshotFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, contents)) in self.snapshots.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let count = contents.lines().count();
+            let s = if count == 1 { "" } else { "s" };
+            writeln!(f, "--- {name} ({count} line{s})")?;
+            for line in contents.lines() {
+                writeln!(f, "{line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The number of unchanged lines shown around each change in a diff
+const DIFF_CONTEXT: usize = 3;
+
+/// Make a unified diff of the lines of two strings
+fn unified_diff(old: &str, new: &str) -> String {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    // Lengths of the longest common subsequences of every pair of suffixes
+    let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            edits.push((' ', a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            edits.push(('-', a[i]));
+            i += 1;
+        } else {
+            edits.push(('+', b[j]));
+            j += 1;
+        }
+    }
+    let changes: Vec<usize> = (edits.iter().enumerate())
+        .filter(|(_, (kind, _))| *kind != ' ')
+        .map(|(i, _)| i)
+        .collect();
+    let mut diff = String::from("--- stored\n+++ actual\n");
+    let mut k = 0;
+    while k < changes.len() {
+        // Group changes whose contexts overlap into one hunk
+        let start = changes[k].saturating_sub(DIFF_CONTEXT);
+        let mut end = changes[k] + 1;
+        while k < changes.len() && changes[k] <= end + 2 * DIFF_CONTEXT {
+            end = changes[k] + 1;
+            k += 1;
+        }
+        let end = (end + DIFF_CONTEXT).min(edits.len());
+        let count = |edits: &[(char, &str)], skip: char| {
+            edits.iter().filter(|(kind, _)| *kind != skip).count()
+        };
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            count(&edits[..start], '+') + 1,
+            count(&edits[start..end], '+'),
+            count(&edits[..start], '-') + 1,
+            count(&edits[start..end], '-'),
+        ));
+        for (kind, line) in &edits[start..end] {
+            diff.push(*kind);
+            diff.push_str(line);
+            diff.push('\n');
+        }
+    }
+    diff
+}
+
+#[test]
+fn snapshot_file_round_trip() {
+    let snapshots = [
+        Snapshot {
+            name: "1".into(),
+            line: 2,
+            output: "hello\n--- 1 (3 lines)\n".into(),
+            stack: vec!["[1 2 3]".into()],
+        },
+        Snapshot {
+            name: "table".into(),
+            line: 8,
+            output: String::new(),
+            stack: vec!["╭─\n╷ 1 2\n  3 4\n╯".into()],
+        },
+    ];
+    let file = SnapshotFile::from_snapshots(&snapshots);
+    let parsed = SnapshotFile::parse(&file.to_string()).unwrap();
+    assert_eq!(parsed, file);
+    assert!(parsed.check(&snapshots).is_empty());
+
+    let mut changed = snapshots.clone();
+    changed[1].stack = vec!["╭─\n╷ 1 2\n  3 5\n╯".into()];
+    let failures = parsed.check(&changed);
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].line, Some(8));
+    assert!(failures[0].message.ends_with(
+        "--- stored\n+++ actual\n@@ -1,5 +1,5 @@\n # stack\n ╭─\n ╷ 1 2\n-  3 4\n+  3 5\n ╯"
+    ));
+}
+
+#[test]
+fn snapshot_blocks() {
+    use crate::{RunMode, Uiua};
+
+    let code = "\
+---
+# snapshot squares
+&p \"squares\"
+ⁿ2 ⇡4
+---
+---
+# snapshot
+&pf 1
+---
+---
+# not a snapshot
++1 2
+---";
+    let mut env = Uiua::with_native_sys().with_mode(RunMode::Test);
+    env.load_str(code).unwrap();
+    let snapshots = env.take_snapshots();
+    let names: Vec<_> = snapshots.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["squares", "2"]);
+    assert_eq!(snapshots[0].contents(), "squares\n# stack\n[0 1 4 9]\n");
+    assert_eq!(snapshots[1].contents(), "1\n");
+
+    let mut env = Uiua::with_native_sys();
+    env.load_str(code).unwrap();
+    assert!(env.take_snapshots().is_empty());
+}
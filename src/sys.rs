@@ -1,7 +1,7 @@
 use std::{
     any::Any,
     collections::{HashMap, HashSet},
-    io::{stderr, stdin, Cursor, Read, Write},
+    io::{stderr, Cursor, Write},
     sync::OnceLock,
     time::Duration,
 };
@@ -18,6 +18,7 @@ use crate::{
     array::{Array, Shape},
     boxed::Boxed,
     cowslice::{cowslice, CowSlice},
+    ffi::{FfiElem, FfiSignature, FfiType, FfiValue},
     function::Signature,
     primitive::PrimDoc,
     value::Value,
@@ -131,6 +132,8 @@ pub enum SysOpClass {
     Images,
     Gifs,
     Tcp,
+    Udp,
+    Ffi,
     Misc,
 }
 
@@ -440,6 +443,96 @@ sys_op! {
     /// - The HTTP version
     /// - The `Host` header (if not defined)
     (2, HttpsWrite, Tcp, "&httpsw", "http - Make an HTTP request"),
+    /// Create a UDP socket and bind it to a local address
+    ///
+    /// Returns a socket handle
+    /// Binding to port 0 picks any free port. The address that was picked can be found with [&udpaddr].
+    /// [under][&udpb] calls [&cl] automatically.
+    (1, UdpBind, Udp, "&udpb", "udp - bind"),
+    /// Send bytes from a UDP socket to an address
+    ///
+    /// Expects the bytes, the address, and a socket handle.
+    /// Strings are sent as UTF-8.
+    (3(0), UdpSend, Udp, "&udps", "udp - send"),
+    /// Receive a datagram with a UDP socket
+    ///
+    /// Expects a maximum number of bytes and a socket handle.
+    /// Returns the address the datagram came from and its bytes.
+    /// Any bytes past the maximum are discarded.
+    (2(2), UdpReceive, Udp, "&udpr", "udp - receive"),
+    /// Set a UDP socket to non-blocking mode
+    (1(0), UdpSetNonBlocking, Udp, "&udpsnb", "udp - set non-blocking"),
+    /// Set the read timeout of a UDP socket in seconds
+    (2(0), UdpSetReadTimeout, Udp, "&udpsrt", "udp - set read timeout"),
+    /// Set the write timeout of a UDP socket in seconds
+    (2(0), UdpSetWriteTimeout, Udp, "&udpswt", "udp - set write timeout"),
+    /// Get the local address of a UDP socket
+    (1, UdpAddr, Udp, "&udpaddr", "udp - address"),
+    /// Encode an array as JSON
+    ///
+    /// Number arrays become numbers or nested arrays of numbers, with `NaN` becoming `null`.
+    /// Character lists become strings.
+    /// ex: &jsone [1_2 3_4]
+    /// ex: &jsone ["Uiua" "Rust"]
+    /// Lists of boxes become arrays of their contents.
+    /// ex: &jsone {"Uiua" 5 [1 2 3]}
+    /// 2-column tables of boxes with string keys become objects.
+    /// ex: &jsone [{"name" "Uiua"} {"version" 0.23}]
+    /// Other box arrays and infinite numbers cannot be encoded.
+    ///
+    /// [invert][&jsone] is [&jsond].
+    (1, JsonEncode, Misc, "&jsone", "json - encode"),
+    /// Decode JSON into an array
+    ///
+    /// Arrays whose items decode to non-box arrays of the same type and shape become a single array. Other arrays become lists of boxes.
+    /// ex: &jsond "[[1, 2], [3, 4]]"
+    /// ex: &jsond "[1, \"two\", [3]]"
+    /// Objects become 2-column tables of boxed keys and values.
+    /// ex: &jsond "{\"name\": \"Uiua\", \"version\": 0.23}"
+    /// `true` and `false` become `1` and `0`, and `null` becomes `NaN`.
+    ///
+    /// [invert][&jsond] is [&jsone].
+    (1, JsonDecode, Misc, "&jsond", "json - decode"),
+    /// Load a shared library for calling its functions with [&ffi]
+    ///
+    /// Returns a library handle
+    /// [under][&ffil] calls [&cl] automatically, which unloads the library.
+    ///
+    /// This is only available if the interpreter was built with the `ffi` feature.
+    (1, FfiLoad, Ffi, "&ffil", "ffi - load library"),
+    /// Call a C function in a shared library
+    ///
+    /// Expects a C function declaration, a library handle from [&ffil], and the arguments.
+    /// The arguments should be a list of boxes, but a function with a single argument may be passed it unboxed.
+    ///
+    /// Supported types are `int`, `long`, `long long`, `int32_t`, `int64_t`, `float`, and `double` for numbers, `char*` for strings, and `void*` for opaque pointers, which are passed around as numbers.
+    /// Pointers to `unsigned char`, `uint8_t`, `int`, `long`, `float`, or `double` take arrays of numbers, which are copied into a buffer.
+    /// If there are any such arguments, the result is a list of boxes of the return value (unless it is `void`) followed by the contents of each buffer after the call.
+    ///
+    /// For example, with `Libm ← &ffil "libm.so.6"`:
+    ///   : Cos ← &ffi "double cos(double)" Libm
+    ///   : Cos 0
+    /// Declaring a function incorrectly is undefined behavior.
+    ///
+    /// This is only available if the interpreter was built with the `ffi` feature.
+    (3, Ffi, Ffi, "&ffi", "ffi - call function"),
+}
+
+impl SysOp {
+    /// Check if the system function only transforms its arguments
+    /// without touching the outside world
+    pub fn is_pure(&self) -> bool {
+        matches!(
+            self,
+            SysOp::JsonEncode
+                | SysOp::JsonDecode
+                | SysOp::ImDecode
+                | SysOp::ImEncode
+                | SysOp::GifDecode
+                | SysOp::GifEncode
+                | SysOp::AudioDecode
+        )
+    }
 }
 
 /// A handle to an IO stream
@@ -451,9 +544,9 @@ sys_op! {
 pub struct Handle(pub u64);
 
 impl Handle {
-    const STDIN: Self = Self(0);
-    const STDOUT: Self = Self(1);
-    const STDERR: Self = Self(2);
+    pub(crate) const STDIN: Self = Self(0);
+    pub(crate) const STDOUT: Self = Self(1);
+    pub(crate) const STDERR: Self = Self(2);
     /// The first handle that can be used by the user
     pub const FIRST_UNRESERVED: Self = Self(3);
 }
@@ -582,6 +675,14 @@ pub trait SysBackend: Any + Send + Sync + 'static {
     fn sleep(&self, seconds: f64) -> Result<(), String> {
         Err("Sleeping is not supported in this environment".into())
     }
+    /// Get the current time in seconds since the Unix epoch
+    fn now(&self) -> f64 {
+        instant::now() / 1000.0
+    }
+    /// Get a seed for the random number generator used by `random`
+    fn random_seed(&self) -> u64 {
+        instant::now().to_bits()
+    }
     /// Show an image
     fn show_image(&self, image: DynamicImage) -> Result<(), String> {
         Err("Showing images not supported in this environment".into())
@@ -638,6 +739,59 @@ pub trait SysBackend: Any + Send + Sync + 'static {
     ) -> Result<(), String> {
         Err("TCP sockets are not supported in this environment".into())
     }
+    /// Create a UDP socket and bind it to an address
+    fn udp_bind(&self, addr: &str) -> Result<Handle, String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    /// Send bytes from a UDP socket to an address
+    fn udp_send(&self, handle: Handle, addr: &str, bytes: &[u8]) -> Result<(), String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    /// Receive a datagram of at most `max` bytes with a UDP socket
+    ///
+    /// Returns the bytes and the address they came from
+    fn udp_receive(&self, handle: Handle, max: usize) -> Result<(Vec<u8>, String), String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    /// Get the local address of a UDP socket
+    fn udp_addr(&self, handle: Handle) -> Result<String, String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    /// Set a UDP socket to non-blocking mode
+    fn udp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    /// Set the read timeout of a UDP socket
+    fn udp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    /// Set the write timeout of a UDP socket
+    fn udp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    /// Load a shared library for calling foreign functions
+    fn ffi_load(&self, path: &str) -> Result<Handle, String> {
+        Err("FFI is not supported in this environment".into())
+    }
+    /// Call a function in a library loaded with [`SysBackend::ffi_load`]
+    ///
+    /// Buffer arguments should be updated with what the function wrote to them.
+    fn ffi_call(
+        &self,
+        lib: Handle,
+        sig: &FfiSignature,
+        args: &mut [FfiValue],
+    ) -> Result<FfiValue, String> {
+        Err("FFI is not supported in this environment".into())
+    }
     /// Close a stream
     fn close(&self, handle: Handle) -> Result<(), String> {
         Ok(())
@@ -673,25 +827,19 @@ impl SysOp {
         match self {
             SysOp::Show => {
                 let s = env.pop(1)?.show();
-                env.backend.print_str_stdout(&s).map_err(|e| env.error(e))?;
-                env.backend
-                    .print_str_stdout("\n")
-                    .map_err(|e| env.error(e))?;
+                env.print_str_stdout(&s).map_err(|e| env.error(e))?;
+                env.print_str_stdout("\n").map_err(|e| env.error(e))?;
             }
             SysOp::Prin => {
                 let val = env.pop(1)?;
-                env.backend
-                    .print_str_stdout(&val.to_string())
+                env.print_str_stdout(&val.to_string())
                     .map_err(|e| env.error(e))?;
             }
             SysOp::Print => {
                 let val = env.pop(1)?;
-                env.backend
-                    .print_str_stdout(&val.to_string())
-                    .map_err(|e| env.error(e))?;
-                env.backend
-                    .print_str_stdout("\n")
+                env.print_str_stdout(&val.to_string())
                     .map_err(|e| env.error(e))?;
+                env.print_str_stdout("\n").map_err(|e| env.error(e))?;
             }
             SysOp::ScanLine => {
                 if let Some(line) = env.backend.scan_line_stdin().map_err(|e| env.error(e))? {
@@ -750,12 +898,6 @@ impl SysOp {
                 let bytes = match handle {
                     Handle::STDOUT => return Err(env.error("Cannot read from stdout")),
                     Handle::STDERR => return Err(env.error("Cannot read from stderr")),
-                    Handle::STDIN => stdin()
-                        .lock()
-                        .bytes()
-                        .take(count)
-                        .collect::<Result<Vec<_>, _>>()
-                        .map_err(|e| env.error(e))?,
                     _ => env.backend.read(handle, count).map_err(|e| env.error(e))?,
                 };
                 let s = String::from_utf8(bytes).map_err(|e| env.error(e))?;
@@ -770,12 +912,6 @@ impl SysOp {
                 let bytes = match handle {
                     Handle::STDOUT => return Err(env.error("Cannot read from stdout")),
                     Handle::STDERR => return Err(env.error("Cannot read from stderr")),
-                    Handle::STDIN => stdin()
-                        .lock()
-                        .bytes()
-                        .take(count)
-                        .collect::<Result<Vec<_>, _>>()
-                        .map_err(|e| env.error(e))?,
                     _ => env.backend.read(handle, count).map_err(|e| env.error(e))?,
                 };
                 env.push(Array::from(bytes.as_slice()));
@@ -792,33 +928,6 @@ impl SysOp {
                 match handle {
                     Handle::STDOUT => return Err(env.error("Cannot read from stdout")),
                     Handle::STDERR => return Err(env.error("Cannot read from stderr")),
-                    Handle::STDIN => {
-                        let mut is_string = false;
-                        let delim_bytes: Vec<u8> = match delim {
-                            Value::Num(arr) => arr.data.iter().map(|&x| x as u8).collect(),
-                            Value::Byte(arr) => arr.data.into(),
-                            Value::Char(arr) => {
-                                is_string = true;
-                                arr.data.iter().collect::<String>().into()
-                            }
-                            _ => return Err(env.error("Delimiter must be a string or byte array")),
-                        };
-                        let mut buffer = Vec::new();
-                        let stdin = stdin().lock();
-                        for byte in stdin.bytes() {
-                            let byte = byte.map_err(|e| env.error(e))?;
-                            buffer.push(byte);
-                            if buffer.ends_with(&delim_bytes) {
-                                break;
-                            }
-                        }
-                        if is_string {
-                            let s = String::from_utf8_lossy(&buffer).into_owned();
-                            env.push(s);
-                        } else {
-                            env.push(Array::from(buffer.as_slice()));
-                        }
-                    }
                     _ => match delim {
                         Value::Num(arr) => {
                             let delim: Vec<u8> = arr.data.iter().map(|&x| x as u8).collect();
@@ -863,7 +972,6 @@ impl SysOp {
                 };
                 match handle {
                     Handle::STDOUT => env
-                        .backend
                         .print_str_stdout(&String::from_utf8_lossy(&bytes))
                         .map_err(|e| env.error(e))?,
                     Handle::STDERR => env
@@ -1132,7 +1240,7 @@ impl SysOp {
                     .pop(1)?
                     .as_num(env, "Sleep time must be a number")?
                     .max(0.0);
-                env.backend.sleep(seconds).map_err(|e| env.error(e))?;
+                env.sleep(seconds).map_err(|e| env.error(e))?;
             }
             SysOp::TcpListen => {
                 let addr = env.pop(1)?.as_string(env, "Address must be a string")?;
@@ -1213,6 +1321,131 @@ impl SysOp {
                     .map_err(|e| env.error(e))?;
                 env.push(res);
             }
+            SysOp::UdpBind => {
+                let addr = env.pop(1)?.as_string(env, "Address must be a string")?;
+                let handle = env.backend.udp_bind(&addr).map_err(|e| env.error(e))?;
+                env.push(handle);
+            }
+            SysOp::UdpSend => {
+                let data = env.pop(1)?;
+                let addr = env.pop(2)?.as_string(env, "Address must be a string")?;
+                let handle = env
+                    .pop(3)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let bytes: Vec<u8> = match data {
+                    Value::Num(arr) => arr.data.iter().map(|&x| x as u8).collect(),
+                    Value::Byte(arr) => arr.data.into(),
+                    Value::Char(arr) => arr.data.iter().collect::<String>().into(),
+                    Value::Box(_) => return Err(env.error("Cannot send box array")),
+                };
+                env.backend
+                    .udp_send(handle, &addr, &bytes)
+                    .map_err(|e| env.error(e))?;
+            }
+            SysOp::UdpReceive => {
+                let max = env.pop(1)?.as_nat(env, "Count must be an integer")?;
+                let handle = env
+                    .pop(2)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let (bytes, addr) = env
+                    .backend
+                    .udp_receive(handle, max)
+                    .map_err(|e| env.error(e))?;
+                env.push(addr);
+                env.push(Array::from(bytes.as_slice()));
+            }
+            SysOp::UdpAddr => {
+                let handle = env
+                    .pop(1)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let addr = env.backend.udp_addr(handle).map_err(|e| env.error(e))?;
+                env.push(addr);
+            }
+            SysOp::UdpSetNonBlocking => {
+                let handle = env
+                    .pop(1)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                env.backend
+                    .udp_set_non_blocking(handle, true)
+                    .map_err(|e| env.error(e))?;
+            }
+            SysOp::UdpSetReadTimeout => {
+                let timeout = env.pop(1)?.as_num(env, "Timeout must be a number")?.abs();
+                let timeout = if timeout.is_infinite() {
+                    None
+                } else {
+                    Some(Duration::from_secs_f64(timeout))
+                };
+                let handle = env
+                    .pop(2)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                env.backend
+                    .udp_set_read_timeout(handle, timeout)
+                    .map_err(|e| env.error(e))?;
+            }
+            SysOp::UdpSetWriteTimeout => {
+                let timeout = env.pop(1)?.as_num(env, "Timeout must be a number")?.abs();
+                let timeout = if timeout.is_infinite() {
+                    None
+                } else {
+                    Some(Duration::from_secs_f64(timeout))
+                };
+                let handle = env
+                    .pop(2)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                env.backend
+                    .udp_set_write_timeout(handle, timeout)
+                    .map_err(|e| env.error(e))?;
+            }
+            SysOp::JsonEncode => {
+                let json = env.pop(1)?.to_json().map_err(|e| env.error(e))?;
+                env.push(json);
+            }
+            SysOp::JsonDecode => {
+                let json = env.pop(1)?.as_string(env, "JSON must be a string")?;
+                let value = Value::from_json(&json).map_err(|e| env.error(e))?;
+                env.push(value);
+            }
+            SysOp::FfiLoad => {
+                let path = env
+                    .pop(1)?
+                    .as_string(env, "Library path must be a string")?;
+                let handle = env.backend.ffi_load(&path).map_err(|e| env.error(e))?;
+                env.push(handle);
+            }
+            SysOp::Ffi => {
+                let decl = env
+                    .pop(1)?
+                    .as_string(env, "C function declaration must be a string")?;
+                let sig = FfiSignature::parse(&decl).map_err(|e| env.error(e))?;
+                let lib = env
+                    .pop(2)?
+                    .as_nat(env, "Library handle must be a natural number")?
+                    .into();
+                let mut args = ffi_args(&sig, env.pop(3)?, env)?;
+                let ret = env
+                    .backend
+                    .ffi_call(lib, &sig, &mut args)
+                    .map_err(|e| env.error(e))?;
+                let buffers: Vec<Value> = args
+                    .into_iter()
+                    .filter(FfiValue::is_array)
+                    .map(Value::from)
+                    .collect();
+                if buffers.is_empty() {
+                    env.push(ret);
+                } else {
+                    let ret = (ret != FfiValue::Void).then(|| ret.into());
+                    let results = ret.into_iter().chain(buffers).map(Boxed);
+                    env.push(Array::<Boxed>::from_iter(results));
+                }
+            }
             SysOp::Close => {
                 let handle = env
                     .pop(1)?
@@ -1251,6 +1484,67 @@ impl SysOp {
     }
 }
 
+/// Convert the arguments to [`SysOp::Ffi`] to the types of a C function's parameters
+fn ffi_args(sig: &FfiSignature, args: Value, env: &Uiua) -> UiuaResult<Vec<FfiValue>> {
+    let args: Vec<Value> = match args {
+        Value::Box(arr) if arr.rank() == 1 => arr.data.into_iter().map(Boxed::into_inner).collect(),
+        arg if sig.args.len() == 1 => vec![arg.unpacked()],
+        _ => return Err(env.error("FFI arguments must be a list of boxes")),
+    };
+    if args.len() != sig.args.len() {
+        return Err(env.error(format!(
+            "`{}` takes {} arguments, but {} were given",
+            sig.name,
+            sig.args.len(),
+            args.len()
+        )));
+    }
+    let nums = |arg: &Value| arg.as_nums(env, "FFI buffer argument must be a list of numbers");
+    sig.args
+        .iter()
+        .zip(args)
+        .map(|(ty, arg)| {
+            Ok(match ty {
+                FfiType::Void => FfiValue::Void,
+                FfiType::Int => FfiValue::Int(
+                    arg.as_int(env, "FFI int argument must be an integer")?
+                        .try_into()
+                        .map_err(|_| env.error("FFI int argument is out of range"))?,
+                ),
+                FfiType::Long => {
+                    FfiValue::Long(arg.as_int(env, "FFI long argument must be an integer")? as i64)
+                }
+                FfiType::Float => {
+                    FfiValue::Float(arg.as_num(env, "FFI float argument must be a number")? as f32)
+                }
+                FfiType::Double => {
+                    FfiValue::Double(arg.as_num(env, "FFI double argument must be a number")?)
+                }
+                FfiType::Ptr => {
+                    FfiValue::Ptr(arg.as_nat(env, "FFI pointer argument must be a natural number")?)
+                }
+                FfiType::CStr => {
+                    FfiValue::CStr(arg.as_string(env, "FFI string argument must be a string")?)
+                }
+                FfiType::Array(FfiElem::Byte) => FfiValue::Bytes(match &arg {
+                    Value::Char(_) => arg.as_string(env, "")?.into_bytes(),
+                    _ => arg.as_bytes(env, "FFI byte buffer argument must be a list of bytes")?,
+                }),
+                FfiType::Array(FfiElem::Int) => {
+                    FfiValue::Ints(nums(&arg)?.into_iter().map(|n| n as i32).collect())
+                }
+                FfiType::Array(FfiElem::Long) => {
+                    FfiValue::Longs(nums(&arg)?.into_iter().map(|n| n as i64).collect())
+                }
+                FfiType::Array(FfiElem::Float) => {
+                    FfiValue::Floats(nums(&arg)?.into_iter().map(|n| n as f32).collect())
+                }
+                FfiType::Array(FfiElem::Double) => FfiValue::Doubles(nums(&arg)?),
+            })
+        })
+        .collect()
+}
+
 fn value_to_command(value: &Value, env: &Uiua) -> UiuaResult<(String, Vec<String>)> {
     let mut strings = Vec::new();
     match value {
@@ -0,0 +1,342 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    io::SeekFrom,
+    path::Path,
+    sync::{
+        atomic::{self, AtomicU64},
+        Arc,
+    },
+};
+
+use parking_lot::Mutex;
+
+use crate::{Handle, SysBackend};
+
+/// A system backend with an in-memory filesystem
+///
+/// The filesystem is a tree of directories and files that lives entirely in memory.
+/// It can be seeded before running code and inspected afterwards, which makes it useful
+/// for testing Uiua code that works with files, or for running in environments without
+/// a real filesystem.
+///
+/// Output printed to stdout and stderr is captured and can be retrieved with
+/// [`MemoryFsSys::take_stdout`] and [`MemoryFsSys::take_stderr`].
+///
+/// ```
+/// use uiua::*;
+///
+/// let fs = MemoryFsSys::new().with_file("data/in.txt", "hello");
+/// let mut uiua = Uiua::with_backend(fs);
+/// uiua.load_str("&fwa \"data/out.txt\" ⇌ &fras \"data/in.txt\"").unwrap();
+///
+/// let fs = uiua.downcast_backend::<MemoryFsSys>().unwrap();
+/// assert_eq!(fs.file_string("data/out.txt").unwrap(), "olleh");
+///
+/// uiua.load_str("&fde \"data/in.txt\"\n⧻&fld \"data\"").unwrap();
+/// assert_eq!(uiua.pop_nat().unwrap(), 1);
+/// let fs = uiua.downcast_backend::<MemoryFsSys>().unwrap();
+/// assert_eq!(fs.paths(), ["data", "data/out.txt"]);
+/// ```
+pub struct MemoryFsSys {
+    entries: Mutex<BTreeMap<String, Entry>>,
+    trashed: Mutex<Vec<String>>,
+    streams: Mutex<HashMap<Handle, Stream>>,
+    next_handle: AtomicU64,
+    stdout: Mutex<String>,
+    stderr: Mutex<String>,
+}
+
+type FileData = Arc<Mutex<Vec<u8>>>;
+
+#[derive(Clone)]
+enum Entry {
+    Dir,
+    File(FileData),
+}
+
+struct Stream {
+    data: FileData,
+    pos: usize,
+}
+
+impl Default for MemoryFsSys {
+    fn default() -> Self {
+        let mut entries = BTreeMap::new();
+        entries.insert(String::new(), Entry::Dir);
+        Self {
+            entries: Mutex::new(entries),
+            trashed: Mutex::new(Vec::new()),
+            streams: Mutex::new(HashMap::new()),
+            next_handle: Handle::FIRST_UNRESERVED.0.into(),
+            stdout: Mutex::new(String::new()),
+            stderr: Mutex::new(String::new()),
+        }
+    }
+}
+
+/// Normalize a path into a key of the entry map
+///
+/// Absolute and relative paths refer to the same tree, so `/a/b`, `a/b`, and `./a/c/../b` are equivalent.
+fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+fn parent(key: &str) -> &str {
+    key.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+fn is_child_of(key: &str, dir: &str) -> bool {
+    dir.is_empty() && !key.is_empty()
+        || key.len() > dir.len() && key.starts_with(dir) && key.as_bytes()[dir.len()] == b'/'
+}
+
+impl MemoryFsSys {
+    /// Create a new backend with an empty filesystem
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add a file to the filesystem, creating its parent directories
+    pub fn with_file(self, path: &str, contents: impl Into<Vec<u8>>) -> Self {
+        self.add_file(path, contents);
+        self
+    }
+    /// Add a directory to the filesystem, creating its parent directories
+    pub fn with_dir(self, path: &str) -> Self {
+        self.add_dir(path);
+        self
+    }
+    /// Add a file to the filesystem, creating its parent directories
+    ///
+    /// If the file already exists, it is overwritten.
+    pub fn add_file(&self, path: &str, contents: impl Into<Vec<u8>>) {
+        let key = normalize(path);
+        self.add_dir(parent(&key));
+        let data = Arc::new(Mutex::new(contents.into()));
+        self.entries.lock().insert(key, Entry::File(data));
+    }
+    /// Add a directory to the filesystem, creating its parent directories
+    ///
+    /// Existing files along the path are left as they are.
+    pub fn add_dir(&self, path: &str) {
+        let key = normalize(path);
+        let mut entries = self.entries.lock();
+        let mut prefix = String::new();
+        for part in key.split('/').filter(|part| !part.is_empty()) {
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(part);
+            entries.entry(prefix.clone()).or_insert(Entry::Dir);
+        }
+    }
+    /// Get the contents of a file
+    pub fn file(&self, path: &str) -> Option<Vec<u8>> {
+        match self.entries.lock().get(&normalize(path))? {
+            Entry::File(data) => Some(data.lock().clone()),
+            Entry::Dir => None,
+        }
+    }
+    /// Get the contents of a file as a string
+    pub fn file_string(&self, path: &str) -> Option<String> {
+        self.file(path)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+    }
+    /// Check if a path is a directory
+    pub fn is_dir(&self, path: &str) -> bool {
+        matches!(self.entries.lock().get(&normalize(path)), Some(Entry::Dir))
+    }
+    /// Get the paths of all files and directories, in sorted order
+    pub fn paths(&self) -> Vec<String> {
+        (self.entries.lock().keys())
+            .filter(|key| !key.is_empty())
+            .cloned()
+            .collect()
+    }
+    /// Get the paths of all files and directories that have been moved to the trash
+    pub fn trashed(&self) -> Vec<String> {
+        self.trashed.lock().clone()
+    }
+    /// Take everything that has been printed to stdout
+    pub fn take_stdout(&self) -> String {
+        std::mem::take(&mut *self.stdout.lock())
+    }
+    /// Take everything that has been printed to stderr
+    pub fn take_stderr(&self) -> String {
+        std::mem::take(&mut *self.stderr.lock())
+    }
+    /// Move the position of an open stream
+    ///
+    /// Returns the new position from the start of the file.
+    pub fn seek(&self, handle: Handle, pos: SeekFrom) -> Result<u64, String> {
+        let mut streams = self.streams.lock();
+        let stream = streams.get_mut(&handle).ok_or("Invalid file handle")?;
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => (stream.data.lock().len() as u64).checked_add_signed(offset),
+            SeekFrom::Current(offset) => (stream.pos as u64).checked_add_signed(offset),
+        }
+        .ok_or("Cannot seek before the start of a file")?;
+        stream.pos = new_pos as usize;
+        Ok(new_pos)
+    }
+    fn open(&self, data: FileData) -> Handle {
+        let handle = Handle(self.next_handle.fetch_add(1, atomic::Ordering::Relaxed));
+        self.streams.lock().insert(handle, Stream { data, pos: 0 });
+        handle
+    }
+    fn remove(&self, path: &str) -> Result<String, String> {
+        let key = normalize(path);
+        if key.is_empty() {
+            return Err("Cannot delete the root directory".into());
+        }
+        let mut entries = self.entries.lock();
+        if entries.remove(&key).is_none() {
+            return Err(format!("{path} does not exist"));
+        }
+        entries.retain(|k, _| !is_child_of(k, &key));
+        Ok(key)
+    }
+}
+
+impl SysBackend for MemoryFsSys {
+    fn any(&self) -> &dyn Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn print_str_stdout(&self, s: &str) -> Result<(), String> {
+        self.stdout.lock().push_str(s);
+        Ok(())
+    }
+    fn print_str_stderr(&self, s: &str) -> Result<(), String> {
+        self.stderr.lock().push_str(s);
+        Ok(())
+    }
+    fn print_str_trace(&self, s: &str) {
+        self.stderr.lock().push_str(s);
+    }
+    fn file_exists(&self, path: &str) -> bool {
+        self.entries.lock().contains_key(&normalize(path))
+    }
+    fn list_dir(&self, path: &str) -> Result<Vec<String>, String> {
+        let key = normalize(path);
+        let entries = self.entries.lock();
+        match entries.get(&key) {
+            Some(Entry::Dir) => {}
+            Some(Entry::File(_)) => return Err(format!("{path} is not a directory")),
+            None => return Err(format!("{path} does not exist")),
+        }
+        Ok(entries
+            .keys()
+            .filter(|k| is_child_of(k, &key) && parent(k) == key)
+            .map(|k| {
+                let name = &k[k.rfind('/').map_or(0, |i| i + 1)..];
+                Path::new(path).join(name).to_string_lossy().into_owned()
+            })
+            .collect())
+    }
+    fn is_file(&self, path: &str) -> Result<bool, String> {
+        match self.entries.lock().get(&normalize(path)) {
+            Some(entry) => Ok(matches!(entry, Entry::File(_))),
+            None => Err(format!("{path} does not exist")),
+        }
+    }
+    fn delete(&self, path: &str) -> Result<(), String> {
+        self.remove(path).map(drop)
+    }
+    fn trash(&self, path: &str) -> Result<(), String> {
+        let key = self.remove(path)?;
+        self.trashed.lock().push(key);
+        Ok(())
+    }
+    fn read(&self, handle: Handle, count: usize) -> Result<Vec<u8>, String> {
+        let mut streams = self.streams.lock();
+        let stream = streams.get_mut(&handle).ok_or("Invalid file handle")?;
+        let data = stream.data.lock();
+        let start = stream.pos.min(data.len());
+        let end = start.saturating_add(count).min(data.len());
+        let bytes = data[start..end].to_vec();
+        drop(data);
+        stream.pos = end;
+        Ok(bytes)
+    }
+    fn write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        let mut streams = self.streams.lock();
+        let stream = streams.get_mut(&handle).ok_or("Invalid file handle")?;
+        let mut data = stream.data.lock();
+        let end = stream.pos + contents.len();
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[stream.pos..end].copy_from_slice(contents);
+        drop(data);
+        stream.pos = end;
+        Ok(())
+    }
+    fn create_file(&self, path: &str) -> Result<Handle, String> {
+        let key = normalize(path);
+        let mut entries = self.entries.lock();
+        match entries.get(parent(&key)) {
+            Some(Entry::Dir) => {}
+            _ => return Err(format!("Parent directory of {path} does not exist")),
+        }
+        let data = match entries.get(&key) {
+            Some(Entry::Dir) => return Err(format!("{path} is a directory")),
+            Some(Entry::File(data)) => {
+                data.lock().clear();
+                data.clone()
+            }
+            None => {
+                let data = FileData::default();
+                entries.insert(key, Entry::File(data.clone()));
+                data
+            }
+        };
+        drop(entries);
+        Ok(self.open(data))
+    }
+    fn open_file(&self, path: &str) -> Result<Handle, String> {
+        let data = match self.entries.lock().get(&normalize(path)) {
+            Some(Entry::File(data)) => data.clone(),
+            Some(Entry::Dir) => return Err(format!("{path} is a directory")),
+            None => return Err(format!("{path} does not exist")),
+        };
+        Ok(self.open(data))
+    }
+    fn file_read_all(&self, path: &str) -> Result<Vec<u8>, String> {
+        match self.entries.lock().get(&normalize(path)) {
+            Some(Entry::File(data)) => Ok(data.lock().clone()),
+            Some(Entry::Dir) => Err(format!("{path} is a directory")),
+            None => Err(format!("{path} does not exist")),
+        }
+    }
+    fn close(&self, handle: Handle) -> Result<(), String> {
+        self.streams
+            .lock()
+            .remove(&handle)
+            .map(drop)
+            .ok_or_else(|| "Invalid stream handle".into())
+    }
+}
+
+#[test]
+fn add_dir() {
+    let fs = MemoryFsSys::new()
+        .with_dir("ünï/cödé")
+        .with_file("a/file", "data")
+        .with_dir("a/file");
+    assert!(fs.is_dir("ünï") && fs.is_dir("ünï/cödé"));
+    assert_eq!(fs.file_string("a/file").as_deref(), Some("data"));
+}
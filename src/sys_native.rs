@@ -2,7 +2,7 @@ use std::{
     any::Any,
     env,
     fs::{self, File},
-    io::{stderr, stdin, stdout, Read, Write},
+    io::{self, stderr, stdin, stdout, BufRead, Read, Write},
     net::*,
     path::Path,
     process::Command,
@@ -28,7 +28,10 @@ struct GlobalNativeSys {
     files: DashMap<Handle, Buffered<File>>,
     tcp_listeners: DashMap<Handle, TcpListener>,
     tcp_sockets: DashMap<Handle, Buffered<TcpStream>>,
+    udp_sockets: DashMap<Handle, UdpSocket>,
     hostnames: DashMap<Handle, String>,
+    #[cfg(feature = "ffi")]
+    libraries: DashMap<Handle, libloading::Library>,
     #[cfg(feature = "audio")]
     audio_stream_time: parking_lot::Mutex<Option<f64>>,
     #[cfg(feature = "audio")]
@@ -49,7 +52,10 @@ impl Default for GlobalNativeSys {
             files: DashMap::new(),
             tcp_listeners: DashMap::new(),
             tcp_sockets: DashMap::new(),
+            udp_sockets: DashMap::new(),
             hostnames: DashMap::new(),
+            #[cfg(feature = "ffi")]
+            libraries: DashMap::new(),
             #[cfg(feature = "audio")]
             audio_stream_time: parking_lot::Mutex::new(None),
             #[cfg(feature = "audio")]
@@ -63,10 +69,13 @@ impl GlobalNativeSys {
     fn new_handle(&self) -> Handle {
         for _ in 0..u64::MAX {
             let handle = Handle(self.next_handle.fetch_add(1, atomic::Ordering::Relaxed));
-            if !self.files.contains_key(&handle)
-                && !self.tcp_listeners.contains_key(&handle)
-                && !self.tcp_sockets.contains_key(&handle)
-            {
+            let taken = self.files.contains_key(&handle)
+                || self.tcp_listeners.contains_key(&handle)
+                || self.tcp_sockets.contains_key(&handle)
+                || self.udp_sockets.contains_key(&handle);
+            #[cfg(feature = "ffi")]
+            let taken = taken || self.libraries.contains_key(&handle);
+            if !taken {
                 return handle;
             }
         }
@@ -102,6 +111,32 @@ pub fn set_audio_stream_time_port(port: u16) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Read from a buffered reader until a delimiter is reached
+fn read_until_delim(reader: &mut impl BufRead, delim: &[u8]) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    let Some(&last) = delim.last() else {
+        let mut byte = [0];
+        let n = reader.read(&mut byte)?;
+        buffer.extend_from_slice(&byte[..n]);
+        return Ok(buffer);
+    };
+    while reader.read_until(last, &mut buffer)? > 0 && !buffer.ends_with(delim) {}
+    Ok(buffer)
+}
+
+/// Read from a stream until a delimiter is reached, switching it to reading if it was writing
+fn read_until_buffered<T: Read + Write>(
+    stream: &mut Buffered<T>,
+    delim: &[u8],
+) -> io::Result<Vec<u8>> {
+    if !stream.is_reader() {
+        // An empty read flushes the writer and switches to reading
+        let read = stream.read(&mut [])?;
+        debug_assert_eq!(read, 0);
+    }
+    read_until_delim(stream.get_bufreader_mut().unwrap(), delim)
+}
+
 impl SysBackend for NativeSys {
     fn any(&self) -> &dyn Any {
         self
@@ -195,6 +230,15 @@ impl SysBackend for NativeSys {
         trash::delete(path).map_err(|e| e.to_string())
     }
     fn read(&self, handle: Handle, len: usize) -> Result<Vec<u8>, String> {
+        if handle == Handle::STDIN {
+            let mut buf = Vec::new();
+            stdin()
+                .lock()
+                .take(len as u64)
+                .read_to_end(&mut buf)
+                .map_err(|e| e.to_string())?;
+            return Ok(buf);
+        }
         Ok(match NATIVE_SYS.get_stream(handle)? {
             SysStream::File(mut file) => {
                 let mut buf = Vec::new();
@@ -215,6 +259,17 @@ impl SysBackend for NativeSys {
             }
         })
     }
+    fn read_until(&self, handle: Handle, delim: &[u8]) -> Result<Vec<u8>, String> {
+        if handle == Handle::STDIN {
+            return read_until_delim(&mut stdin().lock(), delim).map_err(|e| e.to_string());
+        }
+        let res = match NATIVE_SYS.get_stream(handle)? {
+            SysStream::File(mut file) => read_until_buffered(&mut file, delim),
+            SysStream::TcpListener(_) => return Err("Cannot read from a tcp listener".to_string()),
+            SysStream::TcpSocket(mut socket) => read_until_buffered(&mut socket, delim),
+        };
+        res.map_err(|e| e.to_string())
+    }
     fn write(&self, handle: Handle, conts: &[u8]) -> Result<(), String> {
         let mut conts = conts;
         let colored;
@@ -429,11 +484,103 @@ impl SysBackend for NativeSys {
             .map_err(|e| e.to_string())?;
         Ok(())
     }
+    fn udp_bind(&self, addr: &str) -> Result<Handle, String> {
+        let handle = NATIVE_SYS.new_handle();
+        let socket = UdpSocket::bind(addr).map_err(|e| e.to_string())?;
+        NATIVE_SYS.udp_sockets.insert(handle, socket);
+        Ok(handle)
+    }
+    fn udp_send(&self, handle: Handle, addr: &str, bytes: &[u8]) -> Result<(), String> {
+        let socket = NATIVE_SYS
+            .udp_sockets
+            .get(&handle)
+            .ok_or_else(|| "Invalid udp socket handle".to_string())?;
+        let sent = socket.send_to(bytes, addr).map_err(|e| e.to_string())?;
+        if sent < bytes.len() {
+            return Err(format!("Only sent {sent} of {} bytes", bytes.len()));
+        }
+        Ok(())
+    }
+    fn udp_receive(&self, handle: Handle, max: usize) -> Result<(Vec<u8>, String), String> {
+        let socket = NATIVE_SYS
+            .udp_sockets
+            .get(&handle)
+            .ok_or_else(|| "Invalid udp socket handle".to_string())?;
+        let mut buffer = vec![0; max];
+        let (len, addr) = socket.recv_from(&mut buffer).map_err(|e| e.to_string())?;
+        buffer.truncate(len);
+        Ok((buffer, addr.to_string()))
+    }
+    fn udp_addr(&self, handle: Handle) -> Result<String, String> {
+        let socket = NATIVE_SYS
+            .udp_sockets
+            .get(&handle)
+            .ok_or_else(|| "Invalid udp socket handle".to_string())?;
+        Ok(socket.local_addr().map_err(|e| e.to_string())?.to_string())
+    }
+    fn udp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        let socket = NATIVE_SYS
+            .udp_sockets
+            .get(&handle)
+            .ok_or_else(|| "Invalid udp socket handle".to_string())?;
+        socket
+            .set_nonblocking(non_blocking)
+            .map_err(|e| e.to_string())
+    }
+    fn udp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        let socket = NATIVE_SYS
+            .udp_sockets
+            .get(&handle)
+            .ok_or_else(|| "Invalid udp socket handle".to_string())?;
+        socket.set_read_timeout(timeout).map_err(|e| e.to_string())
+    }
+    fn udp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        let socket = NATIVE_SYS
+            .udp_sockets
+            .get(&handle)
+            .ok_or_else(|| "Invalid udp socket handle".to_string())?;
+        socket.set_write_timeout(timeout).map_err(|e| e.to_string())
+    }
+    #[cfg(feature = "ffi")]
+    fn ffi_load(&self, path: &str) -> Result<Handle, String> {
+        // Safety: loading a library runs its initialization code, which is
+        // no different from running any other native code the user asks for
+        let lib = unsafe { libloading::Library::new(path) }.map_err(|e| e.to_string())?;
+        let handle = NATIVE_SYS.new_handle();
+        NATIVE_SYS.libraries.insert(handle, lib);
+        Ok(handle)
+    }
+    #[cfg(feature = "ffi")]
+    fn ffi_call(
+        &self,
+        lib: Handle,
+        sig: &crate::FfiSignature,
+        args: &mut [crate::FfiValue],
+    ) -> Result<crate::FfiValue, String> {
+        let lib = NATIVE_SYS
+            .libraries
+            .get(&lib)
+            .ok_or("Invalid library handle")?;
+        crate::ffi::call(&lib, sig, args)
+    }
     fn close(&self, handle: Handle) -> Result<(), String> {
+        #[cfg(feature = "ffi")]
+        if NATIVE_SYS.libraries.remove(&handle).is_some() {
+            return Ok(());
+        }
         if NATIVE_SYS.files.remove(&handle).is_some()
             || NATIVE_SYS.tcp_listeners.remove(&handle).is_some()
             || (NATIVE_SYS.tcp_sockets.remove(&handle).is_some()
                 && NATIVE_SYS.hostnames.remove(&handle).is_some())
+            || NATIVE_SYS.udp_sockets.remove(&handle).is_some()
         {
             Ok(())
         } else {
@@ -607,3 +754,16 @@ fn check_http(mut request: String, hostname: &str) -> Result<String, String> {
 
     Ok(request)
 }
+
+#[test]
+fn read_until() {
+    let path = env::temp_dir().join(format!("uiua-read-until-{}.txt", std::process::id()));
+    fs::write(&path, "one\r\ntwo\r\nthree").unwrap();
+    let handle = NativeSys.open_file(path.to_str().unwrap()).unwrap();
+    assert_eq!(NativeSys.read_until(handle, b"\r\n").unwrap(), b"one\r\n");
+    assert_eq!(NativeSys.read(handle, 2).unwrap(), b"tw");
+    assert_eq!(NativeSys.read_until(handle, b"\r\n").unwrap(), b"o\r\n");
+    assert_eq!(NativeSys.read_until(handle, b"\r\n").unwrap(), b"three");
+    NativeSys.close(handle).unwrap();
+    fs::remove_file(path).unwrap();
+}
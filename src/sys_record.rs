@@ -0,0 +1,870 @@
+use std::{
+    any::Any,
+    io::{self, Write},
+    time::Duration,
+};
+
+use image::DynamicImage;
+use parking_lot::Mutex;
+use serde_json::{json, Value as Json};
+
+use crate::{AudioStreamFn, FfiSignature, FfiValue, Handle, NativeSys, SysBackend, UiuaError};
+
+/// A system backend that wraps another one and records every call that touches the outside world
+///
+/// Each call is logged as a line of JSON with the name of the [`SysBackend`] method,
+/// its arguments, and its result. The log can be replayed with [`ReplaySys`].
+///
+/// Printing to stdout and stderr is passed through but not recorded.
+/// FFI calls cannot be recorded and always fail.
+/// Programs that use the backend from multiple threads may not replay in the same order.
+///
+/// ```
+/// use uiua::*;
+///
+/// let fs = MemoryFsSys::new().with_file("in.txt", "hello");
+/// let mut uiua = Uiua::with_backend(RecordingSys::new(fs));
+/// uiua.load_str("⇌&fras \"in.txt\"").unwrap();
+/// let log = uiua.downcast_backend::<RecordingSys>().unwrap().log();
+///
+/// let mut uiua = Uiua::with_backend(ReplaySys::new(&log).unwrap());
+/// uiua.load_str("⇌&fras \"in.txt\"").unwrap();
+/// assert_eq!(uiua.pop_string().unwrap(), "olleh");
+/// uiua.downcast_backend::<ReplaySys>().unwrap().finish().unwrap();
+/// ```
+pub struct RecordingSys {
+    backend: Box<dyn SysBackend>,
+    log: Mutex<RecordLog>,
+}
+
+struct RecordLog {
+    entries: String,
+    writer: Option<Box<dyn Write + Send>>,
+    error: Option<io::Error>,
+}
+
+impl RecordingSys {
+    /// Create a new recording backend that wraps another backend
+    pub fn new(backend: impl SysBackend) -> Self {
+        Self {
+            backend: Box::new(backend),
+            log: Mutex::new(RecordLog {
+                entries: String::new(),
+                writer: None,
+                error: None,
+            }),
+        }
+    }
+    /// Also write each entry to a writer as it is recorded
+    ///
+    /// This makes sure that the log survives the program crashing.
+    pub fn with_writer(self, writer: impl Write + Send + 'static) -> Self {
+        self.log.lock().writer = Some(Box::new(writer));
+        self
+    }
+    /// Get the log recorded so far
+    pub fn log(&self) -> String {
+        self.log.lock().entries.clone()
+    }
+    /// Get the wrapped backend
+    pub fn backend(&self) -> &dyn SysBackend {
+        &*self.backend
+    }
+    /// Flush the writer and return the first error encountered while writing to it
+    pub fn finish(&self) -> io::Result<()> {
+        let mut log = self.log.lock();
+        if let Some(error) = log.error.take() {
+            return Err(error);
+        }
+        match &mut log.writer {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+    fn record<T: Record>(&self, op: &str, args: Json, result: T) -> T {
+        let entry = json!({ "op": op, "args": args, "result": result.to_json() }).to_string();
+        let mut log = self.log.lock();
+        let RecordLog {
+            entries,
+            writer,
+            error,
+        } = &mut *log;
+        entries.push_str(&entry);
+        entries.push('\n');
+        if let (Some(writer), None) = (writer, &error) {
+            if let Err(e) = writeln!(writer, "{entry}").and_then(|_| writer.flush()) {
+                *error = Some(e);
+            }
+        }
+        result
+    }
+}
+
+/// A system backend that replays a log made by [`RecordingSys`]
+///
+/// Every call must match the next recorded call's method and arguments, and gets the recorded result.
+/// Nothing outside the program is touched, except that printing to stdout and stderr still works.
+///
+/// Once a call diverges from the log, it and every later fallible call fail with an error describing the divergence.
+/// [`ReplaySys::finish`] reports any divergence, including the program making fewer calls than were recorded.
+///
+/// ```
+/// use uiua::*;
+///
+/// let log = r#"{"op":"file_exists","args":["a.txt"],"result":true}"#;
+/// let mut uiua = Uiua::with_backend(ReplaySys::new(log).unwrap());
+/// uiua.load_str("&fe \"a.txt\"").unwrap();
+/// assert!(uiua.pop_bool().unwrap());
+/// assert!(uiua.downcast_backend::<ReplaySys>().unwrap().finish().is_ok());
+///
+/// let mut uiua = Uiua::with_backend(ReplaySys::new(log).unwrap());
+/// uiua.load_str("&fe \"b.txt\"").unwrap();
+/// assert!(uiua.downcast_backend::<ReplaySys>().unwrap().finish().is_err());
+/// ```
+pub struct ReplaySys {
+    entries: Vec<Entry>,
+    state: Mutex<ReplayState>,
+}
+
+struct Entry {
+    op: String,
+    args: Json,
+    result: Json,
+}
+
+#[derive(Default)]
+struct ReplayState {
+    next: usize,
+    divergence: Option<String>,
+}
+
+impl ReplaySys {
+    /// Create a new replaying backend from a log
+    pub fn new(log: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        for (i, line) in log.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut json: Json = serde_json::from_str(line)
+                .map_err(|e| format!("Invalid replay log entry on line {}: {e}", i + 1))?;
+            let op = json
+                .get("op")
+                .and_then(Json::as_str)
+                .ok_or_else(|| format!("Replay log entry on line {} has no op", i + 1))?
+                .to_string();
+            entries.push(Entry {
+                op,
+                args: json["args"].take(),
+                result: json["result"].take(),
+            });
+        }
+        Ok(Self {
+            entries,
+            state: Mutex::new(ReplayState::default()),
+        })
+    }
+    /// Check that the program made exactly the recorded calls
+    pub fn finish(&self) -> Result<(), String> {
+        let state = self.state.lock();
+        if let Some(divergence) = &state.divergence {
+            return Err(divergence.clone());
+        }
+        if state.next < self.entries.len() {
+            let entry = &self.entries[state.next];
+            return Err(format!(
+                "Replay diverged at call {}: the log has {}, but the program finished",
+                state.next + 1,
+                describe(&entry.op, &entry.args)
+            ));
+        }
+        Ok(())
+    }
+    fn replay<T: Replay>(&self, op: &str, args: Json) -> T {
+        let mut state = self.state.lock();
+        if let Some(divergence) = &state.divergence {
+            return T::diverged(divergence.clone());
+        }
+        let n = state.next + 1;
+        let divergence = match self.entries.get(state.next) {
+            Some(entry) if entry.op == op && entry.args == args => {
+                state.next += 1;
+                match T::from_json(&entry.result) {
+                    Some(result) => return result,
+                    None => format!("Replay log entry {n} has an invalid result for {op}"),
+                }
+            }
+            Some(entry) => format!(
+                "Replay diverged at call {n}: the log has {}, but the program called {}",
+                describe(&entry.op, &entry.args),
+                describe(op, &args)
+            ),
+            None => format!(
+                "Replay diverged at call {n}: the log has ended, but the program called {}",
+                describe(op, &args)
+            ),
+        };
+        state.divergence = Some(divergence.clone());
+        T::diverged(divergence)
+    }
+}
+
+fn describe(op: &str, args: &Json) -> String {
+    let args = match args {
+        Json::Array(args) => args
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        args => args.to_string(),
+    };
+    format!("{op}({args})")
+}
+
+/// A value that can be stored in a recording
+trait Record: Sized {
+    fn to_json(&self) -> Json;
+    fn from_json(json: &Json) -> Option<Self>;
+}
+
+/// A value that can be returned from a replayed call
+trait Replay: Record {
+    /// The value to return once the replay has diverged
+    fn diverged(message: String) -> Self;
+}
+
+impl Record for () {
+    fn to_json(&self) -> Json {
+        Json::Null
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        json.is_null().then_some(())
+    }
+}
+
+impl Record for bool {
+    fn to_json(&self) -> Json {
+        (*self).into()
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        json.as_bool()
+    }
+}
+
+impl Record for u32 {
+    fn to_json(&self) -> Json {
+        (*self).into()
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        json.as_u64()?.try_into().ok()
+    }
+}
+
+impl Record for i32 {
+    fn to_json(&self) -> Json {
+        (*self).into()
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        json.as_i64()?.try_into().ok()
+    }
+}
+
+impl Record for u64 {
+    fn to_json(&self) -> Json {
+        (*self).into()
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        json.as_u64()
+    }
+}
+
+/// JSON has no NaN or infinity, so non-finite numbers are stored as strings
+fn f64_json(n: f64) -> Json {
+    if n.is_finite() {
+        n.into()
+    } else {
+        n.to_string().into()
+    }
+}
+
+impl Record for f64 {
+    fn to_json(&self) -> Json {
+        f64_json(*self)
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        match json {
+            Json::String(s) => s.parse().ok(),
+            json => json.as_f64(),
+        }
+    }
+}
+
+impl Record for usize {
+    fn to_json(&self) -> Json {
+        (*self).into()
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        json.as_u64()?.try_into().ok()
+    }
+}
+
+impl Record for Handle {
+    fn to_json(&self) -> Json {
+        self.0.into()
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        json.as_u64().map(Handle)
+    }
+}
+
+impl Record for String {
+    fn to_json(&self) -> Json {
+        self.as_str().into()
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        json.as_str().map(Into::into)
+    }
+}
+
+/// Bytes are stored as a string if they are valid UTF-8 so that logs are readable
+fn bytes_json(bytes: &[u8]) -> Json {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.into(),
+        Err(_) => bytes.into(),
+    }
+}
+
+impl Record for Vec<u8> {
+    fn to_json(&self) -> Json {
+        bytes_json(self)
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        match json {
+            Json::String(s) => Some(s.as_bytes().to_vec()),
+            Json::Array(bytes) => bytes.iter().map(|b| b.as_u64()?.try_into().ok()).collect(),
+            _ => None,
+        }
+    }
+}
+
+impl Record for Vec<String> {
+    fn to_json(&self) -> Json {
+        self.as_slice().into()
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        json.as_array()?.iter().map(String::from_json).collect()
+    }
+}
+
+impl Record for Option<String> {
+    fn to_json(&self) -> Json {
+        self.as_deref().into()
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        match json {
+            Json::Null => Some(None),
+            json => String::from_json(json).map(Some),
+        }
+    }
+}
+
+impl<A: Record, B: Record> Record for (A, B) {
+    fn to_json(&self) -> Json {
+        json!([self.0.to_json(), self.1.to_json()])
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        match json.as_array()?.as_slice() {
+            [a, b] => Some((A::from_json(a)?, B::from_json(b)?)),
+            _ => None,
+        }
+    }
+}
+
+impl<A: Record, B: Record, C: Record> Record for (A, B, C) {
+    fn to_json(&self) -> Json {
+        json!([self.0.to_json(), self.1.to_json(), self.2.to_json()])
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        match json.as_array()?.as_slice() {
+            [a, b, c] => Some((A::from_json(a)?, B::from_json(b)?, C::from_json(c)?)),
+            _ => None,
+        }
+    }
+}
+
+impl<T: Record> Record for Result<T, String> {
+    fn to_json(&self) -> Json {
+        match self {
+            Ok(value) => json!({ "ok": value.to_json() }),
+            Err(error) => json!({ "err": error }),
+        }
+    }
+    fn from_json(json: &Json) -> Option<Self> {
+        if let Some(value) = json.get("ok") {
+            T::from_json(value).map(Ok)
+        } else {
+            json.get("err")?.as_str().map(|e| Err(e.into()))
+        }
+    }
+}
+
+impl<T: Record> Replay for Result<T, String> {
+    fn diverged(message: String) -> Self {
+        Err(message)
+    }
+}
+
+impl Replay for bool {
+    fn diverged(_: String) -> Self {
+        false
+    }
+}
+
+impl Replay for u32 {
+    fn diverged(_: String) -> Self {
+        44100
+    }
+}
+
+impl Replay for u64 {
+    fn diverged(_: String) -> Self {
+        0
+    }
+}
+
+impl Replay for f64 {
+    fn diverged(_: String) -> Self {
+        0.0
+    }
+}
+
+impl Replay for Option<String> {
+    fn diverged(_: String) -> Self {
+        None
+    }
+}
+
+fn timeout_json(timeout: Option<Duration>) -> Json {
+    timeout.map(|timeout| timeout.as_secs_f64()).into()
+}
+
+const FFI_ERROR: &str = "FFI calls cannot be recorded or replayed";
+
+impl SysBackend for RecordingSys {
+    fn any(&self) -> &dyn Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn save_error_color(&self, error: &UiuaError) {
+        self.backend.save_error_color(error)
+    }
+    fn print_str_stdout(&self, s: &str) -> Result<(), String> {
+        self.backend.print_str_stdout(s)
+    }
+    fn print_str_stderr(&self, s: &str) -> Result<(), String> {
+        self.backend.print_str_stderr(s)
+    }
+    fn print_str_trace(&self, s: &str) {
+        self.backend.print_str_trace(s)
+    }
+    fn scan_line_stdin(&self) -> Result<Option<String>, String> {
+        let res = self.backend.scan_line_stdin();
+        self.record("scan_line_stdin", json!([]), res)
+    }
+    fn set_raw_mode(&self, raw_mode: bool) -> Result<(), String> {
+        let res = self.backend.set_raw_mode(raw_mode);
+        self.record("set_raw_mode", json!([raw_mode]), res)
+    }
+    fn var(&self, name: &str) -> Option<String> {
+        self.record("var", json!([name]), self.backend.var(name))
+    }
+    fn term_size(&self) -> Result<(usize, usize), String> {
+        self.record("term_size", json!([]), self.backend.term_size())
+    }
+    fn file_exists(&self, path: &str) -> bool {
+        self.record("file_exists", json!([path]), self.backend.file_exists(path))
+    }
+    fn list_dir(&self, path: &str) -> Result<Vec<String>, String> {
+        self.record("list_dir", json!([path]), self.backend.list_dir(path))
+    }
+    fn is_file(&self, path: &str) -> Result<bool, String> {
+        self.record("is_file", json!([path]), self.backend.is_file(path))
+    }
+    fn delete(&self, path: &str) -> Result<(), String> {
+        self.record("delete", json!([path]), self.backend.delete(path))
+    }
+    fn trash(&self, path: &str) -> Result<(), String> {
+        self.record("trash", json!([path]), self.backend.trash(path))
+    }
+    fn read(&self, handle: Handle, count: usize) -> Result<Vec<u8>, String> {
+        let res = self.backend.read(handle, count);
+        self.record("read", json!([handle.0, count]), res)
+    }
+    fn read_until(&self, handle: Handle, delim: &[u8]) -> Result<Vec<u8>, String> {
+        let res = self.backend.read_until(handle, delim);
+        self.record("read_until", json!([handle.0, bytes_json(delim)]), res)
+    }
+    fn write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        let res = self.backend.write(handle, contents);
+        self.record("write", json!([handle.0, bytes_json(contents)]), res)
+    }
+    fn create_file(&self, path: &str) -> Result<Handle, String> {
+        self.record("create_file", json!([path]), self.backend.create_file(path))
+    }
+    fn open_file(&self, path: &str) -> Result<Handle, String> {
+        self.record("open_file", json!([path]), self.backend.open_file(path))
+    }
+    fn file_read_all(&self, path: &str) -> Result<Vec<u8>, String> {
+        let res = self.backend.file_read_all(path);
+        self.record("file_read_all", json!([path]), res)
+    }
+    fn file_write_all(&self, path: &str, contents: &[u8]) -> Result<(), String> {
+        let res = self.backend.file_write_all(path, contents);
+        self.record("file_write_all", json!([path, bytes_json(contents)]), res)
+    }
+    fn sleep(&self, seconds: f64) -> Result<(), String> {
+        self.record(
+            "sleep",
+            json!([f64_json(seconds)]),
+            self.backend.sleep(seconds),
+        )
+    }
+    fn now(&self) -> f64 {
+        self.record("now", json!([]), self.backend.now())
+    }
+    fn random_seed(&self) -> u64 {
+        self.record("random_seed", json!([]), self.backend.random_seed())
+    }
+    fn show_image(&self, image: DynamicImage) -> Result<(), String> {
+        let args = json!([image.width(), image.height()]);
+        self.record("show_image", args, self.backend.show_image(image))
+    }
+    fn show_gif(&self, gif_bytes: Vec<u8>) -> Result<(), String> {
+        let args = json!([gif_bytes.len()]);
+        self.record("show_gif", args, self.backend.show_gif(gif_bytes))
+    }
+    fn play_audio(&self, wave_bytes: Vec<u8>) -> Result<(), String> {
+        let args = json!([wave_bytes.len()]);
+        self.record("play_audio", args, self.backend.play_audio(wave_bytes))
+    }
+    fn audio_sample_rate(&self) -> u32 {
+        let rate = self.backend.audio_sample_rate();
+        self.record("audio_sample_rate", json!([]), rate)
+    }
+    fn stream_audio(&self, f: AudioStreamFn) -> Result<(), String> {
+        self.record("stream_audio", json!([]), self.backend.stream_audio(f))
+    }
+    fn tcp_listen(&self, addr: &str) -> Result<Handle, String> {
+        self.record("tcp_listen", json!([addr]), self.backend.tcp_listen(addr))
+    }
+    fn tcp_accept(&self, handle: Handle) -> Result<Handle, String> {
+        let res = self.backend.tcp_accept(handle);
+        self.record("tcp_accept", json!([handle.0]), res)
+    }
+    fn tcp_connect(&self, addr: &str) -> Result<Handle, String> {
+        self.record("tcp_connect", json!([addr]), self.backend.tcp_connect(addr))
+    }
+    fn tcp_addr(&self, handle: Handle) -> Result<String, String> {
+        self.record("tcp_addr", json!([handle.0]), self.backend.tcp_addr(handle))
+    }
+    fn tcp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        let res = self.backend.tcp_set_non_blocking(handle, non_blocking);
+        self.record("tcp_set_non_blocking", json!([handle.0, non_blocking]), res)
+    }
+    fn tcp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        let res = self.backend.tcp_set_read_timeout(handle, timeout);
+        let args = json!([handle.0, timeout_json(timeout)]);
+        self.record("tcp_set_read_timeout", args, res)
+    }
+    fn tcp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        let res = self.backend.tcp_set_write_timeout(handle, timeout);
+        let args = json!([handle.0, timeout_json(timeout)]);
+        self.record("tcp_set_write_timeout", args, res)
+    }
+    fn udp_bind(&self, addr: &str) -> Result<Handle, String> {
+        self.record("udp_bind", json!([addr]), self.backend.udp_bind(addr))
+    }
+    fn udp_send(&self, handle: Handle, addr: &str, bytes: &[u8]) -> Result<(), String> {
+        let res = self.backend.udp_send(handle, addr, bytes);
+        self.record("udp_send", json!([handle.0, addr, bytes_json(bytes)]), res)
+    }
+    fn udp_receive(&self, handle: Handle, max: usize) -> Result<(Vec<u8>, String), String> {
+        let res = self.backend.udp_receive(handle, max);
+        self.record("udp_receive", json!([handle.0, max]), res)
+    }
+    fn udp_addr(&self, handle: Handle) -> Result<String, String> {
+        self.record("udp_addr", json!([handle.0]), self.backend.udp_addr(handle))
+    }
+    fn udp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        let res = self.backend.udp_set_non_blocking(handle, non_blocking);
+        self.record("udp_set_non_blocking", json!([handle.0, non_blocking]), res)
+    }
+    fn udp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        let res = self.backend.udp_set_read_timeout(handle, timeout);
+        let args = json!([handle.0, timeout_json(timeout)]);
+        self.record("udp_set_read_timeout", args, res)
+    }
+    fn udp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        let res = self.backend.udp_set_write_timeout(handle, timeout);
+        let args = json!([handle.0, timeout_json(timeout)]);
+        self.record("udp_set_write_timeout", args, res)
+    }
+    fn ffi_load(&self, _path: &str) -> Result<Handle, String> {
+        Err(FFI_ERROR.into())
+    }
+    fn ffi_call(
+        &self,
+        _lib: Handle,
+        _sig: &FfiSignature,
+        _args: &mut [FfiValue],
+    ) -> Result<FfiValue, String> {
+        Err(FFI_ERROR.into())
+    }
+    fn close(&self, handle: Handle) -> Result<(), String> {
+        self.record("close", json!([handle.0]), self.backend.close(handle))
+    }
+    fn invoke(&self, path: &str) -> Result<(), String> {
+        self.record("invoke", json!([path]), self.backend.invoke(path))
+    }
+    fn run_command_inherit(&self, command: &str, args: &[&str]) -> Result<i32, String> {
+        let res = self.backend.run_command_inherit(command, args);
+        self.record("run_command_inherit", json!([command, args]), res)
+    }
+    fn run_command_capture(
+        &self,
+        command: &str,
+        args: &[&str],
+    ) -> Result<(i32, String, String), String> {
+        let res = self.backend.run_command_capture(command, args);
+        self.record("run_command_capture", json!([command, args]), res)
+    }
+    fn change_directory(&self, path: &str) -> Result<(), String> {
+        let res = self.backend.change_directory(path);
+        self.record("change_directory", json!([path]), res)
+    }
+    fn https_get(&self, request: &str, handle: Handle) -> Result<String, String> {
+        let res = self.backend.https_get(request, handle);
+        self.record("https_get", json!([request, handle.0]), res)
+    }
+}
+
+impl SysBackend for ReplaySys {
+    fn any(&self) -> &dyn Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn save_error_color(&self, error: &UiuaError) {
+        NativeSys.save_error_color(error)
+    }
+    fn print_str_stdout(&self, s: &str) -> Result<(), String> {
+        NativeSys.print_str_stdout(s)
+    }
+    fn print_str_stderr(&self, s: &str) -> Result<(), String> {
+        NativeSys.print_str_stderr(s)
+    }
+    fn print_str_trace(&self, s: &str) {
+        NativeSys.print_str_trace(s)
+    }
+    fn scan_line_stdin(&self) -> Result<Option<String>, String> {
+        self.replay("scan_line_stdin", json!([]))
+    }
+    fn set_raw_mode(&self, raw_mode: bool) -> Result<(), String> {
+        self.replay("set_raw_mode", json!([raw_mode]))
+    }
+    fn var(&self, name: &str) -> Option<String> {
+        self.replay("var", json!([name]))
+    }
+    fn term_size(&self) -> Result<(usize, usize), String> {
+        self.replay("term_size", json!([]))
+    }
+    fn file_exists(&self, path: &str) -> bool {
+        self.replay("file_exists", json!([path]))
+    }
+    fn list_dir(&self, path: &str) -> Result<Vec<String>, String> {
+        self.replay("list_dir", json!([path]))
+    }
+    fn is_file(&self, path: &str) -> Result<bool, String> {
+        self.replay("is_file", json!([path]))
+    }
+    fn delete(&self, path: &str) -> Result<(), String> {
+        self.replay("delete", json!([path]))
+    }
+    fn trash(&self, path: &str) -> Result<(), String> {
+        self.replay("trash", json!([path]))
+    }
+    fn read(&self, handle: Handle, count: usize) -> Result<Vec<u8>, String> {
+        self.replay("read", json!([handle.0, count]))
+    }
+    fn read_until(&self, handle: Handle, delim: &[u8]) -> Result<Vec<u8>, String> {
+        self.replay("read_until", json!([handle.0, bytes_json(delim)]))
+    }
+    fn write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        self.replay("write", json!([handle.0, bytes_json(contents)]))
+    }
+    fn create_file(&self, path: &str) -> Result<Handle, String> {
+        self.replay("create_file", json!([path]))
+    }
+    fn open_file(&self, path: &str) -> Result<Handle, String> {
+        self.replay("open_file", json!([path]))
+    }
+    fn file_read_all(&self, path: &str) -> Result<Vec<u8>, String> {
+        self.replay("file_read_all", json!([path]))
+    }
+    fn file_write_all(&self, path: &str, contents: &[u8]) -> Result<(), String> {
+        self.replay("file_write_all", json!([path, bytes_json(contents)]))
+    }
+    fn sleep(&self, seconds: f64) -> Result<(), String> {
+        self.replay("sleep", json!([f64_json(seconds)]))
+    }
+    fn now(&self) -> f64 {
+        self.replay("now", json!([]))
+    }
+    fn random_seed(&self) -> u64 {
+        self.replay("random_seed", json!([]))
+    }
+    fn show_image(&self, image: DynamicImage) -> Result<(), String> {
+        self.replay("show_image", json!([image.width(), image.height()]))
+    }
+    fn show_gif(&self, gif_bytes: Vec<u8>) -> Result<(), String> {
+        self.replay("show_gif", json!([gif_bytes.len()]))
+    }
+    fn play_audio(&self, wave_bytes: Vec<u8>) -> Result<(), String> {
+        self.replay("play_audio", json!([wave_bytes.len()]))
+    }
+    fn audio_sample_rate(&self) -> u32 {
+        self.replay("audio_sample_rate", json!([]))
+    }
+    fn stream_audio(&self, _f: AudioStreamFn) -> Result<(), String> {
+        self.replay("stream_audio", json!([]))
+    }
+    fn tcp_listen(&self, addr: &str) -> Result<Handle, String> {
+        self.replay("tcp_listen", json!([addr]))
+    }
+    fn tcp_accept(&self, handle: Handle) -> Result<Handle, String> {
+        self.replay("tcp_accept", json!([handle.0]))
+    }
+    fn tcp_connect(&self, addr: &str) -> Result<Handle, String> {
+        self.replay("tcp_connect", json!([addr]))
+    }
+    fn tcp_addr(&self, handle: Handle) -> Result<String, String> {
+        self.replay("tcp_addr", json!([handle.0]))
+    }
+    fn tcp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        self.replay("tcp_set_non_blocking", json!([handle.0, non_blocking]))
+    }
+    fn tcp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        let args = json!([handle.0, timeout_json(timeout)]);
+        self.replay("tcp_set_read_timeout", args)
+    }
+    fn tcp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        let args = json!([handle.0, timeout_json(timeout)]);
+        self.replay("tcp_set_write_timeout", args)
+    }
+    fn udp_bind(&self, addr: &str) -> Result<Handle, String> {
+        self.replay("udp_bind", json!([addr]))
+    }
+    fn udp_send(&self, handle: Handle, addr: &str, bytes: &[u8]) -> Result<(), String> {
+        self.replay("udp_send", json!([handle.0, addr, bytes_json(bytes)]))
+    }
+    fn udp_receive(&self, handle: Handle, max: usize) -> Result<(Vec<u8>, String), String> {
+        self.replay("udp_receive", json!([handle.0, max]))
+    }
+    fn udp_addr(&self, handle: Handle) -> Result<String, String> {
+        self.replay("udp_addr", json!([handle.0]))
+    }
+    fn udp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        self.replay("udp_set_non_blocking", json!([handle.0, non_blocking]))
+    }
+    fn udp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        let args = json!([handle.0, timeout_json(timeout)]);
+        self.replay("udp_set_read_timeout", args)
+    }
+    fn udp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        let args = json!([handle.0, timeout_json(timeout)]);
+        self.replay("udp_set_write_timeout", args)
+    }
+    fn ffi_load(&self, _path: &str) -> Result<Handle, String> {
+        Err(FFI_ERROR.into())
+    }
+    fn ffi_call(
+        &self,
+        _lib: Handle,
+        _sig: &FfiSignature,
+        _args: &mut [FfiValue],
+    ) -> Result<FfiValue, String> {
+        Err(FFI_ERROR.into())
+    }
+    fn close(&self, handle: Handle) -> Result<(), String> {
+        self.replay("close", json!([handle.0]))
+    }
+    fn invoke(&self, path: &str) -> Result<(), String> {
+        self.replay("invoke", json!([path]))
+    }
+    fn run_command_inherit(&self, command: &str, args: &[&str]) -> Result<i32, String> {
+        self.replay("run_command_inherit", json!([command, args]))
+    }
+    fn run_command_capture(
+        &self,
+        command: &str,
+        args: &[&str],
+    ) -> Result<(i32, String, String), String> {
+        self.replay("run_command_capture", json!([command, args]))
+    }
+    fn change_directory(&self, path: &str) -> Result<(), String> {
+        self.replay("change_directory", json!([path]))
+    }
+    fn https_get(&self, request: &str, handle: Handle) -> Result<String, String> {
+        self.replay("https_get", json!([request, handle.0]))
+    }
+}
+
+#[test]
+fn non_finite_floats() {
+    for n in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -0.0, 1.5] {
+        let line = json!({ "result": n.to_json() }).to_string();
+        let json: Json = serde_json::from_str(&line).unwrap();
+        let replayed = f64::from_json(&json["result"]).unwrap();
+        assert_eq!(replayed.to_bits(), n.to_bits());
+    }
+    let sys = RecordingSys::new(crate::MemoryFsSys::new());
+    _ = sys.sleep(f64::INFINITY);
+    let replay = ReplaySys::new(&sys.log()).unwrap();
+    _ = replay.sleep(f64::INFINITY);
+    replay.finish().unwrap();
+}
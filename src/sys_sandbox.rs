@@ -0,0 +1,679 @@
+use std::{
+    any::Any,
+    collections::HashSet,
+    env, fmt,
+    path::{Component, Path, PathBuf},
+    sync::atomic::{self, AtomicUsize},
+    time::Duration,
+};
+
+use image::DynamicImage;
+use parking_lot::Mutex;
+
+use crate::{AudioStreamFn, FfiSignature, FfiValue, Handle, NativeSys, SysBackend, UiuaError};
+
+/// A capability that a [`SandboxSys`] may be missing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Reading files and directories
+    FileRead,
+    /// Creating, writing, and deleting files and directories
+    FileWrite,
+    /// Changing the current directory
+    ChangeDirectory,
+    /// Invoking paths with the system's default program
+    Invoke,
+    /// Running commands
+    RunCommand,
+    /// Connecting TCP sockets
+    TcpConnect,
+    /// Binding TCP listeners
+    TcpListen,
+    /// Binding UDP sockets
+    UdpBind,
+    /// Sending UDP datagrams
+    UdpSend,
+    /// Reading environment variables
+    EnvVar,
+    /// Loading shared libraries and calling their functions
+    Ffi,
+    /// Using a stream handle that was not opened by the sandbox
+    ForeignHandle,
+    /// Opening more streams than the handle quota allows
+    HandleQuota,
+    /// Reading more bytes from streams than the read quota allows
+    ReadQuota,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capability::FileRead => write!(f, "file read"),
+            Capability::FileWrite => write!(f, "file write"),
+            Capability::ChangeDirectory => write!(f, "change directory"),
+            Capability::Invoke => write!(f, "invoke"),
+            Capability::RunCommand => write!(f, "run command"),
+            Capability::TcpConnect => write!(f, "tcp connect"),
+            Capability::TcpListen => write!(f, "tcp listen"),
+            Capability::UdpBind => write!(f, "udp bind"),
+            Capability::UdpSend => write!(f, "udp send"),
+            Capability::EnvVar => write!(f, "environment variable"),
+            Capability::Ffi => write!(f, "ffi"),
+            Capability::ForeignHandle => write!(f, "foreign handle"),
+            Capability::HandleQuota => write!(f, "handle quota"),
+            Capability::ReadQuota => write!(f, "read quota"),
+        }
+    }
+}
+
+/// An operation denied by a [`SandboxSys`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxError {
+    /// The capability that was missing
+    pub capability: Capability,
+    /// A description of the denied operation
+    pub detail: String,
+}
+
+impl fmt::Display for SandboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Sandbox denied {}: missing {} capability",
+            self.detail, self.capability
+        )
+    }
+}
+
+impl std::error::Error for SandboxError {}
+
+impl From<SandboxError> for String {
+    fn from(error: SandboxError) -> Self {
+        error.to_string()
+    }
+}
+
+/// A host and port pattern for network allowlists
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct NetRule {
+    /// `None` matches any host
+    host: Option<String>,
+    /// `None` matches any port
+    port: Option<u16>,
+}
+
+impl NetRule {
+    fn new(host: &str, port: Option<u16>) -> Self {
+        let host = (host != "*").then(|| normalize_host(host));
+        Self { host, port }
+    }
+    fn matches(&self, host: &str, port: u16) -> bool {
+        self.host.iter().all(|h| *h == normalize_host(host)) && self.port.iter().all(|&p| p == port)
+    }
+}
+
+fn normalize_host(host: &str) -> String {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_ascii_lowercase()
+}
+
+fn split_addr(addr: &str) -> Option<(&str, u16)> {
+    let (host, port) = addr.rsplit_once(':')?;
+    Some((host, port.parse().ok()?))
+}
+
+/// The set of capabilities granted to a [`SandboxSys`]
+///
+/// The default policy denies everything except standard IO and other
+/// operations that do not touch the filesystem, processes, or the network.
+#[derive(Debug, Clone, Default)]
+pub struct SandboxPolicy {
+    read_roots: Vec<PathBuf>,
+    write_roots: Vec<PathBuf>,
+    allowed_commands: HashSet<String>,
+    denied_commands: HashSet<String>,
+    allow_all_commands: bool,
+    tcp_connect: Vec<NetRule>,
+    tcp_listen: Vec<NetRule>,
+    udp_bind: Vec<NetRule>,
+    udp_send: Vec<NetRule>,
+    allowed_vars: HashSet<String>,
+    allow_all_vars: bool,
+    change_directory: bool,
+    invoke: bool,
+    max_handles: Option<usize>,
+    read_quota: Option<usize>,
+}
+
+impl SandboxPolicy {
+    /// Create a policy that denies everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Allow reading files and directories under a root directory
+    pub fn allow_read(mut self, root: impl Into<PathBuf>) -> Self {
+        self.read_roots.push(root.into());
+        self
+    }
+    /// Allow reading and writing files and directories under a root directory
+    pub fn allow_dir(mut self, root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        self.read_roots.push(root.clone());
+        self.write_roots.push(root);
+        self
+    }
+    /// Allow running a command
+    ///
+    /// The command must match exactly as it is passed to `&runi` or `&runc`.
+    pub fn allow_command(mut self, command: impl Into<String>) -> Self {
+        self.allowed_commands.insert(command.into());
+        self
+    }
+    /// Allow running any command that is not explicitly denied
+    pub fn allow_all_commands(mut self) -> Self {
+        self.allow_all_commands = true;
+        self
+    }
+    /// Deny running a command
+    ///
+    /// This matches both the full command and its file name, so denying `rm` also denies `/bin/rm`.
+    /// Denials take precedence over allowances.
+    pub fn deny_command(mut self, command: impl Into<String>) -> Self {
+        self.denied_commands.insert(command.into());
+        self
+    }
+    /// Allow connecting TCP sockets to a host and port
+    ///
+    /// A host of `"*"` matches any host, and a port of `None` matches any port.
+    pub fn allow_tcp_connect(mut self, host: &str, port: impl Into<Option<u16>>) -> Self {
+        self.tcp_connect.push(NetRule::new(host, port.into()));
+        self
+    }
+    /// Allow binding TCP listeners to a host and port
+    ///
+    /// A host of `"*"` matches any host, and a port of `None` matches any port.
+    pub fn allow_tcp_listen(mut self, host: &str, port: impl Into<Option<u16>>) -> Self {
+        self.tcp_listen.push(NetRule::new(host, port.into()));
+        self
+    }
+    /// Allow binding UDP sockets to a host and port
+    ///
+    /// A host of `"*"` matches any host, and a port of `None` matches any port.
+    pub fn allow_udp_bind(mut self, host: &str, port: impl Into<Option<u16>>) -> Self {
+        self.udp_bind.push(NetRule::new(host, port.into()));
+        self
+    }
+    /// Allow sending UDP datagrams to a host and port
+    ///
+    /// A host of `"*"` matches any host, and a port of `None` matches any port.
+    pub fn allow_udp_send(mut self, host: &str, port: impl Into<Option<u16>>) -> Self {
+        self.udp_send.push(NetRule::new(host, port.into()));
+        self
+    }
+    /// Allow reading an environment variable
+    pub fn allow_env_var(mut self, name: impl Into<String>) -> Self {
+        self.allowed_vars.insert(name.into());
+        self
+    }
+    /// Allow reading any environment variable
+    pub fn allow_all_env_vars(mut self) -> Self {
+        self.allow_all_vars = true;
+        self
+    }
+    /// Allow changing the current directory to a readable directory
+    pub fn allow_change_directory(mut self) -> Self {
+        self.change_directory = true;
+        self
+    }
+    /// Allow invoking paths with the system's default program
+    pub fn allow_invoke(mut self) -> Self {
+        self.invoke = true;
+        self
+    }
+    /// Limit the number of streams that may be open at once
+    pub fn max_handles(mut self, max_handles: usize) -> Self {
+        self.max_handles = Some(max_handles);
+        self
+    }
+    /// Limit the total number of bytes that may be read from streams
+    ///
+    /// This counts every byte read over the sandbox's lifetime, including bytes that
+    /// have since been freed. It does not limit how much memory arrays use. For that,
+    /// see [`Uiua::with_memory_limit`](crate::Uiua::with_memory_limit).
+    pub fn read_quota(mut self, bytes: usize) -> Self {
+        self.read_quota = Some(bytes);
+        self
+    }
+}
+
+/// A system backend that wraps [`NativeSys`] and only allows what its [`SandboxPolicy`] grants
+///
+/// Denied operations fail with a [`SandboxError`] message naming the missing [`Capability`].
+/// Denials are also recorded and can be retrieved with [`SandboxSys::take_denials`].
+///
+/// ```
+/// use uiua::*;
+///
+/// let policy = SandboxPolicy::new().allow_read("tests");
+/// let mut uiua = Uiua::with_backend(SandboxSys::new(policy));
+/// uiua.load_str("&fras \"tests/under.ua\"").unwrap();
+/// assert!(uiua.load_str("&runc \"ls\"").is_err());
+///
+/// let denials = uiua.downcast_backend::<SandboxSys>().unwrap().take_denials();
+/// assert_eq!(denials[0].capability, Capability::RunCommand);
+/// ```
+pub struct SandboxSys {
+    policy: SandboxPolicy,
+    handles: Mutex<HashSet<Handle>>,
+    bytes_read: AtomicUsize,
+    denials: Mutex<Vec<SandboxError>>,
+}
+
+impl SandboxSys {
+    /// Create a new sandboxed backend with the given policy
+    pub fn new(mut policy: SandboxPolicy) -> Self {
+        for root in policy.read_roots.iter_mut().chain(&mut policy.write_roots) {
+            *root = resolve_path(root);
+        }
+        Self {
+            policy,
+            handles: Mutex::new(HashSet::new()),
+            bytes_read: AtomicUsize::new(0),
+            denials: Mutex::new(Vec::new()),
+        }
+    }
+    /// Get the sandbox's policy
+    pub fn policy(&self) -> &SandboxPolicy {
+        &self.policy
+    }
+    /// Take all denials that have occurred so far
+    pub fn take_denials(&self) -> Vec<SandboxError> {
+        std::mem::take(&mut *self.denials.lock())
+    }
+    /// Get the number of bytes that have been read from streams
+    pub fn bytes_read(&self) -> usize {
+        self.bytes_read.load(atomic::Ordering::Relaxed)
+    }
+    fn deny(&self, capability: Capability, detail: impl Into<String>) -> String {
+        let error = SandboxError {
+            capability,
+            detail: detail.into(),
+        };
+        let message = error.to_string();
+        self.denials.lock().push(error);
+        message
+    }
+    fn check_path(&self, path: &str, write: bool) -> Result<(), String> {
+        let (roots, capability, verb) = if write {
+            (&self.policy.write_roots, Capability::FileWrite, "writing")
+        } else {
+            (&self.policy.read_roots, Capability::FileRead, "reading")
+        };
+        let resolved = resolve_path(Path::new(path));
+        if roots.iter().any(|root| resolved.starts_with(root)) {
+            Ok(())
+        } else {
+            Err(self.deny(capability, format!("{verb} {path:?}")))
+        }
+    }
+    fn check_handle(&self, handle: Handle) -> Result<(), String> {
+        if handle < Handle::FIRST_UNRESERVED || self.handles.lock().contains(&handle) {
+            Ok(())
+        } else {
+            Err(self.deny(
+                Capability::ForeignHandle,
+                format!("use of stream handle {}", handle.0),
+            ))
+        }
+    }
+    fn open_handle(&self, open: impl FnOnce() -> Result<Handle, String>) -> Result<Handle, String> {
+        let mut handles = self.handles.lock();
+        if let Some(max) = self.policy.max_handles {
+            if handles.len() >= max {
+                drop(handles);
+                return Err(self.deny(
+                    Capability::HandleQuota,
+                    format!("opening more than {max} streams"),
+                ));
+            }
+        }
+        let handle = open()?;
+        handles.insert(handle);
+        Ok(handle)
+    }
+    fn check_command(&self, command: &str) -> Result<(), String> {
+        let policy = &self.policy;
+        let file_name = Path::new(command)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        let denied = policy.denied_commands.contains(command)
+            || file_name.is_some_and(|name| policy.denied_commands.contains(&name));
+        if !denied && (policy.allow_all_commands || policy.allowed_commands.contains(command)) {
+            Ok(())
+        } else {
+            Err(self.deny(Capability::RunCommand, format!("running {command:?}")))
+        }
+    }
+    fn check_addr(&self, addr: &str, capability: Capability) -> Result<(), String> {
+        let (rules, verb) = match capability {
+            Capability::TcpListen => (&self.policy.tcp_listen, "listening on"),
+            Capability::TcpConnect => (&self.policy.tcp_connect, "connecting to"),
+            Capability::UdpBind => (&self.policy.udp_bind, "binding"),
+            Capability::UdpSend => (&self.policy.udp_send, "sending to"),
+            _ => unreachable!("{capability} is not a network capability"),
+        };
+        let allowed = split_addr(addr)
+            .is_some_and(|(host, port)| rules.iter().any(|rule| rule.matches(host, port)));
+        if allowed {
+            Ok(())
+        } else {
+            Err(self.deny(capability, format!("{verb} {addr:?}")))
+        }
+    }
+    fn account_read(&self, len: usize) -> Result<(), String> {
+        let total = self.bytes_read.fetch_add(len, atomic::Ordering::Relaxed) + len;
+        match self.policy.read_quota {
+            Some(quota) if total > quota => Err(self.deny(
+                Capability::ReadQuota,
+                format!("reading more than {quota} bytes"),
+            )),
+            _ => Ok(()),
+        }
+    }
+    fn remaining_quota(&self) -> usize {
+        self.policy
+            .read_quota
+            .map_or(usize::MAX, |quota| quota.saturating_sub(self.bytes_read()))
+    }
+}
+
+impl Default for SandboxSys {
+    fn default() -> Self {
+        Self::new(SandboxPolicy::default())
+    }
+}
+
+impl Drop for SandboxSys {
+    fn drop(&mut self) {
+        for handle in self.handles.get_mut().drain() {
+            _ = NativeSys.close(handle);
+        }
+    }
+}
+
+/// Make a path absolute and resolve `.`, `..`, and symlinks as far as the path exists
+///
+/// Each component is resolved before the next is applied, so a `..` after a
+/// symlink goes to the parent of the symlink's target, as it does for the OS.
+fn resolve_path(path: &Path) -> PathBuf {
+    let mut resolved = env::current_dir().unwrap_or_default();
+    if let Ok(canonical) = resolved.canonicalize() {
+        resolved = canonical;
+    }
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => resolved.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => {
+                resolved.push(name);
+                if let Ok(canonical) = resolved.canonicalize() {
+                    resolved = canonical;
+                }
+            }
+        }
+    }
+    resolved
+}
+
+impl SysBackend for SandboxSys {
+    fn any(&self) -> &dyn Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn save_error_color(&self, error: &UiuaError) {
+        NativeSys.save_error_color(error)
+    }
+    fn print_str_stdout(&self, s: &str) -> Result<(), String> {
+        NativeSys.print_str_stdout(s)
+    }
+    fn print_str_stderr(&self, s: &str) -> Result<(), String> {
+        NativeSys.print_str_stderr(s)
+    }
+    fn print_str_trace(&self, s: &str) {
+        NativeSys.print_str_trace(s)
+    }
+    fn scan_line_stdin(&self) -> Result<Option<String>, String> {
+        NativeSys.scan_line_stdin()
+    }
+    fn set_raw_mode(&self, raw_mode: bool) -> Result<(), String> {
+        NativeSys.set_raw_mode(raw_mode)
+    }
+    fn var(&self, name: &str) -> Option<String> {
+        if self.policy.allow_all_vars || self.policy.allowed_vars.contains(name) {
+            NativeSys.var(name)
+        } else {
+            self.deny(Capability::EnvVar, format!("reading {name:?}"));
+            None
+        }
+    }
+    fn term_size(&self) -> Result<(usize, usize), String> {
+        NativeSys.term_size()
+    }
+    fn file_exists(&self, path: &str) -> bool {
+        self.check_path(path, false).is_ok() && NativeSys.file_exists(path)
+    }
+    fn list_dir(&self, path: &str) -> Result<Vec<String>, String> {
+        self.check_path(path, false)?;
+        NativeSys.list_dir(path)
+    }
+    fn is_file(&self, path: &str) -> Result<bool, String> {
+        self.check_path(path, false)?;
+        NativeSys.is_file(path)
+    }
+    fn delete(&self, path: &str) -> Result<(), String> {
+        self.check_path(path, true)?;
+        NativeSys.delete(path)
+    }
+    fn trash(&self, path: &str) -> Result<(), String> {
+        self.check_path(path, true)?;
+        NativeSys.trash(path)
+    }
+    fn read(&self, handle: Handle, count: usize) -> Result<Vec<u8>, String> {
+        self.check_handle(handle)?;
+        // Read one byte past the quota so that exceeding it is detected
+        let count = count.min(self.remaining_quota().saturating_add(1));
+        let bytes = NativeSys.read(handle, count)?;
+        self.account_read(bytes.len())?;
+        Ok(bytes)
+    }
+    fn write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        self.check_handle(handle)?;
+        NativeSys.write(handle, contents)
+    }
+    fn create_file(&self, path: &str) -> Result<Handle, String> {
+        self.check_path(path, true)?;
+        self.open_handle(|| NativeSys.create_file(path))
+    }
+    fn open_file(&self, path: &str) -> Result<Handle, String> {
+        self.check_path(path, false)?;
+        self.open_handle(|| NativeSys.open_file(path))
+    }
+    fn sleep(&self, seconds: f64) -> Result<(), String> {
+        NativeSys.sleep(seconds)
+    }
+    fn show_image(&self, image: DynamicImage) -> Result<(), String> {
+        NativeSys.show_image(image)
+    }
+    fn show_gif(&self, gif_bytes: Vec<u8>) -> Result<(), String> {
+        NativeSys.show_gif(gif_bytes)
+    }
+    fn play_audio(&self, wave_bytes: Vec<u8>) -> Result<(), String> {
+        NativeSys.play_audio(wave_bytes)
+    }
+    fn audio_sample_rate(&self) -> u32 {
+        NativeSys.audio_sample_rate()
+    }
+    fn stream_audio(&self, f: AudioStreamFn) -> Result<(), String> {
+        NativeSys.stream_audio(f)
+    }
+    fn tcp_listen(&self, addr: &str) -> Result<Handle, String> {
+        self.check_addr(addr, Capability::TcpListen)?;
+        self.open_handle(|| NativeSys.tcp_listen(addr))
+    }
+    fn tcp_accept(&self, handle: Handle) -> Result<Handle, String> {
+        self.check_handle(handle)?;
+        self.open_handle(|| NativeSys.tcp_accept(handle))
+    }
+    fn tcp_connect(&self, addr: &str) -> Result<Handle, String> {
+        self.check_addr(addr, Capability::TcpConnect)?;
+        self.open_handle(|| NativeSys.tcp_connect(addr))
+    }
+    fn tcp_addr(&self, handle: Handle) -> Result<String, String> {
+        self.check_handle(handle)?;
+        NativeSys.tcp_addr(handle)
+    }
+    fn tcp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        self.check_handle(handle)?;
+        NativeSys.tcp_set_non_blocking(handle, non_blocking)
+    }
+    fn tcp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        self.check_handle(handle)?;
+        NativeSys.tcp_set_read_timeout(handle, timeout)
+    }
+    fn tcp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        self.check_handle(handle)?;
+        NativeSys.tcp_set_write_timeout(handle, timeout)
+    }
+    fn udp_bind(&self, addr: &str) -> Result<Handle, String> {
+        self.check_addr(addr, Capability::UdpBind)?;
+        self.open_handle(|| NativeSys.udp_bind(addr))
+    }
+    fn udp_send(&self, handle: Handle, addr: &str, bytes: &[u8]) -> Result<(), String> {
+        self.check_handle(handle)?;
+        self.check_addr(addr, Capability::UdpSend)?;
+        NativeSys.udp_send(handle, addr, bytes)
+    }
+    fn udp_receive(&self, handle: Handle, max: usize) -> Result<(Vec<u8>, String), String> {
+        self.check_handle(handle)?;
+        let (bytes, addr) = NativeSys.udp_receive(handle, max)?;
+        self.account_read(bytes.len())?;
+        Ok((bytes, addr))
+    }
+    fn udp_addr(&self, handle: Handle) -> Result<String, String> {
+        self.check_handle(handle)?;
+        NativeSys.udp_addr(handle)
+    }
+    fn udp_set_non_blocking(&self, handle: Handle, non_blocking: bool) -> Result<(), String> {
+        self.check_handle(handle)?;
+        NativeSys.udp_set_non_blocking(handle, non_blocking)
+    }
+    fn udp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        self.check_handle(handle)?;
+        NativeSys.udp_set_read_timeout(handle, timeout)
+    }
+    fn udp_set_write_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        self.check_handle(handle)?;
+        NativeSys.udp_set_write_timeout(handle, timeout)
+    }
+    fn close(&self, handle: Handle) -> Result<(), String> {
+        self.check_handle(handle)?;
+        self.handles.lock().remove(&handle);
+        NativeSys.close(handle)
+    }
+    fn ffi_load(&self, path: &str) -> Result<Handle, String> {
+        // Native code can do anything, so no policy grants this
+        Err(self.deny(Capability::Ffi, format!("loading library {path:?}")))
+    }
+    fn ffi_call(
+        &self,
+        _lib: Handle,
+        sig: &FfiSignature,
+        _args: &mut [FfiValue],
+    ) -> Result<FfiValue, String> {
+        Err(self.deny(Capability::Ffi, format!("calling `{}`", sig.name)))
+    }
+    fn invoke(&self, path: &str) -> Result<(), String> {
+        if !self.policy.invoke {
+            return Err(self.deny(Capability::Invoke, format!("invoking {path:?}")));
+        }
+        NativeSys.invoke(path)
+    }
+    fn run_command_inherit(&self, command: &str, args: &[&str]) -> Result<i32, String> {
+        self.check_command(command)?;
+        NativeSys.run_command_inherit(command, args)
+    }
+    fn run_command_capture(
+        &self,
+        command: &str,
+        args: &[&str],
+    ) -> Result<(i32, String, String), String> {
+        self.check_command(command)?;
+        NativeSys.run_command_capture(command, args)
+    }
+    fn change_directory(&self, path: &str) -> Result<(), String> {
+        if !self.policy.change_directory {
+            return Err(self.deny(
+                Capability::ChangeDirectory,
+                format!("changing directory to {path:?}"),
+            ));
+        }
+        self.check_path(path, false)?;
+        NativeSys.change_directory(path)
+    }
+    fn https_get(&self, request: &str, handle: Handle) -> Result<String, String> {
+        self.check_handle(handle)?;
+        let response = NativeSys.https_get(request, handle)?;
+        self.account_read(response.len())?;
+        Ok(response)
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_parent_escape() {
+    let dir = env::temp_dir().join(format!("uiua-sandbox-test-{}", std::process::id()));
+    let root = dir.join("root");
+    let outside = dir.join("outside").join("inner");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::create_dir_all(&outside).unwrap();
+    std::fs::write(dir.join("outside").join("secret.txt"), "secret").unwrap();
+    std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+
+    let sandbox = SandboxSys::new(SandboxPolicy::new().allow_read(&root));
+    // The OS resolves `link` before `..`, which leaves the root
+    let escape = root.join("link").join("..").join("secret.txt");
+    assert!(sandbox
+        .check_path(&escape.to_string_lossy(), false)
+        .is_err());
+    let back_inside = root.join("link").join("..").join("..").join("root");
+    assert!(sandbox
+        .check_path(&back_inside.to_string_lossy(), false)
+        .is_ok());
+    std::fs::write(root.join("file.txt"), "ok").unwrap();
+    let fine = root.join("sub").join("..").join("file.txt");
+    assert!(sandbox.check_path(&fine.to_string_lossy(), false).is_ok());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
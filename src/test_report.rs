@@ -0,0 +1,198 @@
+//! Test results and reports
+//!
+//! When run in [`RunMode::Test`](crate::RunMode::Test), every line with code in a
+//! test block is its own test case. A test block whose first line is a comment is
+//! instead a single test case named by that comment. A failing test case is
+//! recorded, and the rest of the tests still run.
+
+use std::fmt::Write;
+
+use serde_json::json;
+
+use crate::Report;
+
+/// The result of running a test case
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    /// The test's name
+    ///
+    /// This is the code of the test's line, or the comment that starts its block.
+    pub name: String,
+    /// The line the test starts on
+    pub line: usize,
+    /// How long the test took to run in seconds
+    pub duration: f64,
+    /// A report of why the test failed, if it did
+    pub failure: Option<Report>,
+}
+
+impl TestResult {
+    /// Check if the test passed
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+    fn failure_message(&self) -> Option<String> {
+        (self.failure.clone()).map(|report| report.color(false).to_string())
+    }
+}
+
+/// The results of running the tests in a file
+#[derive(Debug, Clone, Default)]
+pub struct TestReport {
+    /// The name of the file the tests are in
+    pub file: String,
+    /// The results of the file's tests, in order
+    pub results: Vec<TestResult>,
+}
+
+impl TestReport {
+    /// Create a new test report
+    pub fn new(file: impl Into<String>, results: Vec<TestResult>) -> Self {
+        TestReport {
+            file: file.into(),
+            results,
+        }
+    }
+    /// Get the number of tests that passed
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|res| res.passed()).count()
+    }
+    /// Get the number of tests that failed
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+    /// Get the total time spent running the tests in seconds
+    pub fn duration(&self) -> f64 {
+        self.results.iter().map(|res| res.duration).sum()
+    }
+    /// Format the report as JUnit XML
+    pub fn to_junit(&self) -> String {
+        let file = xml_escape(&self.file);
+        let (tests, failures, time) = (self.results.len(), self.failed(), self.duration());
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        _ = writeln!(
+            xml,
+            "<testsuites tests=\"{tests}\" failures=\"{failures}\" time=\"{time:.6}\">"
+        );
+        _ = writeln!(
+            xml,
+            "  <testsuite name=\"{file}\" tests=\"{tests}\" failures=\"{failures}\" \
+            errors=\"0\" skipped=\"0\" time=\"{time:.6}\">"
+        );
+        for res in &self.results {
+            _ = write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{file}\" file=\"{file}\" \
+                line=\"{}\" time=\"{:.6}\"",
+                xml_escape(&res.name),
+                res.line,
+                res.duration
+            );
+            if let Some(message) = res.failure_message() {
+                let summary = message.lines().next().unwrap_or_default();
+                _ = writeln!(xml, ">");
+                _ = writeln!(
+                    xml,
+                    "      <failure message=\"{}\">{}</failure>",
+                    xml_escape(summary),
+                    xml_escape(&message)
+                );
+                _ = writeln!(xml, "    </testcase>");
+            } else {
+                _ = writeln!(xml, "/>");
+            }
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+    /// Format the report as JSON
+    pub fn to_json(&self) -> String {
+        let cases: Vec<_> = (self.results.iter())
+            .map(|res| {
+                json!({
+                    "name": res.name,
+                    "line": res.line,
+                    "time": res.duration,
+                    "passed": res.passed(),
+                    "failure": res.failure_message(),
+                })
+            })
+            .collect();
+        let report = json!({
+            "file": self.file,
+            "tests": self.results.len(),
+            "passed": self.passed(),
+            "failed": self.failed(),
+            "time": self.duration(),
+            "cases": cases,
+        });
+        serde_json::to_string_pretty(&report).unwrap()
+    }
+}
+
+/// Escape text for use in XML, dropping characters XML cannot contain
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' | '\r' | '\t' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[test]
+fn test_results() {
+    use crate::{RunMode, Uiua};
+
+    let code = "\
+---
+⍤\"pass\" =2 +1 1
+⍤\"fail\" =3 +1 1
+# a comment
+⍤\"after\" 1
+---
+---
+# named <block>
+⍤\"block\" 1
+⍤\"stops\" 0
+⍤\"skipped\" 1
+---";
+    let mut env = Uiua::with_native_sys().with_mode(RunMode::Test);
+    env.load_str(code).unwrap();
+    let results = env.take_test_results();
+    let summary: Vec<_> = (results.iter())
+        .map(|res| (res.name.as_str(), res.line, res.passed()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("⍤\"pass\" =2 +1 1", 2, true),
+            ("⍤\"fail\" =3 +1 1", 3, false),
+            ("⍤\"after\" 1", 5, true),
+            ("named <block>", 8, false),
+        ]
+    );
+    let report = TestReport::new("test.ua", results);
+    assert_eq!((report.passed(), report.failed()), (2, 2));
+    let junit = report.to_junit();
+    assert!(junit.contains("<testcase name=\"named &lt;block&gt;\""));
+    assert!(junit.contains("<failure message=\"Error: stops\">"));
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["cases"][1]["passed"], false);
+
+    let mut env = Uiua::with_native_sys()
+        .with_mode(RunMode::Test)
+        .with_test_filter("block");
+    env.load_str(code).unwrap();
+    let results = env.take_test_results();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].name, "named <block>");
+}
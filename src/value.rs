@@ -7,6 +7,7 @@ use std::{
 };
 
 use ecow::EcoVec;
+use tinyvec::tiny_vec;
 
 use crate::{
     algorithm::{pervade::*, FillContext},
@@ -717,7 +718,9 @@ impl Value {
                 for n in take(&mut nums.data) {
                     bytes.push(n as u8);
                 }
-                *self = (take(&mut nums.shape), bytes).into();
+                let mut bytes = Array::new(take(&mut nums.shape), bytes);
+                bytes.map_keys = take(&mut nums.map_keys);
+                *self = bytes.into();
             }
         }
     }
@@ -792,6 +795,155 @@ impl FromIterator<usize> for Value {
     }
 }
 
+impl Value {
+    /// Encode the value as JSON
+    ///
+    /// Number arrays become numbers or nested arrays of numbers, with `NaN` becoming `null`.
+    /// Character lists become strings, and higher-rank character arrays become nested arrays of strings.
+    /// Box lists become arrays of their contents, and 2-column box tables with string keys become objects.
+    ///
+    /// Other box arrays and infinite numbers cannot be encoded.
+    ///
+    /// ```
+    /// use uiua::*;
+    ///
+    /// let value = Value::from_json(r#"{"name": "Uiua", "tags": ["array", "stack"]}"#).unwrap();
+    /// assert_eq!(value.shape(), [2, 2]);
+    /// assert_eq!(value.to_json().unwrap(), r#"{"name":"Uiua","tags":["array","stack"]}"#);
+    /// ```
+    pub fn to_json(&self) -> Result<String, String> {
+        Ok(self.to_json_value()?.to_string())
+    }
+    /// Decode a value from JSON
+    ///
+    /// Arrays whose items decode to non-box values of the same type and shape become a single array.
+    /// Other arrays become box lists.
+    /// Objects become 2-column box tables of keys and values.
+    /// `true` and `false` become `1` and `0`, and `null` becomes `NaN`.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let json: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        Ok(Self::from_json_value(json))
+    }
+    fn to_json_value(&self) -> Result<serde_json::Value, String> {
+        use serde_json::Value as Json;
+        Ok(match self {
+            Value::Num(arr) => {
+                let items: Vec<Json> = arr
+                    .data
+                    .iter()
+                    .map(|&n| json_number(n))
+                    .collect::<Result<_, _>>()?;
+                json_nest(&arr.shape, &mut items.into_iter())
+            }
+            Value::Byte(arr) => json_nest(&arr.shape, &mut arr.data.iter().map(|&b| Json::from(b))),
+            Value::Char(arr) => {
+                if arr.rank() == 0 {
+                    return Ok(Json::String(arr.data[0].to_string()));
+                }
+                let row_len = arr.shape[arr.rank() - 1];
+                let mut strings = (0..arr.shape[..arr.rank() - 1].iter().product()).map(|i| {
+                    Json::String(arr.data[i * row_len..(i + 1) * row_len].iter().collect())
+                });
+                json_nest(&arr.shape[..arr.rank() - 1], &mut strings)
+            }
+            Value::Box(arr) => match arr.shape.as_slice() {
+                [] => arr.data[0].0.to_json_value()?,
+                [_] => Json::Array(
+                    arr.data
+                        .iter()
+                        .map(|bx| bx.0.to_json_value())
+                        .collect::<Result<_, _>>()?,
+                ),
+                [_, 2] => {
+                    let mut object = serde_json::Map::new();
+                    for pair in arr.data.chunks_exact(2) {
+                        let key = match &pair[0].0 {
+                            Value::Char(key) if key.rank() == 1 => {
+                                key.data.iter().collect::<String>()
+                            }
+                            key => {
+                                return Err(format!(
+                                    "JSON object keys must be strings, \
+                                    but a key is a {} array of shape {}",
+                                    key.type_name(),
+                                    key.format_shape()
+                                ))
+                            }
+                        };
+                        if object.contains_key(&key) {
+                            return Err(format!("Duplicate JSON object key {key:?}"));
+                        }
+                        object.insert(key, pair[1].0.to_json_value()?);
+                    }
+                    Json::Object(object)
+                }
+                _ => {
+                    return Err(format!(
+                        "Only box lists and 2-column box tables can be encoded as JSON, \
+                        but the box array has shape {}",
+                        arr.format_shape()
+                    ))
+                }
+            },
+        })
+    }
+    fn from_json_value(json: serde_json::Value) -> Self {
+        use serde_json::Value as Json;
+        match json {
+            Json::Null => f64::NAN.into(),
+            Json::Bool(b) => (b as u8 as f64).into(),
+            Json::Number(n) => n.as_f64().unwrap_or(f64::NAN).into(),
+            Json::String(s) => s.into(),
+            Json::Array(items) => {
+                let rows: Vec<Value> = items.into_iter().map(Self::from_json_value).collect();
+                let uniform = rows.windows(2).all(|pair| {
+                    pair[0].type_name() == pair[1].type_name() && pair[0].shape() == pair[1].shape()
+                });
+                if uniform && !matches!(rows.first(), Some(Value::Box(_))) {
+                    Value::from_row_values_infallible(rows)
+                } else {
+                    rows.into_iter().map(Boxed).collect()
+                }
+            }
+            Json::Object(object) => {
+                let len = object.len();
+                let data: EcoVec<Boxed> = object
+                    .into_iter()
+                    .flat_map(|(key, value)| {
+                        [Boxed(key.into()), Boxed(Self::from_json_value(value))]
+                    })
+                    .collect();
+                Array::new(tiny_vec![len, 2], data).into()
+            }
+        }
+    }
+}
+
+fn json_number(n: f64) -> Result<serde_json::Value, String> {
+    Ok(if n.is_nan() {
+        serde_json::Value::Null
+    } else if n.is_infinite() {
+        return Err("Infinity cannot be encoded as JSON".into());
+    } else if n.fract() == 0.0 && n.abs() <= (1u64 << f64::MANTISSA_DIGITS) as f64 {
+        (n as i64).into()
+    } else {
+        n.into()
+    })
+}
+
+/// Nest JSON items into arrays according to a shape
+fn json_nest(
+    shape: &[usize],
+    items: &mut dyn Iterator<Item = serde_json::Value>,
+) -> serde_json::Value {
+    match shape {
+        [] => items.next().unwrap(),
+        [len, rest @ ..] => {
+            serde_json::Value::Array((0..*len).map(|_| json_nest(rest, items)).collect())
+        }
+    }
+}
+
 impl From<bool> for Value {
     fn from(b: bool) -> Self {
         Value::from(b as u8)